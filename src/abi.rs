@@ -0,0 +1,263 @@
+//! The AArch64 procedure call standard
+//!
+//! Describes which registers carry arguments and results and which survive a
+//! call, for the base AAPCS64 and the Apple and Windows variants.
+//!
+//! Only the low 64 bits of `V8`-`V15` are callee-saved, so `D8` is preserved
+//! across a call while `Q8` is not.
+//!
+//! # Example
+//! ```
+//! use bad64::Reg;
+//! use bad64::abi::Abi;
+//!
+//! let abi = Abi::Aapcs64;
+//!
+//! assert_eq!(abi.argument_regs()[0], Reg::X0);
+//! assert_eq!(abi.indirect_result_reg(), Reg::X8);
+//!
+//! assert!(abi.preserves(Reg::W19));
+//! assert!(abi.preserves(Reg::D8));
+//! assert!(!abi.preserves(Reg::Q8));
+//! assert!(!abi.preserves(Reg::X9));
+//!
+//! // x18 is a temporary, unless the platform reserves it
+//! assert!(!Abi::Aapcs64.preserves(Reg::X18));
+//! assert!(Abi::Apple.preserves(Reg::X18));
+//! ```
+
+use num_traits::ToPrimitive;
+
+use crate::Reg;
+
+/// A procedure call standard variant
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Abi {
+    /// The base procedure call standard, as used by Linux
+    #[default]
+    Aapcs64,
+    /// Apple arm64 (macOS, iOS)
+    Apple,
+    /// Windows arm64
+    Windows,
+}
+
+static ARGUMENTS: [Reg; 8] = [
+    Reg::X0,
+    Reg::X1,
+    Reg::X2,
+    Reg::X3,
+    Reg::X4,
+    Reg::X5,
+    Reg::X6,
+    Reg::X7,
+];
+
+static FP_ARGUMENTS: [Reg; 8] = [
+    Reg::V0,
+    Reg::V1,
+    Reg::V2,
+    Reg::V3,
+    Reg::V4,
+    Reg::V5,
+    Reg::V6,
+    Reg::V7,
+];
+
+static CALLEE_SAVED: [Reg; 19] = [
+    Reg::X19,
+    Reg::X20,
+    Reg::X21,
+    Reg::X22,
+    Reg::X23,
+    Reg::X24,
+    Reg::X25,
+    Reg::X26,
+    Reg::X27,
+    Reg::X28,
+    Reg::X29,
+    Reg::D8,
+    Reg::D9,
+    Reg::D10,
+    Reg::D11,
+    Reg::D12,
+    Reg::D13,
+    Reg::D14,
+    Reg::D15,
+];
+
+static CALLER_SAVED: [Reg; 52] = [
+    Reg::X0,
+    Reg::X1,
+    Reg::X2,
+    Reg::X3,
+    Reg::X4,
+    Reg::X5,
+    Reg::X6,
+    Reg::X7,
+    Reg::X8,
+    Reg::X9,
+    Reg::X10,
+    Reg::X11,
+    Reg::X12,
+    Reg::X13,
+    Reg::X14,
+    Reg::X15,
+    Reg::X16,
+    Reg::X17,
+    Reg::X30,
+    Reg::V0,
+    Reg::V1,
+    Reg::V2,
+    Reg::V3,
+    Reg::V4,
+    Reg::V5,
+    Reg::V6,
+    Reg::V7,
+    Reg::V8,
+    Reg::V9,
+    Reg::V10,
+    Reg::V11,
+    Reg::V12,
+    Reg::V13,
+    Reg::V14,
+    Reg::V15,
+    Reg::V16,
+    Reg::V17,
+    Reg::V18,
+    Reg::V19,
+    Reg::V20,
+    Reg::V21,
+    Reg::V22,
+    Reg::V23,
+    Reg::V24,
+    Reg::V25,
+    Reg::V26,
+    Reg::V27,
+    Reg::V28,
+    Reg::V29,
+    Reg::V30,
+    Reg::V31,
+    Reg::X18,
+];
+
+impl Abi {
+    /// Returns the general purpose argument registers, `X0`-`X7`
+    pub fn argument_regs(&self) -> &'static [Reg] {
+        &ARGUMENTS
+    }
+
+    /// Returns the floating point and SIMD argument registers, `V0`-`V7`
+    pub fn fp_argument_regs(&self) -> &'static [Reg] {
+        &FP_ARGUMENTS
+    }
+
+    /// Returns the general purpose result registers, `X0` and `X1`
+    ///
+    /// Larger results are returned in memory at the address passed in
+    /// [`Abi::indirect_result_reg`].
+    pub fn return_regs(&self) -> &'static [Reg] {
+        &ARGUMENTS[..2]
+    }
+
+    /// Returns the floating point and SIMD result registers, `V0`-`V3`
+    ///
+    /// Four registers are needed for the largest homogeneous aggregates.
+    pub fn fp_return_regs(&self) -> &'static [Reg] {
+        &FP_ARGUMENTS[..4]
+    }
+
+    /// Returns the register holding the address of a result returned in
+    /// memory, `X8`
+    pub fn indirect_result_reg(&self) -> Reg {
+        Reg::X8
+    }
+
+    /// Returns the platform register, `X18`
+    ///
+    /// Apple reserves it and Windows uses it for the thread environment
+    /// block, so code must not modify it there. Otherwise it is a temporary.
+    pub fn platform_reg(&self) -> Reg {
+        Reg::X18
+    }
+
+    /// Returns if code must leave a register alone
+    pub fn is_reserved(&self, reg: Reg) -> bool {
+        match self {
+            Abi::Aapcs64 => false,
+            Abi::Apple | Abi::Windows => general(reg) == Some(18),
+        }
+    }
+
+    /// Returns the callee-saved registers
+    ///
+    /// These are `X19`-`X29` and `D8`-`D15`; the upper halves of `V8`-`V15`
+    /// are not preserved. `SP` is also preserved.
+    pub fn callee_saved_regs(&self) -> &'static [Reg] {
+        &CALLEE_SAVED
+    }
+
+    /// Returns the registers a call may modify, in whole or in part
+    ///
+    /// This includes the link register, and `V8`-`V15` as their upper halves
+    /// are not preserved. `X18` is only included for [`Abi::Aapcs64`].
+    pub fn caller_saved_regs(&self) -> &'static [Reg] {
+        match self {
+            Abi::Aapcs64 => &CALLER_SAVED,
+            Abi::Apple | Abi::Windows => &CALLER_SAVED[..CALLER_SAVED.len() - 1],
+        }
+    }
+
+    /// Returns if the whole of a register has the same value after a call
+    ///
+    /// Registers that are not general purpose or SIMD registers, such as the
+    /// zero registers or SVE registers, are not preserved.
+    pub fn preserves(&self, reg: Reg) -> bool {
+        if matches!(reg, Reg::SP | Reg::WSP) {
+            return true;
+        }
+
+        if let Some(n) = general(reg) {
+            return (19..=29).contains(&n) || self.is_reserved(reg);
+        }
+
+        // only the low 64 bits of v8-v15 are preserved
+        [
+            (Reg::B0, Reg::B31),
+            (Reg::H0, Reg::H31),
+            (Reg::S0, Reg::S31),
+            (Reg::D0, Reg::D31),
+        ]
+        .into_iter()
+        .find_map(|(first, last)| range(reg, first, last))
+        .is_some_and(|n| (8..=15).contains(&n))
+    }
+
+    /// Returns if variadic arguments are passed on the stack rather than in
+    /// registers
+    pub fn variadic_args_on_stack(&self) -> bool {
+        *self == Abi::Apple
+    }
+
+    /// Returns if floating point variadic arguments are passed in general
+    /// purpose registers
+    pub fn variadic_fp_in_general_regs(&self) -> bool {
+        *self == Abi::Windows
+    }
+}
+
+fn range(reg: Reg, first: Reg, last: Reg) -> Option<u32> {
+    let r = reg.to_u32().unwrap();
+    let first = first.to_u32().unwrap();
+
+    if r >= first && r <= last.to_u32().unwrap() {
+        Some(r - first)
+    } else {
+        None
+    }
+}
+
+/// Returns the number of an `X` or `W` register
+fn general(reg: Reg) -> Option<u32> {
+    range(reg, Reg::X0, Reg::X30).or_else(|| range(reg, Reg::W0, Reg::W30))
+}
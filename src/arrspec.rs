@@ -6,6 +6,7 @@ use crate::Reg;
 
 /// An arrangement specifier
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArrSpec {
     Full(Option<u32>),
     TwoDoubles(Option<u32>),
@@ -0,0 +1,406 @@
+//! A command line disassembler
+//!
+//! Disassembles raw files, hex strings, and the sections of ELF and Mach-O
+//! files, as text or JSON Lines.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::process;
+
+use bad64::elf::{Elf, SymbolKind};
+use bad64::jsonl::Record;
+use bad64::macho::MachO;
+use bad64::{Imm, Operand, Walk};
+
+const USAGE: &str = "\
+Usage: bad64 [options] <file>
+       bad64 [options] -x <hex>
+
+Disassembles a raw file, or the executable sections of an ELF or Mach-O file.
+
+Options:
+  -x, --hex <hex>         disassemble hex encoded bytes instead of a file
+  -b, --base <addr>       address of the first byte of raw input [0]
+  -s, --start <addr>      start disassembling at this address
+  -l, --length <n>        disassemble at most this many bytes
+  -S, --section <name>    disassemble this section instead, such as .text or
+                          __TEXT,__text
+  -f, --format <format>   text or json [text]
+      --bytes             show the bytes of each instruction
+      --no-symbols        do not label addresses with symbols
+  -h, --help              show this help";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+struct Options {
+    input: Option<String>,
+    hex: Option<String>,
+    base: u64,
+    start: Option<u64>,
+    length: Option<u64>,
+    section: Option<String>,
+    format: Format,
+    bytes: bool,
+    symbols: bool,
+}
+
+/// Bytes to disassemble, the ranges within holding data, and its symbols
+struct Region<'a> {
+    name: String,
+    address: u64,
+    data: &'a [u8],
+    skip: Vec<Range<u64>>,
+    symbols: BTreeMap<u64, String>,
+}
+
+impl Region<'_> {
+    fn contains(&self, address: u64) -> bool {
+        (self.address..self.address + self.data.len() as u64).contains(&address)
+    }
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("bad64: {}\n\n{}", msg, USAGE);
+    process::exit(1);
+}
+
+fn number(arg: &str) -> u64 {
+    let n = match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
+
+    n.unwrap_or_else(|_| usage(&format!("bad number: {}", arg)))
+}
+
+fn hex(arg: &str) -> Vec<u8> {
+    let digits: Vec<u8> = arg
+        .trim_start_matches("0x")
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+
+    digits
+        .chunks(2)
+        .map(|d| {
+            std::str::from_utf8(d)
+                .ok()
+                .filter(|d| d.len() == 2)
+                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                .unwrap_or_else(|| usage(&format!("bad hex: {}", arg)))
+        })
+        .collect()
+}
+
+fn parse_args() -> Options {
+    let mut opts = Options {
+        input: None,
+        hex: None,
+        base: 0,
+        start: None,
+        length: None,
+        section: None,
+        format: Format::Text,
+        bytes: false,
+        symbols: true,
+    };
+
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| usage(&format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "-x" | "--hex" => opts.hex = Some(value()),
+            "-b" | "--base" => opts.base = number(&value()),
+            "-s" | "--start" => opts.start = Some(number(&value())),
+            "-l" | "--length" => opts.length = Some(number(&value())),
+            "-S" | "--section" => opts.section = Some(value()),
+            "-f" | "--format" => {
+                opts.format = match value().as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    f => usage(&format!("unknown format: {}", f)),
+                }
+            }
+            "--bytes" => opts.bytes = true,
+            "--no-symbols" => opts.symbols = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => usage(&format!("unknown option: {}", arg)),
+            _ if opts.input.is_none() => opts.input = Some(arg),
+            _ => usage("more than one input"),
+        }
+    }
+
+    if opts.input.is_some() == opts.hex.is_some() {
+        usage("expected either a file or --hex");
+    }
+
+    opts
+}
+
+fn elf_regions<'a>(elf: &Elf<'a>, section: Option<&str>) -> Vec<Region<'a>> {
+    let sections: Vec<_> = match section {
+        Some(name) => match elf.section_by_name(name) {
+            Some(s) => vec![s],
+            None => usage(&format!("no section {}", name)),
+        },
+        None => elf.executable_sections().collect(),
+    };
+
+    sections
+        .into_iter()
+        .map(|s| {
+            let symbols = elf
+                .symbols()
+                .iter()
+                .filter(|sym| sym.section == Some(s.index))
+                .filter(|sym| !sym.name.is_empty() && !sym.name.starts_with('$'))
+                .filter(|sym| {
+                    matches!(
+                        sym.kind,
+                        SymbolKind::Func | SymbolKind::NoType | SymbolKind::Object
+                    )
+                })
+                .map(|sym| (sym.address, sym.name.to_string()))
+                .collect();
+
+            Region {
+                name: s.name.to_string(),
+                address: s.address,
+                data: s.data,
+                skip: elf.literals(s).to_vec(),
+                symbols,
+            }
+        })
+        .collect()
+}
+
+fn macho_regions<'a>(macho: &MachO<'a>, section: Option<&str>) -> Vec<Region<'a>> {
+    let sections: Vec<_> = match section {
+        Some(name) => {
+            let (segment, name) = name
+                .split_once(',')
+                .unwrap_or_else(|| usage("Mach-O sections are named segment,section"));
+
+            match macho.section_by_name(segment, name) {
+                Some(s) => vec![s],
+                None => usage(&format!("no section {},{}", segment, name)),
+            }
+        }
+        None => macho.executable_sections().collect(),
+    };
+
+    sections
+        .into_iter()
+        .map(|s| {
+            let index = macho.sections().iter().position(|x| std::ptr::eq(x, s));
+
+            let mut symbols: BTreeMap<_, _> = macho
+                .symbols()
+                .iter()
+                .filter(|sym| Some(sym.section) == index)
+                .map(|sym| (sym.address, sym.name.to_string()))
+                .collect();
+
+            let end = s.address + s.data.len() as u64;
+            for (address, name) in macho.stubs() {
+                if (s.address..end).contains(address) {
+                    symbols.insert(*address, format!("{}$stub", name));
+                }
+            }
+
+            Region {
+                name: format!("{},{}", s.segment, s.name),
+                address: s.address,
+                data: s.data,
+                skip: macho.data_in_code().to_vec(),
+                symbols,
+            }
+        })
+        .collect()
+}
+
+/// Returns a symbol and offset for an address, from the closest symbol before
+fn symbolize(symbols: &BTreeMap<u64, String>, address: u64) -> Option<String> {
+    let (start, name) = symbols.range(..=address).next_back()?;
+
+    Some(match address - start {
+        0 => format!("<{}>", name),
+        off => format!("<{}+{:#x}>", name, off),
+    })
+}
+
+fn print_region(
+    out: &mut impl Write,
+    region: &Region,
+    symbols: &BTreeMap<u64, String>,
+    opts: &Options,
+) -> io::Result<()> {
+    if opts.format == Format::Text {
+        writeln!(
+            out,
+            "Disassembly of {} ({:#x} bytes at {:#x}):",
+            region.name,
+            region.data.len(),
+            region.address
+        )?;
+    }
+
+    for (_, address, bytes, result) in Walk::new(region.data, region.address) {
+        let data = region.skip.iter().any(|r| r.contains(&address));
+
+        if opts.format == Format::Json {
+            let record = match data {
+                true => Record::data(address, bytes),
+                false => Record::new(bytes, &result),
+            };
+
+            serde_json::to_writer(&mut *out, &record)?;
+            writeln!(out)?;
+
+            continue;
+        }
+
+        if let Some(name) = region.symbols.get(&address) {
+            writeln!(out, "\n{:016x} <{}>:", address, name)?;
+        }
+
+        write!(out, "{:>8x}:  ", address)?;
+
+        if opts.bytes {
+            let hex: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            write!(out, "{:<12} ", hex.join(" "))?;
+        }
+
+        match result {
+            _ if data && bytes.len() == 4 => {
+                let word = u32::from_le_bytes(bytes.try_into().unwrap());
+                writeln!(out, ".word {:#010x}", word)?;
+            }
+            Ok(ins) => {
+                write!(out, "{}", ins)?;
+
+                let target = ins.operands().iter().find_map(|o| match o {
+                    Operand::Label(Imm::Unsigned(target)) => Some(*target),
+                    _ => None,
+                });
+
+                // branches within a region use its symbols, which
+                // relocatable objects need as every section starts at zero
+                let label = target.and_then(|t| {
+                    if region.contains(t) {
+                        symbolize(&region.symbols, t)
+                    } else {
+                        symbolize(symbols, t)
+                    }
+                });
+
+                if let Some(label) = label {
+                    write!(out, " {}", label)?;
+                }
+
+                writeln!(out)?;
+            }
+            Err(e) => writeln!(out, "(bad: {})", e.reason())?,
+        }
+    }
+
+    if opts.format == Format::Text {
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn run(opts: &Options) -> io::Result<()> {
+    let buf = match (&opts.input, &opts.hex) {
+        (_, Some(h)) => hex(h),
+        (Some(path), _) => fs::read(path)?,
+        _ => unreachable!(),
+    };
+
+    let elf = Elf::parse(&buf).ok();
+    let macho = MachO::parse(&buf).ok();
+
+    let mut regions = match (&elf, &macho) {
+        (Some(elf), _) => elf_regions(elf, opts.section.as_deref()),
+        (_, Some(macho)) => macho_regions(macho, opts.section.as_deref()),
+        _ if opts.section.is_some() => usage("--section needs an ELF or Mach-O file"),
+        _ => vec![Region {
+            name: opts.input.clone().unwrap_or_else(|| "hex".to_string()),
+            address: opts.base,
+            data: &buf,
+            skip: Vec::new(),
+            symbols: BTreeMap::new(),
+        }],
+    };
+
+    if !opts.symbols {
+        for r in &mut regions {
+            r.symbols.clear();
+        }
+    }
+
+    let mut symbols = BTreeMap::new();
+    for r in &regions {
+        for (address, name) in &r.symbols {
+            symbols.entry(*address).or_insert_with(|| name.clone());
+        }
+    }
+
+    // cut the regions down to the requested address range
+    if opts.start.is_some() || opts.length.is_some() {
+        let start = opts
+            .start
+            .unwrap_or(regions.first().map_or(0, |r| r.address));
+        let end = opts
+            .length
+            .map_or(u64::MAX, |len| start.saturating_add(len));
+
+        for r in &mut regions {
+            let lo = start.clamp(r.address, r.address + r.data.len() as u64);
+            let hi = end.clamp(lo, r.address + r.data.len() as u64);
+
+            r.data = &r.data[(lo - r.address) as usize..(hi - r.address) as usize];
+            r.address = lo;
+        }
+
+        regions.retain(|r| !r.data.is_empty());
+    }
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    for region in &regions {
+        print_region(&mut out, region, &symbols, opts)?;
+    }
+
+    out.flush()
+}
+
+fn main() {
+    let opts = parse_args();
+
+    match run(&opts) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("bad64: {}", e);
+            process::exit(1);
+        }
+    }
+}
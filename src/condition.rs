@@ -2,8 +2,13 @@ use bad64_sys::*;
 
 use core::fmt;
 
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::{Nzcv, Op};
+
 /// A condition
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(non_camel_case_types)]
 pub enum Condition {
@@ -25,25 +30,190 @@ pub enum Condition {
     NV = Condition_COND_NV as u32,
 }
 
-impl fmt::Display for Condition {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Condition {
+    /// Returns the logical inverse of the condition
+    ///
+    /// This flips the low bit of the encoding, so `AL` and `NV` invert to each
+    /// other even though both always hold.
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Condition;
+    ///
+    /// assert_eq!(Condition::EQ.invert(), Condition::NE);
+    /// assert_eq!(Condition::HI.invert(), Condition::LS);
+    /// assert_eq!(Condition::LT.invert(), Condition::GE);
+    /// ```
+    pub fn invert(&self) -> Self {
+        Self::from_u32(self.to_u32().unwrap() ^ 1).unwrap()
+    }
+
+    /// Evaluate the condition against flag values
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Condition;
+    ///
+    /// // unsigned higher: c set and z clear
+    /// assert!(Condition::HI.evaluate(false, false, true, false));
+    /// assert!(!Condition::HI.evaluate(false, true, true, false));
+    ///
+    /// // signed greater or equal: n == v
+    /// assert!(Condition::GE.evaluate(true, false, false, true));
+    /// assert!(!Condition::GE.evaluate(true, false, false, false));
+    ///
+    /// // nv always holds on AArch64
+    /// assert!(Condition::NV.evaluate(false, false, false, false));
+    /// ```
+    pub fn evaluate(&self, n: bool, z: bool, c: bool, v: bool) -> bool {
+        match *self {
+            Self::EQ => z,
+            Self::NE => !z,
+            Self::CS => c,
+            Self::CC => !c,
+            Self::MI => n,
+            Self::PL => !n,
+            Self::VS => v,
+            Self::VC => !v,
+            Self::HI => c && !z,
+            Self::LS => !c || z,
+            Self::GE => n == v,
+            Self::LT => n != v,
+            Self::GT => !z && n == v,
+            Self::LE => z || n != v,
+            Self::AL | Self::NV => true,
+        }
+    }
+
+    /// Evaluate the condition against an NZCV value
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{Condition, Nzcv};
+    ///
+    /// assert!(Condition::EQ.holds(Nzcv::Z));
+    /// assert!(!Condition::EQ.holds(Nzcv::C));
+    /// ```
+    pub fn holds(&self, nzcv: Nzcv) -> bool {
+        self.evaluate(nzcv.n(), nzcv.z(), nzcv.c(), nzcv.v())
+    }
+
+    /// Returns the flags the condition depends on
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{Condition, Nzcv};
+    ///
+    /// assert_eq!(Condition::EQ.flags_read(), Nzcv::Z);
+    /// assert_eq!(Condition::HI.flags_read(), Nzcv::Z | Nzcv::C);
+    /// assert_eq!(Condition::GT.flags_read(), Nzcv::N | Nzcv::Z | Nzcv::V);
+    /// assert_eq!(Condition::AL.flags_read(), Nzcv::NONE);
+    /// ```
+    pub fn flags_read(&self) -> Nzcv {
+        match *self {
+            Self::EQ | Self::NE => Nzcv::Z,
+            Self::CS | Self::CC => Nzcv::C,
+            Self::MI | Self::PL => Nzcv::N,
+            Self::VS | Self::VC => Nzcv::V,
+            Self::HI | Self::LS => Nzcv::Z | Nzcv::C,
+            Self::GE | Self::LT => Nzcv::N | Nzcv::V,
+            Self::GT | Self::LE => Nzcv::N | Nzcv::Z | Nzcv::V,
+            Self::AL | Self::NV => Nzcv::NONE,
+        }
+    }
+
+    /// Returns the canonical condition name
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Condition;
+    ///
+    /// assert_eq!(Condition::CS.name(), "cs");
+    /// assert_eq!(Condition::CC.name(), "cc");
+    /// ```
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::EQ => "eq",
+            Self::NE => "ne",
+            Self::CS => "cs",
+            Self::CC => "cc",
+            Self::MI => "mi",
+            Self::PL => "pl",
+            Self::VS => "vs",
+            Self::VC => "vc",
+            Self::HI => "hi",
+            Self::LS => "ls",
+            Self::GE => "ge",
+            Self::LT => "lt",
+            Self::GT => "gt",
+            Self::LE => "le",
+            Self::AL => "al",
+            Self::NV => "nv",
+        }
+    }
+
+    /// Returns the alternate condition name, if there is one
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Condition;
+    ///
+    /// assert_eq!(Condition::CS.alias(), Some("hs"));
+    /// assert_eq!(Condition::CC.alias(), Some("lo"));
+    /// assert_eq!(Condition::EQ.alias(), None);
+    /// ```
+    pub fn alias(&self) -> Option<&'static str> {
         match *self {
-            Self::EQ => write!(f, "eq"),
-            Self::NE => write!(f, "ne"),
-            Self::CS => write!(f, "cs"),
-            Self::CC => write!(f, "cc"),
-            Self::MI => write!(f, "mi"),
-            Self::PL => write!(f, "pl"),
-            Self::VS => write!(f, "vs"),
-            Self::VC => write!(f, "vc"),
-            Self::HI => write!(f, "hi"),
-            Self::LS => write!(f, "ls"),
-            Self::GE => write!(f, "ge"),
-            Self::LT => write!(f, "lt"),
-            Self::GT => write!(f, "gt"),
-            Self::LE => write!(f, "le"),
-            Self::AL => write!(f, "al"),
-            Self::NV => write!(f, "nv"),
+            Self::CS => Some("hs"),
+            Self::CC => Some("lo"),
+            _ => None,
         }
     }
+
+    /// Look up a condition by its canonical or alternate name
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Condition;
+    ///
+    /// assert_eq!(Condition::from_name("hs"), Some(Condition::CS));
+    /// assert_eq!(Condition::from_name("LO"), Some(Condition::CC));
+    /// assert_eq!(Condition::from_name("gt"), Some(Condition::GT));
+    /// assert_eq!(Condition::from_name("xx"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..16).filter_map(Self::from_u32).find(|c| {
+            name.eq_ignore_ascii_case(c.name())
+                || c.alias().is_some_and(|a| name.eq_ignore_ascii_case(a))
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Returns the condition tested by a `B.cond` operation
+pub(crate) fn branch_condition(op: Op) -> Option<Condition> {
+    match op {
+        Op::B_EQ => Some(Condition::EQ),
+        Op::B_NE => Some(Condition::NE),
+        Op::B_CS => Some(Condition::CS),
+        Op::B_CC => Some(Condition::CC),
+        Op::B_MI => Some(Condition::MI),
+        Op::B_PL => Some(Condition::PL),
+        Op::B_VS => Some(Condition::VS),
+        Op::B_VC => Some(Condition::VC),
+        Op::B_HI => Some(Condition::HI),
+        Op::B_LS => Some(Condition::LS),
+        Op::B_GE => Some(Condition::GE),
+        Op::B_LT => Some(Condition::LT),
+        Op::B_GT => Some(Condition::GT),
+        Op::B_LE => Some(Condition::LE),
+        Op::B_AL => Some(Condition::AL),
+        Op::B_NV => Some(Condition::NV),
+        _ => None,
+    }
 }
@@ -0,0 +1,838 @@
+//! Register liveness and def-use chains
+//!
+//! Registers are tracked as whole architectural registers: `W` registers
+//! alias their `X` register, `B`/`H`/`S`/`D`/`Q` registers alias their `V`
+//! register and the zero registers are never read or written. Instruction
+//! register accesses come from [`crate::lift`]. Instructions the lifter does
+//! not support are assumed to read every register operand, and to maybe
+//! write their first one.
+//!
+//! Calls follow the graph's procedure call standard (see [`crate::abi`]):
+//! they read the argument registers and clobber the caller-saved ones. At a
+//! return the result and callee-saved registers are live, at any other exit
+//! from the graph (such as an indirect branch or tail call) every register is.
+//!
+//! # Example
+//! ```
+//! use bad64::{decode, Reg};
+//! use bad64::dataflow::{Cfg, Liveness};
+//!
+//! let ins = [
+//!     decode(0x8b010000, 0x1000).unwrap(), // add x0, x0, x1
+//!     decode(0x34000042, 0x1004).unwrap(), // cbz w2, 0x100c
+//!     decode(0xd2800020, 0x1008).unwrap(), // mov x0, #1
+//!     decode(0xd65f03c0, 0x100c).unwrap(), // ret
+//! ];
+//!
+//! let cfg = Cfg::build(&ins);
+//! assert_eq!(cfg.blocks().len(), 3);
+//! assert_eq!(cfg.blocks()[0].successors, [2, 1]);
+//!
+//! let live = Liveness::analyze(&cfg);
+//! assert!(live.live_in(0).contains(Reg::W1));
+//! assert!(!live.live_in(1).contains(Reg::X0));
+//!
+//! // x9 is free to use as a scratch register before the add
+//! let before = live.live_at(&cfg, 0x1000).unwrap();
+//! assert!(before.contains(Reg::X2));
+//! assert!(!before.contains(Reg::X9));
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::ops::{BitAnd, BitOr, Not, Sub};
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::abi::Abi;
+use crate::lift::{JumpKind, Lifter, Stmt};
+use crate::{Instruction, NextPc, Op, Operand, Reg, next_pcs};
+
+const SP: u32 = 31;
+const V0: u32 = 32;
+
+/// A set of general purpose and SIMD registers
+///
+/// Registers are stored by their widest alias, so `W0` and `X0` are the same
+/// member, as are `S0` and `V0`. The zero registers are never members.
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub struct RegSet(u64);
+
+impl RegSet {
+    /// No registers
+    pub const NONE: Self = Self(0);
+    /// Every tracked register: `X0`-`X30`, `SP` and `V0`-`V31`
+    pub const ALL: Self = Self(!0);
+
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::NONE
+    }
+
+    /// Adds a register, returning `false` if it is not tracked
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Reg;
+    /// use bad64::dataflow::RegSet;
+    ///
+    /// let mut set = RegSet::new();
+    ///
+    /// assert!(set.insert(Reg::W3));
+    /// assert!(set.insert(Reg::D8));
+    /// assert!(!set.insert(Reg::XZR));
+    ///
+    /// assert!(set.contains(Reg::X3));
+    /// assert!(set.contains(Reg::Q8));
+    /// assert_eq!(set.to_string(), "{x3, v8}");
+    /// ```
+    pub fn insert(&mut self, reg: Reg) -> bool {
+        match unit(reg) {
+            Some(n) => {
+                self.0 |= 1 << n;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes a register
+    pub fn remove(&mut self, reg: Reg) {
+        if let Some(n) = unit(reg) {
+            self.0 &= !(1 << n);
+        }
+    }
+
+    /// Returns if the register, or a register it aliases, is in the set
+    pub fn contains(&self, reg: Reg) -> bool {
+        unit(reg).is_some_and(|n| self.0 & (1 << n) != 0)
+    }
+
+    /// Returns if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the number of registers in the set
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns the registers in the set, as `X`, `SP` or `V` registers
+    pub fn iter(&self) -> impl Iterator<Item = Reg> + '_ {
+        let bits = self.0;
+
+        (0..64).filter(move |n| bits & (1 << n) != 0).map(unit_reg)
+    }
+
+    fn units(&self) -> impl Iterator<Item = u32> {
+        let bits = self.0;
+
+        (0..64).filter(move |n| bits & (1 << n) != 0)
+    }
+}
+
+impl FromIterator<Reg> for RegSet {
+    fn from_iter<I: IntoIterator<Item = Reg>>(iter: I) -> Self {
+        let mut set = Self::new();
+
+        for reg in iter {
+            set.insert(reg);
+        }
+
+        set
+    }
+}
+
+impl BitOr for RegSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for RegSet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Sub for RegSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl Not for RegSet {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
+
+impl fmt::Display for RegSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (n, reg) in self.iter().enumerate() {
+            if n != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", reg)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Debug for RegSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RegSet({})", self)
+    }
+}
+
+/// The registers an instruction reads and writes
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Access {
+    /// Registers read
+    pub uses: RegSet,
+    /// Registers written
+    pub defs: RegSet,
+    /// If `false`, the registers in `defs` may not be written, so their
+    /// previous values may survive
+    pub exact: bool,
+}
+
+impl Access {
+    /// Returns the registers an instruction reads and writes
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Reg};
+    /// use bad64::dataflow::Access;
+    ///
+    /// // ldp w0, w1, [x2], #8
+    /// let access = Access::of(&decode(0x28c10440, 0x1000).unwrap());
+    ///
+    /// assert_eq!(access.uses.to_string(), "{x2}");
+    /// assert_eq!(access.defs.to_string(), "{x0, x1, x2}");
+    /// assert!(access.exact);
+    /// ```
+    pub fn of(ins: &Instruction) -> Self {
+        Self::with_abi(ins, Abi::default())
+    }
+
+    /// Returns the registers an instruction reads and writes, with calls
+    /// following a specific procedure call standard
+    pub fn with_abi(ins: &Instruction, abi: Abi) -> Self {
+        Self::with_lifter(ins, &mut Lifter::new(), abi)
+    }
+
+    fn with_lifter(ins: &Instruction, lifter: &mut Lifter, abi: Abi) -> Self {
+        lifter.clear();
+
+        let stmts = match lifter.lift(ins) {
+            Ok(stmts) => stmts,
+            Err(_) => return Self::from_operands(ins),
+        };
+
+        let mut access = Self {
+            exact: true,
+            ..Self::default()
+        };
+
+        for stmt in stmts {
+            match *stmt {
+                Stmt::ReadReg { reg, .. } => {
+                    access.uses.insert(reg);
+                }
+                Stmt::WriteReg { reg, .. } => {
+                    access.defs.insert(reg);
+                }
+                Stmt::Jump {
+                    kind: JumpKind::Call,
+                    ..
+                } => {
+                    access.uses = access.uses | arguments(abi);
+                    access.defs = access.defs | clobbers(abi);
+                }
+                // system calls have their own conventions
+                Stmt::Trap { .. } => {
+                    access.uses = RegSet::ALL;
+                    access.exact = false;
+                }
+                _ => (),
+            }
+        }
+
+        access
+    }
+
+    fn from_operands(ins: &Instruction) -> Self {
+        let mut access = Self::default();
+
+        for o in ins.operands() {
+            match *o {
+                Operand::Reg { reg, .. }
+                | Operand::ShiftReg { reg, .. }
+                | Operand::QualReg { reg, .. }
+                | Operand::MemReg(reg)
+                | Operand::MemOffset { reg, .. }
+                | Operand::MemPreIdx { reg, .. }
+                | Operand::MemPostIdxImm { reg, .. } => {
+                    access.uses.insert(reg);
+                }
+                Operand::MultiReg { regs, .. } => {
+                    access.uses = access.uses | regs.into_iter().flatten().collect();
+                }
+                Operand::MemPostIdxReg(regs) | Operand::MemExt { regs, .. } => {
+                    access.uses = access.uses | regs.into_iter().collect();
+                }
+                _ => (),
+            }
+        }
+
+        match ins.operands().first() {
+            Some(Operand::Reg { reg, .. }) => {
+                access.defs.insert(*reg);
+            }
+            Some(Operand::MultiReg { regs, .. }) => {
+                access.defs = regs.iter().flatten().copied().collect();
+            }
+            _ => (),
+        }
+
+        access
+    }
+}
+
+/// Registers a call may read: the argument and indirect result registers
+fn arguments(abi: Abi) -> RegSet {
+    abi.argument_regs()
+        .iter()
+        .chain(abi.fp_argument_regs())
+        .copied()
+        .chain([abi.indirect_result_reg()])
+        .collect()
+}
+
+/// Registers a call overwrites entirely
+///
+/// `V8`-`V15` keep their low halves, so a call does not kill them.
+fn clobbers(abi: Abi) -> RegSet {
+    let caller: RegSet = abi.caller_saved_regs().iter().copied().collect();
+    let callee: RegSet = abi.callee_saved_regs().iter().copied().collect();
+
+    caller - callee
+}
+
+/// Registers live at a return: results and everything the caller expects
+/// preserved
+fn return_live(abi: Abi) -> RegSet {
+    let mut live: RegSet = abi
+        .return_regs()
+        .iter()
+        .chain(abi.fp_return_regs())
+        .chain(abi.callee_saved_regs())
+        .copied()
+        .chain([Reg::SP])
+        .collect();
+
+    if abi.is_reserved(abi.platform_reg()) {
+        live.insert(abi.platform_reg());
+    }
+
+    live
+}
+
+/// A basic block
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Block<'a> {
+    /// The block's instructions, in execution order
+    pub instructions: &'a [Instruction],
+    /// Indices of the successor blocks
+    pub successors: Vec<usize>,
+    /// If execution may also continue outside the function, by a branch out
+    /// of it or off its end
+    pub exits: bool,
+}
+
+impl Block<'_> {
+    /// Returns the address of the first instruction
+    pub fn address(&self) -> Option<u64> {
+        self.instructions.first().map(|i| i.address())
+    }
+
+    fn returns(&self) -> bool {
+        self.instructions.last().is_some_and(|i| {
+            matches!(
+                i.op(),
+                Op::RET
+                    | Op::RETAA
+                    | Op::RETAB
+                    | Op::RETAASPPC
+                    | Op::RETAASPPCR
+                    | Op::RETABSPPC
+                    | Op::RETABSPPCR
+            )
+        })
+    }
+}
+
+/// A control flow graph of a single function
+///
+/// Block 0 is the entry block.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cfg<'a> {
+    blocks: Vec<Block<'a>>,
+    abi: Abi,
+}
+
+impl<'a> Cfg<'a> {
+    /// Create a graph from existing blocks
+    pub fn new(blocks: Vec<Block<'a>>) -> Self {
+        Self {
+            blocks,
+            abi: Abi::default(),
+        }
+    }
+
+    /// Sets the procedure call standard the function follows
+    ///
+    /// This decides the registers calls read and clobber, and the registers
+    /// live at a return. The default is [`Abi::Aapcs64`].
+    pub fn with_abi(mut self, abi: Abi) -> Self {
+        self.abi = abi;
+        self
+    }
+
+    /// Returns the procedure call standard the function follows
+    pub fn abi(&self) -> Abi {
+        self.abi
+    }
+
+    /// Build the graph of a function from its instructions, in address order
+    ///
+    /// Blocks are split at direct branch targets within the function. Calls
+    /// fall through, branches through a register have no successors, and
+    /// branches leaving the function mark their block as exiting.
+    pub fn build(ins: &'a [Instruction]) -> Self {
+        let index = |addr: u64| ins.binary_search_by_key(&addr, |i| i.address()).ok();
+
+        let mut leader = vec![false; ins.len()];
+        if let Some(first) = leader.first_mut() {
+            *first = true;
+        }
+
+        let all: Vec<Vec<u64>> = ins.iter().map(successors).collect();
+        let targets: Vec<Vec<usize>> = all
+            .iter()
+            .map(|s| s.iter().filter_map(|a| index(*a)).collect())
+            .collect();
+
+        for (n, i) in ins.iter().enumerate() {
+            if !ends_block(i) {
+                continue;
+            }
+
+            if let Some(next) = leader.get_mut(n + 1) {
+                *next = true;
+            }
+
+            for t in &targets[n] {
+                leader[*t] = true;
+            }
+        }
+
+        let starts: Vec<usize> = (0..ins.len()).filter(|n| leader[*n]).collect();
+        let block_of = |n: usize| starts.partition_point(|s| *s <= n) - 1;
+
+        let blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(b, start)| {
+                let end = starts.get(b + 1).copied().unwrap_or(ins.len());
+                let last = end - 1;
+
+                let mut successors: Vec<usize> =
+                    targets[last].iter().map(|t| block_of(*t)).collect();
+                successors.dedup();
+
+                Block {
+                    instructions: &ins[*start..end],
+                    successors,
+                    exits: targets[last].len() < all[last].len(),
+                }
+            })
+            .collect();
+
+        Self::new(blocks)
+    }
+
+    /// Returns the blocks
+    pub fn blocks(&self) -> &[Block<'a>] {
+        &self.blocks
+    }
+
+    /// Returns the index of the block containing an instruction address
+    pub fn block_at(&self, address: u64) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|b| b.instructions.iter().any(|i| i.address() == address))
+    }
+}
+
+fn is_call(op: Op) -> bool {
+    matches!(
+        op,
+        Op::BL | Op::BLR | Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ
+    )
+}
+
+/// Returns the addresses execution may continue at within the function
+fn successors(ins: &Instruction) -> Vec<u64> {
+    if is_call(ins.op()) {
+        return vec![ins.address().wrapping_add(4)];
+    }
+
+    next_pcs(ins, &())
+        .iter()
+        .filter_map(|pc| match *pc {
+            NextPc::Fallthrough(a) | NextPc::Taken(a) => Some(a),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ends_block(ins: &Instruction) -> bool {
+    if is_call(ins.op()) {
+        return false;
+    }
+
+    let fallthrough = NextPc::Fallthrough(ins.address().wrapping_add(4));
+
+    next_pcs(ins, &()).as_slice() != [fallthrough]
+}
+
+/// Per block register liveness
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Liveness {
+    live_in: Vec<RegSet>,
+    live_out: Vec<RegSet>,
+}
+
+impl Liveness {
+    /// Compute the registers live on entry to and exit from each block
+    pub fn analyze(cfg: &Cfg) -> Self {
+        let mut lifter = Lifter::new();
+        let blocks = cfg.blocks();
+
+        // per block uses before any def, and definite defs
+        let summary: Vec<(RegSet, RegSet)> = blocks
+            .iter()
+            .map(|b| {
+                b.instructions
+                    .iter()
+                    .rev()
+                    .fold((RegSet::NONE, RegSet::NONE), |(uses, defs), i| {
+                        let a = Access::with_lifter(i, &mut lifter, cfg.abi);
+                        let kills = kills(&a);
+
+                        ((uses - kills) | a.uses, defs | kills)
+                    })
+            })
+            .collect();
+
+        let mut live_in = vec![RegSet::NONE; blocks.len()];
+        let mut live_out = vec![RegSet::NONE; blocks.len()];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for n in (0..blocks.len()).rev() {
+                let b = &blocks[n];
+
+                // nothing is known of the code outside the function
+                let out = if b.exits {
+                    RegSet::ALL
+                } else if !b.successors.is_empty() {
+                    b.successors
+                        .iter()
+                        .fold(RegSet::NONE, |acc, s| acc | live_in[*s])
+                } else if b.returns() {
+                    return_live(cfg.abi)
+                } else {
+                    RegSet::ALL
+                };
+
+                let (uses, defs) = summary[n];
+                let inp = (out - defs) | uses;
+
+                if out != live_out[n] || inp != live_in[n] {
+                    live_out[n] = out;
+                    live_in[n] = inp;
+                    changed = true;
+                }
+            }
+        }
+
+        Self { live_in, live_out }
+    }
+
+    /// Returns the registers live on entry to a block
+    pub fn live_in(&self, block: usize) -> RegSet {
+        self.live_in[block]
+    }
+
+    /// Returns the registers live on exit from a block
+    pub fn live_out(&self, block: usize) -> RegSet {
+        self.live_out[block]
+    }
+
+    /// Returns the registers live immediately before an instruction
+    ///
+    /// Registers not in the set can be freely overwritten at that point.
+    pub fn live_at(&self, cfg: &Cfg, address: u64) -> Option<RegSet> {
+        let n = cfg.block_at(address)?;
+        let mut lifter = Lifter::new();
+        let mut live = self.live_out[n];
+
+        for i in cfg.blocks()[n].instructions.iter().rev() {
+            let a = Access::with_lifter(i, &mut lifter, cfg.abi);
+            live = (live - kills(&a)) | a.uses;
+
+            if i.address() == address {
+                break;
+            }
+        }
+
+        Some(live)
+    }
+}
+
+fn kills(access: &Access) -> RegSet {
+    match access.exact {
+        true => access.defs,
+        false => RegSet::NONE,
+    }
+}
+
+/// Where a register value was defined
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Def {
+    /// The value on entry to the function
+    Entry,
+    /// The instruction at this address
+    At(u64),
+}
+
+/// Def-use chains, computed from reaching definitions
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DefUse {
+    // (use address, register unit) -> reaching definitions
+    reaching: BTreeMap<(u64, u32), Vec<Def>>,
+    // (definition, register unit) -> use addresses
+    uses: BTreeMap<(Def, u32), Vec<u64>>,
+}
+
+impl DefUse {
+    /// Compute the def-use chains of a function
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Reg};
+    /// use bad64::dataflow::{Cfg, Def, DefUse};
+    ///
+    /// let ins = [
+    ///     decode(0x34000042, 0x1000).unwrap(), // cbz w2, 0x1008
+    ///     decode(0xd2800020, 0x1004).unwrap(), // mov x0, #1
+    ///     decode(0x8b010000, 0x1008).unwrap(), // add x0, x0, x1
+    ///     decode(0xd65f03c0, 0x100c).unwrap(), // ret
+    /// ];
+    ///
+    /// let cfg = Cfg::build(&ins);
+    /// let chains = DefUse::analyze(&cfg);
+    ///
+    /// assert_eq!(chains.reaching(0x1008, Reg::X0), [Def::Entry, Def::At(0x1004)]);
+    /// assert_eq!(chains.reaching(0x1008, Reg::W1), [Def::Entry]);
+    /// assert_eq!(chains.uses(Def::At(0x1004), Reg::X0), [0x1008]);
+    /// assert_eq!(chains.uses(Def::Entry, Reg::X1), [0x1008]);
+    /// ```
+    pub fn analyze(cfg: &Cfg) -> Self {
+        let mut lifter = Lifter::new();
+        let blocks = cfg.blocks();
+
+        let accesses: Vec<Vec<Access>> = blocks
+            .iter()
+            .map(|b| {
+                b.instructions
+                    .iter()
+                    .map(|i| Access::with_lifter(i, &mut lifter, cfg.abi))
+                    .collect()
+            })
+            .collect();
+
+        // reaching definitions per register unit on block entry
+        let empty: Vec<Vec<Def>> = vec![Vec::new(); 64];
+        let mut entry = vec![empty.clone(); blocks.len()];
+        if let Some(first) = entry.first_mut() {
+            *first = vec![vec![Def::Entry]; 64];
+        }
+
+        let mut work: Vec<usize> = (0..blocks.len()).rev().collect();
+
+        while let Some(n) = work.pop() {
+            let mut state = entry[n].clone();
+
+            for (i, a) in blocks[n].instructions.iter().zip(&accesses[n]) {
+                transfer(&mut state, i, a);
+            }
+
+            for s in &blocks[n].successors {
+                if merge(&mut entry[*s], &state) && !work.contains(s) {
+                    work.push(*s);
+                }
+            }
+        }
+
+        let mut chains = Self::default();
+
+        for (n, b) in blocks.iter().enumerate() {
+            let mut state = entry[n].clone();
+
+            for (i, a) in b.instructions.iter().zip(&accesses[n]) {
+                for u in a.uses.units() {
+                    let defs = &state[u as usize];
+
+                    chains
+                        .reaching
+                        .entry((i.address(), u))
+                        .or_default()
+                        .extend(defs.iter().copied());
+
+                    for d in defs {
+                        chains.uses.entry((*d, u)).or_default().push(i.address());
+                    }
+                }
+
+                transfer(&mut state, i, a);
+            }
+        }
+
+        for v in chains.reaching.values_mut() {
+            v.sort();
+            v.dedup();
+        }
+
+        for v in chains.uses.values_mut() {
+            v.sort();
+            v.dedup();
+        }
+
+        chains
+    }
+
+    /// Returns the definitions of a register that reach its use at an address
+    pub fn reaching(&self, address: u64, reg: Reg) -> &[Def] {
+        unit(reg)
+            .and_then(|u| self.reaching.get(&(address, u)))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the addresses of the instructions using a definition
+    pub fn uses(&self, def: Def, reg: Reg) -> &[u64] {
+        unit(reg)
+            .and_then(|u| self.uses.get(&(def, u)))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+fn transfer(state: &mut [Vec<Def>], ins: &Instruction, access: &Access) {
+    for d in access.defs.units() {
+        let defs = &mut state[d as usize];
+
+        if access.exact {
+            defs.clear();
+        }
+
+        let def = Def::At(ins.address());
+        if !defs.contains(&def) {
+            defs.push(def);
+        }
+    }
+}
+
+/// Unions `from` into `into`, returning if anything changed
+fn merge(into: &mut [Vec<Def>], from: &[Vec<Def>]) -> bool {
+    let mut changed = false;
+
+    for (into, from) in into.iter_mut().zip(from) {
+        for d in from {
+            if !into.contains(d) {
+                into.push(*d);
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+fn range(reg: Reg, first: Reg, last: Reg) -> Option<u32> {
+    let r = reg.to_u32().unwrap();
+    let first = first.to_u32().unwrap();
+
+    if r >= first && r <= last.to_u32().unwrap() {
+        Some(r - first)
+    } else {
+        None
+    }
+}
+
+/// Returns the set bit tracking a register
+fn unit(reg: Reg) -> Option<u32> {
+    match reg {
+        Reg::SP | Reg::WSP => return Some(SP),
+        Reg::XZR | Reg::WZR => return None,
+        _ => (),
+    }
+
+    [(Reg::X0, Reg::X30), (Reg::W0, Reg::W30)]
+        .into_iter()
+        .find_map(|(first, last)| range(reg, first, last))
+        .or_else(|| {
+            [
+                (Reg::V0, Reg::V31),
+                (Reg::B0, Reg::B31),
+                (Reg::H0, Reg::H31),
+                (Reg::S0, Reg::S31),
+                (Reg::D0, Reg::D31),
+                (Reg::Q0, Reg::Q31),
+            ]
+            .into_iter()
+            .find_map(|(first, last)| range(reg, first, last))
+            .map(|n| V0 + n)
+        })
+}
+
+fn unit_reg(n: u32) -> Reg {
+    let (base, n) = match n {
+        SP => return Reg::SP,
+        n if n < SP => (Reg::X0, n),
+        n => (Reg::V0, n - V0),
+    };
+
+    Reg::from_u32(base.to_u32().unwrap() + n).unwrap()
+}
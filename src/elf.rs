@@ -0,0 +1,487 @@
+//! ELF loading
+//!
+//! A minimal reader for 64-bit AArch64 ELF files, in either byte order,
+//! which finds the executable sections and functions to pass to
+//! [`crate::disasm`]. Literal pools marked with `$d` mapping symbols are
+//! skipped, with `$x` marking the return to code, or returned as data by the
+//! `sweep` methods.
+//!
+//! Only what disassembly needs is parsed: section headers, and symbols from
+//! `.symtab` and `.dynsym`. Addresses are virtual addresses, or section
+//! offsets in relocatable objects.
+//!
+//! # Example
+//! ```no_run
+//! use bad64::elf::Elf;
+//!
+//! let buf = std::fs::read("a.out").unwrap();
+//! let elf = Elf::parse(&buf).unwrap();
+//!
+//! for func in elf.functions() {
+//!     println!("{}:", func.name);
+//!
+//!     for ins in func.disasm().filter_map(Result::ok) {
+//!         println!("    {:x}: {}", ins.address(), ins);
+//!     }
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::hardening::Audit;
+use crate::reader::{self, Truncated, string};
+use crate::sweep::{Item, sweep};
+use crate::{DecodeError, Instruction, disasm_skipping};
+
+const EM_AARCH64: u16 = 183;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+
+const SHF_EXECINSTR: u64 = 0x4;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+
+/// ELF parsing errors
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum ElfError {
+    /// The file does not start with the ELF magic
+    BadMagic,
+    /// The file is not a 64-bit ELF
+    BadClass,
+    /// The byte order is not little- or big-endian
+    BadEncoding,
+    /// The file is not for AArch64
+    BadMachine(u16),
+    /// A header or table extends past the end of the file
+    Truncated,
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ElfError::BadMagic => write!(f, "Bad magic"),
+            ElfError::BadClass => write!(f, "Not a 64-bit ELF"),
+            ElfError::BadEncoding => write!(f, "Bad data encoding"),
+            ElfError::BadMachine(m) => write!(f, "Bad machine: {}", m),
+            ElfError::Truncated => write!(f, "Truncated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ElfError {}
+
+impl Truncated for ElfError {
+    const TRUNCATED: Self = ElfError::Truncated;
+}
+
+type Reader<'a> = reader::Reader<'a, ElfError>;
+
+/// Byte order of the file's data
+///
+/// Instructions are always little-endian, even in big-endian files.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// A section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Section<'a> {
+    /// Index in the section header table
+    pub index: usize,
+    pub name: &'a str,
+    /// The section type (`sh_type`)
+    pub kind: u32,
+    /// The section flags (`sh_flags`)
+    pub flags: u64,
+    pub address: u64,
+    /// The section contents, empty for `SHT_NOBITS` sections
+    pub data: &'a [u8],
+}
+
+impl Section<'_> {
+    /// Returns if the section contains instructions
+    pub fn is_executable(&self) -> bool {
+        self.flags & SHF_EXECINSTR != 0
+    }
+
+    /// Returns the address after the section's contents
+    fn end(&self) -> u64 {
+        self.address.saturating_add(self.data.len() as u64)
+    }
+}
+
+/// A symbol type (`STT_*`)
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum SymbolKind {
+    NoType,
+    Object,
+    Func,
+    Section,
+    File,
+    Other(u8),
+}
+
+impl From<u8> for SymbolKind {
+    fn from(info: u8) -> Self {
+        match info & 0xf {
+            0 => SymbolKind::NoType,
+            1 => SymbolKind::Object,
+            2 => SymbolKind::Func,
+            3 => SymbolKind::Section,
+            4 => SymbolKind::File,
+            other => SymbolKind::Other(other),
+        }
+    }
+}
+
+/// A symbol
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub kind: SymbolKind,
+    pub address: u64,
+    pub size: u64,
+    /// Index of the section the symbol is defined in
+    pub section: Option<usize>,
+}
+
+/// A function, with the ranges of its literal pools
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Function<'a> {
+    pub name: &'a str,
+    pub address: u64,
+    /// The function's bytes
+    pub data: &'a [u8],
+    /// Index of the section containing the function
+    pub section: usize,
+    literals: Vec<Range<u64>>,
+}
+
+impl<'a> Function<'a> {
+    /// Disassemble the function, skipping literal pools
+    pub fn disasm(&self) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
+        disasm_skipping(self.data, self.address, &self.literals)
+    }
+
+    /// Returns the address ranges holding data rather than instructions
+    pub fn literals(&self) -> &[Range<u64>] {
+        &self.literals
+    }
+
+    /// Disassemble the function, returning literal pools and other data as
+    /// [`crate::sweep::Data`]
+    pub fn sweep(&self) -> Vec<Item<'a>> {
+        sweep(self.data, self.address, &self.literals)
+    }
+
+    /// Audit the function's PAC and BTI hardening
+    pub fn audit(&self) -> Audit {
+        let ins: Vec<_> = self.disasm().filter_map(Result::ok).collect();
+
+        Audit::of(&ins)
+    }
+}
+
+/// A parsed ELF file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Elf<'a> {
+    endian: Endian,
+    entry: u64,
+    sections: Vec<Section<'a>>,
+    symbols: Vec<Symbol<'a>>,
+    // per section, the ranges marked as data by mapping symbols
+    literals: Vec<Vec<Range<u64>>>,
+}
+
+impl<'a> Elf<'a> {
+    /// Parse an ELF file
+    pub fn parse(data: &'a [u8]) -> Result<Self, ElfError> {
+        if data.get(..4) != Some(b"\x7fELF".as_slice()) {
+            return Err(ElfError::BadMagic);
+        }
+
+        if data.get(4) != Some(&2) {
+            return Err(ElfError::BadClass);
+        }
+
+        let endian = match data.get(5) {
+            Some(1) => Endian::Little,
+            Some(2) => Endian::Big,
+            _ => return Err(ElfError::BadEncoding),
+        };
+
+        let r = Reader::new(data, endian == Endian::Big);
+
+        let machine = r.u16(18)?;
+        if machine != EM_AARCH64 {
+            return Err(ElfError::BadMachine(machine));
+        }
+
+        let entry = r.u64(24)?;
+        let shoff = r.u64(40)? as usize;
+        let shentsize = r.u16(58)? as usize;
+        let shnum = r.u16(60)? as usize;
+        let shstrndx = r.u16(62)? as usize;
+
+        let mut headers = Vec::with_capacity(shnum);
+        for n in 0..shnum {
+            let off = shentsize
+                .checked_mul(n)
+                .and_then(|o| o.checked_add(shoff))
+                .ok_or(ElfError::Truncated)?;
+
+            headers.push(SectionHeader {
+                name: r.u32(off)?,
+                kind: r.u32(off + 4)?,
+                flags: r.u64(off + 8)?,
+                address: r.u64(off + 16)?,
+                offset: r.u64(off + 24)?,
+                size: r.u64(off + 32)?,
+                link: r.u32(off + 40)?,
+            });
+        }
+
+        let contents = |h: &SectionHeader| match h.kind {
+            SHT_NOBITS => Ok(&data[..0]),
+            _ => r.bytes(h.offset as usize, h.size as usize),
+        };
+
+        let shstrtab = match headers.get(shstrndx) {
+            Some(h) => contents(h)?,
+            None => &[],
+        };
+
+        let sections = headers
+            .iter()
+            .enumerate()
+            .map(|(index, h)| {
+                let data = contents(h)?;
+
+                // a section must not run past the end of the address space
+                h.address
+                    .checked_add(data.len() as u64)
+                    .ok_or(ElfError::Truncated)?;
+
+                Ok(Section {
+                    index,
+                    name: string(shstrtab, h.name as usize),
+                    kind: h.kind,
+                    flags: h.flags,
+                    address: h.address,
+                    data,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut symbols = Vec::new();
+        for h in headers
+            .iter()
+            .filter(|h| h.kind == SHT_SYMTAB || h.kind == SHT_DYNSYM)
+        {
+            let table = Reader::new(contents(h)?, endian == Endian::Big);
+            let strtab = match headers.get(h.link as usize) {
+                Some(s) => contents(s)?,
+                None => &[],
+            };
+
+            // skip the null symbol
+            for off in (24..table.data.len().saturating_sub(23)).step_by(24) {
+                let shndx = table.u16(off + 6)?;
+
+                symbols.push(Symbol {
+                    name: string(strtab, table.u32(off)? as usize),
+                    kind: table.data[off + 4].into(),
+                    address: table.u64(off + 8)?,
+                    size: table.u64(off + 16)?,
+                    section: match shndx {
+                        SHN_UNDEF => None,
+                        n if n >= SHN_LORESERVE => None,
+                        // a section which does not exist
+                        n if n as usize >= sections.len() => None,
+                        n => Some(n as usize),
+                    },
+                });
+            }
+        }
+
+        let literals = sections
+            .iter()
+            .map(|s| mapping_ranges(s, &symbols))
+            .collect();
+
+        Ok(Self {
+            endian,
+            entry,
+            sections,
+            symbols,
+            literals,
+        })
+    }
+
+    /// Returns the byte order of the file's data
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Returns the entry point
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    /// Returns every section, including the null section at index 0
+    pub fn sections(&self) -> &[Section<'a>] {
+        &self.sections
+    }
+
+    /// Returns the sections containing instructions
+    pub fn executable_sections(&self) -> impl Iterator<Item = &Section<'a>> {
+        self.sections.iter().filter(|s| s.is_executable())
+    }
+
+    /// Returns the first section with a name
+    pub fn section_by_name(&self, name: &str) -> Option<&Section<'a>> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Returns the symbols from `.symtab` and `.dynsym`
+    pub fn symbols(&self) -> &[Symbol<'a>] {
+        &self.symbols
+    }
+
+    /// Returns the address ranges of a section marked as data by mapping
+    /// symbols
+    pub fn literals(&self, section: &Section<'a>) -> &[Range<u64>] {
+        self.literals
+            .get(section.index)
+            .map(|l| l.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Disassemble a section, skipping literal pools
+    pub fn disasm<'s>(
+        &'s self,
+        section: &'s Section<'a>,
+    ) -> impl Iterator<Item = Result<Instruction, DecodeError>> + 's {
+        disasm_skipping(section.data, section.address, self.literals(section))
+    }
+
+    /// Disassemble a section, returning literal pools and other data as
+    /// [`crate::sweep::Data`]
+    pub fn sweep(&self, section: &Section<'a>) -> Vec<Item<'a>> {
+        sweep(section.data, section.address, self.literals(section))
+    }
+
+    /// Returns the functions in executable sections, sorted by address
+    ///
+    /// Functions come from `STT_FUNC` symbols. A function without a size
+    /// extends to the next function or the end of its section.
+    pub fn functions(&self) -> Vec<Function<'a>> {
+        let mut funcs: Vec<&Symbol<'a>> = self
+            .symbols
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Func)
+            .filter(|s| s.section.is_some_and(|n| self.sections[n].is_executable()))
+            .collect();
+
+        funcs.sort_by_key(|s| (s.section, s.address));
+        funcs.dedup_by_key(|s| (s.section, s.address));
+
+        funcs
+            .iter()
+            .enumerate()
+            .filter_map(|(n, sym)| {
+                let index = sym.section?;
+                let section = &self.sections[index];
+                let section_end = section.end();
+
+                let end = match sym.size {
+                    0 => funcs
+                        .get(n + 1)
+                        .filter(|next| next.section == sym.section)
+                        .map_or(section_end, |next| next.address),
+                    size => sym.address.saturating_add(size),
+                }
+                .min(section_end);
+
+                let start = sym.address.checked_sub(section.address)? as usize;
+                let data = section.data.get(start..(end - section.address) as usize)?;
+
+                let literals = self.literals[index]
+                    .iter()
+                    .filter(|r| r.start < end && r.end > sym.address)
+                    .map(|r| r.start.max(sym.address)..r.end.min(end))
+                    .collect();
+
+                Some(Function {
+                    name: sym.name,
+                    address: sym.address,
+                    data,
+                    section: index,
+                    literals,
+                })
+            })
+            .collect()
+    }
+}
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    address: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+/// Returns if a mapping symbol marks data (`$d`), code (`$x`) or neither
+fn mapping(name: &str) -> Option<bool> {
+    let (kind, rest) = name.split_at_checked(2)?;
+
+    if !rest.is_empty() && !rest.starts_with('.') {
+        return None;
+    }
+
+    match kind {
+        "$d" => Some(true),
+        "$x" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the ranges of a section marked as data by mapping symbols
+fn mapping_ranges(section: &Section, symbols: &[Symbol]) -> Vec<Range<u64>> {
+    let mut marks: Vec<(u64, bool)> = symbols
+        .iter()
+        .filter(|s| s.section == Some(section.index))
+        .filter_map(|s| mapping(s.name).map(|data| (s.address, data)))
+        .collect();
+
+    marks.sort();
+
+    let end = section.end();
+
+    marks
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, data))| *data)
+        .map(|(n, (start, _))| {
+            let next = marks[n + 1..]
+                .iter()
+                .find(|(addr, _)| addr > start)
+                .map_or(end, |(addr, _)| *addr);
+
+            *start..next
+        })
+        .filter(|r| !r.is_empty())
+        .collect()
+}
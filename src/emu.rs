@@ -0,0 +1,738 @@
+//! A small concrete interpreter
+//!
+//! Instructions are executed by lifting them with [`crate::lift`] and
+//! evaluating the statements against a [`Cpu`] and a user supplied
+//! [`Memory`]. This is meant for running short self-contained routines, such
+//! as decryption or unpacking loops, rather than whole programs.
+//!
+//! Memory is little-endian. Floating point always rounds to nearest, the
+//! `FPCR` and `FPSR` registers are stored but have no effect.
+//!
+//! # Example
+//! ```
+//! use bad64::emu::{Cpu, Emulator, FlatMemory};
+//!
+//! // 1000: ldrb w2, [x0], #1    ; "\x02\x14\x40\x38"
+//! // 1004: eor  w2, w2, #0xf    ; "\x42\x0c\x00\x52"
+//! // 1008: strb w2, [x1], #1    ; "\x22\x14\x00\x38"
+//! // 100c: ret                  ; "\xc0\x03\x5f\xd6"
+//! let mut mem = FlatMemory::new(0x1000, vec![0; 0x100]);
+//! mem.as_mut()[..16].copy_from_slice(
+//!     b"\x02\x14\x40\x38\x42\x0c\x00\x52\x22\x14\x00\x38\xc0\x03\x5f\xd6",
+//! );
+//! mem.as_mut()[0x80] = 0x14;
+//!
+//! let mut cpu = Cpu::new();
+//! cpu.pc = 0x1000;
+//! cpu.x[0] = 0x1080;
+//! cpu.x[1] = 0x1090;
+//! cpu.x[30] = 0xdead;
+//!
+//! let mut emu = Emulator::new(cpu, mem);
+//! emu.run(0xdead, 100).unwrap();
+//!
+//! assert_eq!(emu.cpu.pc, 0xdead);
+//! assert_eq!(emu.cpu.x[0], 0x1081);
+//! assert_eq!(emu.mem.as_ref()[0x90], 0x1b);
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use num_traits::ToPrimitive;
+
+use crate::lift::{BinOp, CastOp, Flag, LiftError, Lifter, Stmt, UnOp, Value};
+use crate::{DecodeError, Instruction, Nzcv, Op, Operand, Reg, RegisterView, SysReg, decode};
+
+/// Byte addressable memory
+pub trait Memory {
+    /// Reads `buf.len()` bytes from `addr`, returning `false` on a fault
+    fn read(&mut self, addr: u64, buf: &mut [u8]) -> bool;
+
+    /// Writes `buf` to `addr`, returning `false` on a fault
+    fn write(&mut self, addr: u64, buf: &[u8]) -> bool;
+}
+
+/// A single contiguous region of memory
+///
+/// Accesses outside the region fault.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FlatMemory<T> {
+    base: u64,
+    data: T,
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> FlatMemory<T> {
+    /// Create a region starting at `base`
+    pub fn new(base: u64, data: T) -> Self {
+        Self { base, data }
+    }
+
+    /// Returns the base address of the region
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    fn range(&self, addr: u64, len: usize) -> Option<core::ops::Range<usize>> {
+        let start = usize::try_from(addr.checked_sub(self.base)?).ok()?;
+        let end = start.checked_add(len)?;
+
+        if end <= self.data.as_ref().len() {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> AsRef<[u8]> for FlatMemory<T>
+where
+    T: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+impl<T> AsMut<[u8]> for FlatMemory<T>
+where
+    T: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Memory for FlatMemory<T> {
+    fn read(&mut self, addr: u64, buf: &mut [u8]) -> bool {
+        match self.range(addr, buf.len()) {
+            Some(r) => {
+                buf.copy_from_slice(&self.data.as_ref()[r]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn write(&mut self, addr: u64, buf: &[u8]) -> bool {
+        match self.range(addr, buf.len()) {
+            Some(r) => {
+                self.data.as_mut()[r].copy_from_slice(buf);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The architectural state visible to the interpreter
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Cpu {
+    pub x: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub nzcv: Nzcv,
+    pub v: [u128; 32],
+    pub fpcr: u64,
+    pub fpsr: u64,
+}
+
+impl Cpu {
+    /// Create a CPU with every register zeroed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of a general purpose or SIMD register
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::emu::Cpu;
+    /// use bad64::Reg;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.x[3] = 0x1_0000_0002;
+    /// cpu.v[1] = 0x1122_3344;
+    ///
+    /// assert_eq!(cpu.reg(Reg::W3), Some(2));
+    /// assert_eq!(cpu.reg(Reg::H1), Some(0x3344));
+    /// assert_eq!(cpu.reg(Reg::XZR), Some(0));
+    /// assert_eq!(cpu.reg(Reg::Z0), None);
+    /// ```
+    pub fn reg(&self, reg: Reg) -> Option<u128> {
+        match reg {
+            Reg::XZR | Reg::WZR => return Some(0),
+            Reg::SP => return Some(self.sp as u128),
+            Reg::WSP => return Some(self.sp as u32 as u128),
+            _ => (),
+        }
+
+        if let Some(n) = index(reg, Reg::X0, Reg::X30) {
+            return Some(self.x[n] as u128);
+        }
+
+        if let Some(n) = index(reg, Reg::W0, Reg::W30) {
+            return Some(self.x[n] as u32 as u128);
+        }
+
+        let (n, bits) = simd(reg)?;
+
+        Some(self.v[n] & mask(bits))
+    }
+
+    /// Sets a general purpose or SIMD register
+    ///
+    /// Writes follow the architecture, so writing a `W` register clears the
+    /// upper half of the `X` register. Returns `false` if the register is not
+    /// modelled.
+    pub fn set_reg(&mut self, reg: Reg, value: u128) -> bool {
+        match reg {
+            Reg::XZR | Reg::WZR => return true,
+            Reg::SP => {
+                self.sp = value as u64;
+                return true;
+            }
+            Reg::WSP => {
+                self.sp = value as u32 as u64;
+                return true;
+            }
+            _ => (),
+        }
+
+        if let Some(n) = index(reg, Reg::X0, Reg::X30) {
+            self.x[n] = value as u64;
+            return true;
+        }
+
+        if let Some(n) = index(reg, Reg::W0, Reg::W30) {
+            self.x[n] = value as u32 as u64;
+            return true;
+        }
+
+        match simd(reg) {
+            Some((n, bits)) => {
+                self.v[n] = value & mask(bits);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn flag(&self, flag: Flag) -> bool {
+        match flag {
+            Flag::N => self.nzcv.n(),
+            Flag::Z => self.nzcv.z(),
+            Flag::C => self.nzcv.c(),
+            Flag::V => self.nzcv.v(),
+        }
+    }
+
+    fn set_flag(&mut self, flag: Flag, set: bool) {
+        let bit = match flag {
+            Flag::N => Nzcv::N,
+            Flag::Z => Nzcv::Z,
+            Flag::C => Nzcv::C,
+            Flag::V => Nzcv::V,
+        };
+
+        self.nzcv = if set {
+            self.nzcv | bit
+        } else {
+            self.nzcv & !bit
+        };
+    }
+}
+
+impl RegisterView for Cpu {
+    fn reg(&self, reg: Reg) -> Option<u64> {
+        Cpu::reg(self, reg).map(|v| v as u64)
+    }
+
+    fn nzcv(&self) -> Option<Nzcv> {
+        Some(self.nzcv)
+    }
+}
+
+/// Interpreter errors
+///
+/// Apart from [`EmuError::Trap`], the program counter is left at the
+/// faulting instruction. Registers and memory written before the fault keep
+/// their new values.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum EmuError {
+    /// The instruction at the program counter failed to decode
+    Decode(DecodeError),
+    /// The operation is not implemented
+    Unimplemented(Op),
+    /// The instruction at this address could not be fetched
+    Fetch(u64),
+    /// A read from this address faulted
+    Read(u64),
+    /// A write to this address faulted
+    Write(u64),
+    /// An exception generating instruction, such as `SVC` or `BRK`
+    ///
+    /// The program counter is left at the following instruction, so
+    /// execution can resume once the exception is handled.
+    Trap { op: Op, imm: u64 },
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::Decode(e) => write!(f, "Decode: {}", e),
+            EmuError::Unimplemented(op) => write!(f, "Unimplemented: {}", op),
+            EmuError::Fetch(x) => write!(f, "Fetch: {:#x}", x),
+            EmuError::Read(x) => write!(f, "Read: {:#x}", x),
+            EmuError::Write(x) => write!(f, "Write: {:#x}", x),
+            EmuError::Trap { op, imm } => write!(f, "Trap: {} #{:#x}", op, imm),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EmuError {}
+
+impl From<DecodeError> for EmuError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl From<LiftError> for EmuError {
+    fn from(e: LiftError) -> Self {
+        Self::Unimplemented(e.op())
+    }
+}
+
+/// An interpreter over a CPU and memory
+#[derive(Clone, Debug)]
+pub struct Emulator<M> {
+    pub cpu: Cpu,
+    pub mem: M,
+    lifter: Lifter,
+    temps: Vec<u128>,
+}
+
+impl<M: Memory> Emulator<M> {
+    /// Create an interpreter
+    pub fn new(cpu: Cpu, mem: M) -> Self {
+        Self {
+            cpu,
+            mem,
+            lifter: Lifter::new(),
+            temps: Vec::new(),
+        }
+    }
+
+    /// Fetch, decode and execute the instruction at the program counter
+    pub fn step(&mut self) -> Result<(), EmuError> {
+        let pc = self.cpu.pc;
+
+        let mut word = [0; 4];
+        if !self.mem.read(pc, &mut word) {
+            return Err(EmuError::Fetch(pc));
+        }
+
+        let ins = decode(u32::from_le_bytes(word), pc)?;
+
+        self.execute(&ins)
+    }
+
+    /// Execute instructions until the program counter reaches `stop`
+    ///
+    /// At most `limit` instructions are executed. Returns the number of
+    /// instructions executed.
+    pub fn run(&mut self, stop: u64, limit: usize) -> Result<usize, EmuError> {
+        let mut count = 0;
+
+        while self.cpu.pc != stop && count < limit {
+            self.step()?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Execute an already decoded instruction
+    ///
+    /// The instruction's address is used as the program counter.
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::decode;
+    /// use bad64::emu::{Cpu, EmuError, Emulator, FlatMemory};
+    /// use bad64::Op;
+    ///
+    /// let mut emu = Emulator::new(Cpu::new(), FlatMemory::new(0, [0u8; 0]));
+    ///
+    /// // svc #0x80 - "\x01\x10\x00\xd4"
+    /// let svc = decode(0xd4001001, 0x1000).unwrap();
+    /// assert_eq!(emu.execute(&svc), Err(EmuError::Trap { op: Op::SVC, imm: 0x80 }));
+    /// assert_eq!(emu.cpu.pc, 0x1004);
+    ///
+    /// // aese v0.16b, v1.16b - "\x20\x48\x28\x4e"
+    /// let aese = decode(0x4e284820, 0x1004).unwrap();
+    /// assert_eq!(emu.execute(&aese), Err(EmuError::Unimplemented(Op::AESE)));
+    /// ```
+    pub fn execute(&mut self, ins: &Instruction) -> Result<(), EmuError> {
+        self.cpu.pc = ins.address();
+
+        if self.execute_fp_control(ins) {
+            self.cpu.pc = ins.address().wrapping_add(4);
+            return Ok(());
+        }
+
+        self.lifter.clear();
+        self.lifter.lift(ins)?;
+
+        let stmts = core::mem::take(&mut self.lifter);
+        let r = self.eval(ins, stmts.stmts(), stmts.temps());
+        self.lifter = stmts;
+
+        r
+    }
+
+    /// Handles accesses to `FPCR` and `FPSR`, which the lifter does not model
+    fn execute_fp_control(&mut self, ins: &Instruction) -> bool {
+        let ops = ins.operands();
+
+        let (sysreg, reg, write) = match (ins.op(), ops) {
+            (Op::MRS, [Operand::Reg { reg, .. }, Operand::SysReg(s)]) => (*s, *reg, false),
+            (Op::MSR, [Operand::SysReg(s), Operand::Reg { reg, .. }]) => (*s, *reg, true),
+            _ => return false,
+        };
+
+        let value = self.cpu.reg(reg).unwrap_or(0) as u64;
+
+        let field = match sysreg {
+            SysReg::FPCR => &mut self.cpu.fpcr,
+            SysReg::FPSR => &mut self.cpu.fpsr,
+            _ => return false,
+        };
+
+        if write {
+            *field = value;
+        } else {
+            let v = *field;
+            self.cpu.set_reg(reg, v as u128);
+        }
+
+        true
+    }
+
+    fn eval(&mut self, ins: &Instruction, stmts: &[Stmt], temps: u32) -> Result<(), EmuError> {
+        let unimplemented = EmuError::Unimplemented(ins.op());
+        let mut next = ins.address().wrapping_add(4);
+
+        self.temps.clear();
+        self.temps.resize(temps as usize, 0);
+
+        for stmt in stmts {
+            match *stmt {
+                Stmt::ReadReg { dst, reg } => {
+                    self.temps[dst.0 as usize] = self.cpu.reg(reg).ok_or(unimplemented)?;
+                }
+                Stmt::WriteReg { reg, src } => {
+                    let v = self.value(src);
+                    if !self.cpu.set_reg(reg, v) {
+                        return Err(unimplemented);
+                    }
+                }
+                Stmt::ReadFlag { dst, flag } => {
+                    self.temps[dst.0 as usize] = self.cpu.flag(flag) as u128;
+                }
+                Stmt::WriteFlag { flag, src } => {
+                    let v = self.value(src);
+                    self.cpu.set_flag(flag, v & 1 != 0);
+                }
+                Stmt::Load { dst, addr, bits } => {
+                    let addr = self.value(addr) as u64;
+                    let mut buf = [0; 16];
+                    let buf = &mut buf[..bits as usize / 8];
+
+                    if !self.mem.read(addr, buf) {
+                        return Err(EmuError::Read(addr));
+                    }
+
+                    let v = buf.iter().rev().fold(0u128, |acc, b| acc << 8 | *b as u128);
+                    self.temps[dst.0 as usize] = v;
+                }
+                Stmt::Store { addr, src, bits } => {
+                    let addr = self.value(addr) as u64;
+                    let v = self.value(src).to_le_bytes();
+
+                    if !self.mem.write(addr, &v[..bits as usize / 8]) {
+                        return Err(EmuError::Write(addr));
+                    }
+                }
+                Stmt::Bin {
+                    dst,
+                    op,
+                    bits,
+                    lhs,
+                    rhs,
+                } => {
+                    let (a, b) = (self.value(lhs), self.value(rhs));
+                    self.temps[dst.0 as usize] = bin(op, bits, a, b).ok_or(unimplemented)?;
+                }
+                Stmt::Un { dst, op, bits, src } => {
+                    let a = self.value(src);
+                    self.temps[dst.0 as usize] = un(op, bits, a).ok_or(unimplemented)?;
+                }
+                Stmt::Cast {
+                    dst,
+                    op,
+                    from,
+                    to,
+                    src,
+                } => {
+                    let a = self.value(src);
+                    self.temps[dst.0 as usize] = cast(op, from, to, a).ok_or(unimplemented)?;
+                }
+                Stmt::Select {
+                    dst,
+                    cond,
+                    if_true,
+                    if_false,
+                } => {
+                    let v = if self.value(cond) & 1 != 0 {
+                        self.value(if_true)
+                    } else {
+                        self.value(if_false)
+                    };
+                    self.temps[dst.0 as usize] = v;
+                }
+                Stmt::Jump { target, .. } => next = self.value(target) as u64,
+                Stmt::Branch { cond, target } => {
+                    if self.value(cond) & 1 != 0 {
+                        next = target;
+                    }
+                }
+                Stmt::Trap { op, imm } => {
+                    self.cpu.pc = next;
+                    return Err(EmuError::Trap { op, imm });
+                }
+            }
+        }
+
+        self.cpu.pc = next;
+
+        Ok(())
+    }
+
+    fn value(&self, v: Value) -> u128 {
+        match v {
+            Value::Const(c) => c as u128,
+            Value::Temp(t) => self.temps[t.0 as usize],
+        }
+    }
+}
+
+fn index(reg: Reg, first: Reg, last: Reg) -> Option<usize> {
+    let r = reg.to_u32().unwrap();
+    let first = first.to_u32().unwrap();
+
+    if (first..=last.to_u32().unwrap()).contains(&r) {
+        Some((r - first) as usize)
+    } else {
+        None
+    }
+}
+
+/// Returns the vector register index and width of a SIMD register alias
+fn simd(reg: Reg) -> Option<(usize, u8)> {
+    [
+        (Reg::V0, Reg::V31, 128),
+        (Reg::B0, Reg::B31, 8),
+        (Reg::H0, Reg::H31, 16),
+        (Reg::S0, Reg::S31, 32),
+        (Reg::D0, Reg::D31, 64),
+        (Reg::Q0, Reg::Q31, 128),
+    ]
+    .into_iter()
+    .find_map(|(first, last, bits)| index(reg, first, last).map(|n| (n, bits)))
+}
+
+fn mask(bits: u8) -> u128 {
+    if bits >= 128 { !0 } else { (1 << bits) - 1 }
+}
+
+fn sext(v: u128, bits: u8) -> i128 {
+    let shift = 128 - bits as u32;
+
+    ((v << shift) as i128) >> shift
+}
+
+fn bin(op: BinOp, bits: u8, a: u128, b: u128) -> Option<u128> {
+    if bits == 0 || bits > 128 {
+        return None;
+    }
+
+    let m = mask(bits);
+    let (a, b) = (a & m, b & m);
+    let amount = (b % bits as u128) as u32;
+
+    let r = match op {
+        BinOp::Add => a.wrapping_add(b),
+        BinOp::Sub => a.wrapping_sub(b),
+        BinOp::Mul => a.wrapping_mul(b),
+        BinOp::UMulH if bits == 64 => (a * b) >> 64,
+        BinOp::SMulH if bits == 64 => ((sext(a, 64) * sext(b, 64)) >> 64) as u128,
+        BinOp::UMulH | BinOp::SMulH => return None,
+        BinOp::UDiv => a.checked_div(b).unwrap_or(0),
+        BinOp::SDiv => match sext(b, bits) {
+            0 => 0,
+            d => sext(a, bits).wrapping_div(d) as u128,
+        },
+        BinOp::And => a & b,
+        BinOp::Or => a | b,
+        BinOp::Xor => a ^ b,
+        BinOp::Shl => a << amount,
+        BinOp::LShr => a >> amount,
+        BinOp::AShr => (sext(a, bits) >> amount) as u128,
+        BinOp::Ror => match amount {
+            0 => a,
+            n => a >> n | a << (bits as u32 - n),
+        },
+        BinOp::Eq => (a == b) as u128,
+        BinOp::Ne => (a != b) as u128,
+        BinOp::ULt => (a < b) as u128,
+        BinOp::ULe => (a <= b) as u128,
+        BinOp::SLt => (sext(a, bits) < sext(b, bits)) as u128,
+        BinOp::SLe => (sext(a, bits) <= sext(b, bits)) as u128,
+        _ => return fbin(op, bits, a, b),
+    };
+
+    Some(r & m)
+}
+
+macro_rules! float_bin {
+    ($t:ty, $bits:ty, $op:expr, $a:expr, $b:expr) => {{
+        let a = <$t>::from_bits($a as $bits);
+        let b = <$t>::from_bits($b as $bits);
+        let quiet = |x: $t| <$t>::from_bits(x.to_bits() | (1 << (<$t>::MANTISSA_DIGITS - 2)));
+
+        let r = match $op {
+            BinOp::FAdd => a + b,
+            BinOp::FSub => a - b,
+            BinOp::FMul => a * b,
+            BinOp::FDiv => a / b,
+            BinOp::FMin | BinOp::FMax if a.is_nan() => quiet(a),
+            BinOp::FMin | BinOp::FMax if b.is_nan() => quiet(b),
+            BinOp::FMinNm | BinOp::FMaxNm if a.is_nan() && b.is_nan() => quiet(a),
+            BinOp::FMinNm | BinOp::FMaxNm if a.is_nan() => b,
+            BinOp::FMinNm | BinOp::FMaxNm if b.is_nan() => a,
+            // -0.0 is less than 0.0
+            BinOp::FMin | BinOp::FMinNm if a == b => <$t>::from_bits(a.to_bits() | b.to_bits()),
+            BinOp::FMax | BinOp::FMaxNm if a == b => <$t>::from_bits(a.to_bits() & b.to_bits()),
+            BinOp::FMin | BinOp::FMinNm => {
+                if a < b {
+                    a
+                } else {
+                    b
+                }
+            }
+            BinOp::FMax | BinOp::FMaxNm => {
+                if a > b {
+                    a
+                } else {
+                    b
+                }
+            }
+            BinOp::FEq => return Some((a == b) as u128),
+            BinOp::FLt => return Some((a < b) as u128),
+            BinOp::FUnord => return Some((a.is_nan() || b.is_nan()) as u128),
+            _ => return None,
+        };
+
+        Some(r.to_bits() as u128)
+    }};
+}
+
+fn fbin(op: BinOp, bits: u8, a: u128, b: u128) -> Option<u128> {
+    match bits {
+        32 => float_bin!(f32, u32, op, a, b),
+        64 => float_bin!(f64, u64, op, a, b),
+        _ => None,
+    }
+}
+
+fn un(op: UnOp, bits: u8, a: u128) -> Option<u128> {
+    if bits == 0 || bits > 128 {
+        return None;
+    }
+
+    let m = mask(bits);
+    let a = a & m;
+    let unused = 128 - bits as u32;
+    let sign = 1 << (bits - 1);
+
+    let r = match op {
+        UnOp::Not => !a,
+        UnOp::Neg => a.wrapping_neg(),
+        UnOp::Clz => (a.leading_zeros() - unused) as u128,
+        UnOp::Cls if bits == 1 => 0,
+        UnOp::Cls => (((a >> 1) ^ a) & mask(bits - 1)).leading_zeros() as u128 - unused as u128 - 1,
+        UnOp::Rbit => a.reverse_bits() >> unused,
+        UnOp::Rev => a.swap_bytes() >> unused,
+        UnOp::FNeg => a ^ sign,
+        UnOp::FAbs => a & !sign,
+        UnOp::FSqrt => sqrt(bits, a)?,
+    };
+
+    Some(r & m)
+}
+
+#[cfg(feature = "std")]
+fn sqrt(bits: u8, a: u128) -> Option<u128> {
+    match bits {
+        32 => Some(f32::from_bits(a as u32).sqrt().to_bits() as u128),
+        64 => Some(f64::from_bits(a as u64).sqrt().to_bits() as u128),
+        _ => None,
+    }
+}
+
+// square roots need the standard library
+#[cfg(not(feature = "std"))]
+fn sqrt(_bits: u8, _a: u128) -> Option<u128> {
+    None
+}
+
+fn cast(op: CastOp, from: u8, to: u8, a: u128) -> Option<u128> {
+    if from == 0 || from > 128 || to == 0 || to > 128 {
+        return None;
+    }
+
+    let r = match (op, from, to) {
+        (CastOp::ZeroExtend, ..) => a & mask(from),
+        (CastOp::SignExtend, ..) => sext(a & mask(from), from) as u128,
+        (CastOp::SIntToFp, _, 32) if from <= 64 => (sext(a, from) as i64 as f32).to_bits() as u128,
+        (CastOp::SIntToFp, _, 64) if from <= 64 => (sext(a, from) as i64 as f64).to_bits() as u128,
+        (CastOp::UIntToFp, _, 32) if from <= 64 => {
+            ((a & mask(from)) as u64 as f32).to_bits() as u128
+        }
+        (CastOp::UIntToFp, _, 64) if from <= 64 => {
+            ((a & mask(from)) as u64 as f64).to_bits() as u128
+        }
+        (CastOp::FpToSInt | CastOp::FpToUInt | CastOp::FpToFp, 32 | 64, _) => {
+            let f = match from {
+                32 => f32::from_bits(a as u32) as f64,
+                _ => f64::from_bits(a as u64),
+            };
+
+            match (op, to) {
+                (CastOp::FpToSInt, 32) => f as i32 as u32 as u128,
+                (CastOp::FpToSInt, 64) => f as i64 as u64 as u128,
+                (CastOp::FpToUInt, 32) => f as u32 as u128,
+                (CastOp::FpToUInt, 64) => f as u64 as u128,
+                (CastOp::FpToFp, 32) => (f as f32).to_bits() as u128,
+                (CastOp::FpToFp, 64) => f.to_bits() as u128,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some(r & mask(to))
+}
@@ -1,5 +1,6 @@
 /// The semantic meaning of the flag setting
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FlagEffect {
     Sets,
     Integer,
@@ -0,0 +1,514 @@
+//! Stack frame analysis
+//!
+//! Recovers the frame layout of a function from its instructions: the
+//! allocated frame size, where callee-saved registers are spilled, which
+//! stack slots are accessed as locals, and whether a frame pointer is set up.
+//!
+//! All offsets are relative to the canonical frame address (CFA), which is
+//! the value of `sp` on function entry. Locals therefore have negative
+//! offsets and incoming stack arguments have non-negative ones.
+//!
+//! # Example
+//! ```
+//! use bad64::{disasm, Reg};
+//! use bad64::frame::Frame;
+//!
+//! // stp x29, x30, [sp, #-32]!
+//! // mov x29, sp
+//! // str x19, [sp, #16]
+//! // str w0, [x29, #28]
+//! // ldr x19, [sp, #16]
+//! // ldp x29, x30, [sp], #32
+//! // ret
+//! let code = b"\xfd\x7b\xbe\xa9\xfd\x03\x00\x91\xf3\x0b\x00\xf9\xa0\x1f\x00\xb9\
+//!              \xf3\x0b\x40\xf9\xfd\x7b\xc2\xa8\xc0\x03\x5f\xd6";
+//! let ins: Vec<_> = disasm(code, 0x1000).filter_map(Result::ok).collect();
+//!
+//! let frame = Frame::analyze(&ins);
+//!
+//! assert_eq!(frame.size(), 32);
+//! assert_eq!(frame.frame_pointer(), Some(-32));
+//! assert_eq!(frame.spills().len(), 3);
+//! assert_eq!(frame.spills()[2].reg, Reg::X19);
+//! assert_eq!(frame.spills()[2].offset, -16);
+//!
+//! let var = frame.vars()[0];
+//! assert_eq!(var.offset, -4);
+//! assert_eq!(var.size, 4);
+//! assert_eq!(var.to_string(), "var_4");
+//! ```
+
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use num_traits::ToPrimitive;
+
+use crate::{Imm, Instruction, Op, Operand, Reg, Shift};
+
+/// A callee-saved register spilled to the stack
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Spill {
+    /// The saved register
+    pub reg: Reg,
+    /// Offset of the save slot from the CFA
+    pub offset: i64,
+    /// Address of the instruction saving the register
+    pub address: u64,
+}
+
+/// A stack slot accessed as a local variable or stack argument
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct StackVar {
+    /// Offset of the slot from the CFA
+    pub offset: i64,
+    /// Widest access size seen, in bytes (0 if only its address is taken)
+    pub size: usize,
+    /// The slot is loaded from
+    pub read: bool,
+    /// The slot is stored to
+    pub written: bool,
+    /// The slot's address is computed into a register
+    pub address_taken: bool,
+}
+
+impl fmt::Display for StackVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset < 0 {
+            write!(f, "var_{:x}", -self.offset)
+        } else {
+            write!(f, "arg_{:x}", self.offset)
+        }
+    }
+}
+
+/// The recovered stack frame layout of a function
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Frame {
+    size: u64,
+    frame_pointer: Option<i64>,
+    spills: Vec<Spill>,
+    vars: Vec<StackVar>,
+}
+
+impl Frame {
+    /// Analyze a function's instructions, in address order
+    ///
+    /// The analysis is a single linear pass. After an unconditional branch or
+    /// return the stack pointer is assumed to be back at the function body's
+    /// depth, so early-return epilogues do not disturb later blocks.
+    pub fn analyze<'a, I>(ins: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Instruction>,
+    {
+        let mut frame = Frame::default();
+        let mut tracker = Tracker::default();
+
+        for ins in ins {
+            tracker.step(ins, &mut frame);
+        }
+
+        frame.vars.sort_by_key(|v| v.offset);
+
+        frame
+    }
+
+    /// Returns the number of bytes allocated below the CFA
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the CFA offset `x29` points to, if a frame pointer is set up
+    pub fn frame_pointer(&self) -> Option<i64> {
+        self.frame_pointer
+    }
+
+    /// Returns if the function establishes a frame pointer
+    pub fn has_frame_pointer(&self) -> bool {
+        self.frame_pointer.is_some()
+    }
+
+    /// Returns the callee-saved register spills, in program order
+    pub fn spills(&self) -> &[Spill] {
+        &self.spills
+    }
+
+    /// Returns the stack variables, sorted by offset
+    pub fn vars(&self) -> &[StackVar] {
+        &self.vars
+    }
+
+    /// Returns the stack variable covering a CFA offset
+    pub fn var_at(&self, offset: i64) -> Option<&StackVar> {
+        self.vars
+            .iter()
+            .find(|v| offset >= v.offset && offset < v.offset + v.size.max(1) as i64)
+    }
+
+    fn add_spill(&mut self, reg: Reg, offset: i64, address: u64) {
+        if !self.spills.iter().any(|s| s.reg == reg) {
+            self.spills.push(Spill {
+                reg,
+                offset,
+                address,
+            });
+        }
+    }
+
+    fn is_spill_slot(&self, offset: i64) -> bool {
+        self.spills
+            .iter()
+            .any(|s| offset >= s.offset && offset < s.offset + s.reg.size() as i64)
+    }
+
+    fn add_var(&mut self, offset: i64, size: usize, access: Access) {
+        let var = match self.vars.iter_mut().find(|v| v.offset == offset) {
+            Some(v) => v,
+            None => {
+                self.vars.push(StackVar {
+                    offset,
+                    size: 0,
+                    read: false,
+                    written: false,
+                    address_taken: false,
+                });
+                self.vars.last_mut().unwrap()
+            }
+        };
+
+        var.size = var.size.max(size);
+
+        match access {
+            Access::Read => var.read = true,
+            Access::Write => var.written = true,
+            Access::Address => var.address_taken = true,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    Write,
+    Address,
+}
+
+#[derive(Default)]
+struct Tracker {
+    // current sp, relative to the cfa
+    sp: Option<i64>,
+    // deepest sp seen so far
+    body: i64,
+    // constants materialized by mov/movk, for `sub sp, sp, xN`
+    consts: [Option<u64>; 31],
+    // the prologue is over once something other than a spill is stored
+    in_prologue: bool,
+    started: bool,
+}
+
+impl Tracker {
+    fn step(&mut self, ins: &Instruction, frame: &mut Frame) {
+        if !self.started {
+            self.started = true;
+            self.sp = Some(0);
+            self.in_prologue = true;
+        }
+
+        let ops = ins.operands();
+
+        match (ins.op(), ops) {
+            // sp arithmetic
+            (
+                Op::SUB | Op::ADD,
+                [
+                    Operand::Reg { reg: dst, .. },
+                    Operand::Reg { reg: src, .. },
+                    rhs,
+                ],
+            ) => {
+                let amount = match rhs {
+                    Operand::Reg { reg, .. } => gpr(*reg)
+                        .and_then(|(n, mask)| self.consts[n].map(|c| c & mask))
+                        .map(|c| c as i64),
+                    other => imm_value(other),
+                };
+                let amount = match ins.op() {
+                    Op::SUB => amount.and_then(i64::checked_neg),
+                    _ => amount,
+                };
+
+                match (*dst, *src) {
+                    (Reg::SP, Reg::SP) => {
+                        self.sp = self.sp.zip(amount).and_then(|(sp, a)| sp.checked_add(a))
+                    }
+                    (Reg::SP, Reg::X29) => {
+                        self.sp = frame
+                            .frame_pointer
+                            .zip(amount)
+                            .and_then(|(fp, a)| fp.checked_add(a))
+                    }
+                    (Reg::X29, Reg::SP) => {
+                        if let Some(fp) = self.sp.zip(amount).and_then(|(sp, a)| sp.checked_add(a))
+                        {
+                            frame.frame_pointer.get_or_insert(fp);
+                        }
+                    }
+                    (dst, base @ (Reg::SP | Reg::X29)) => {
+                        let base = if base == Reg::SP {
+                            self.sp
+                        } else {
+                            frame.frame_pointer
+                        };
+
+                        if let Some(off) = base.zip(amount).and_then(|(b, a)| b.checked_add(a)) {
+                            frame.add_var(off, 0, Access::Address);
+                        }
+
+                        self.clobber(dst);
+                    }
+                    (dst, _) => self.clobber(dst),
+                }
+            }
+            (
+                Op::MOV,
+                [
+                    Operand::Reg { reg: Reg::X29, .. },
+                    Operand::Reg { reg: Reg::SP, .. },
+                ],
+            ) => {
+                if let Some(sp) = self.sp {
+                    frame.frame_pointer.get_or_insert(sp);
+                }
+            }
+            (
+                Op::MOV,
+                [
+                    Operand::Reg { reg: Reg::SP, .. },
+                    Operand::Reg { reg: Reg::X29, .. },
+                ],
+            ) => {
+                self.sp = frame.frame_pointer;
+            }
+            // constant tracking
+            (
+                Op::MOV,
+                [
+                    Operand::Reg { reg, .. },
+                    imm @ (Operand::Imm32 { .. } | Operand::Imm64 { .. }),
+                ],
+            ) => {
+                let value = imm_value(imm);
+
+                if let Some((n, mask)) = gpr(*reg) {
+                    self.consts[n] = value.map(|v| v as u64 & mask);
+                }
+            }
+            (
+                Op::MOVK,
+                [
+                    Operand::Reg { reg, .. },
+                    Operand::Imm32 { imm, shift } | Operand::Imm64 { imm, shift },
+                ],
+            ) => {
+                if let Some((n, mask)) = gpr(*reg) {
+                    let shift = match shift {
+                        Some(Shift::LSL(s)) => *s,
+                        _ => 0,
+                    };
+                    let imm = imm_raw(*imm) as u64;
+
+                    self.consts[n] =
+                        self.consts[n].map(|c| ((c & !(0xffff << shift)) | (imm << shift)) & mask);
+                }
+            }
+            (op, _) if is_load(op) || is_store(op) => self.memory(ins, frame),
+            (
+                Op::RET
+                | Op::RETAA
+                | Op::RETAB
+                | Op::B
+                | Op::BR
+                | Op::BRAA
+                | Op::BRAAZ
+                | Op::BRAB
+                | Op::BRABZ
+                | Op::ERET,
+                _,
+            ) => {
+                self.sp = Some(self.body);
+                self.in_prologue = false;
+            }
+            (Op::BL | Op::BLR | Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ, _) => {
+                self.in_prologue = false;
+                self.consts = [None; 31];
+            }
+            (_, [Operand::Reg { reg, .. }, ..]) => self.clobber(*reg),
+            _ => (),
+        }
+
+        if let Some(sp) = self.sp {
+            self.body = self.body.min(sp);
+            frame.size = frame.size.max(self.body.unsigned_abs());
+        }
+    }
+
+    fn clobber(&mut self, reg: Reg) {
+        if let Some((n, _)) = gpr(reg) {
+            self.consts[n] = None;
+        }
+    }
+
+    fn memory(&mut self, ins: &Instruction, frame: &mut Frame) {
+        let ops = ins.operands();
+
+        let Some(mem) = ops.iter().position(is_mem) else {
+            return;
+        };
+
+        let (base, offset, writeback) = match ops[mem] {
+            Operand::MemReg(reg) => (reg, 0, None),
+            Operand::MemOffset { reg, offset, .. } => (reg, imm_raw(offset), None),
+            Operand::MemPreIdx { reg, imm } => (reg, imm_raw(imm), Some(imm_raw(imm))),
+            Operand::MemPostIdxImm { reg, imm } => (reg, 0, Some(imm_raw(imm))),
+            _ => return,
+        };
+
+        let base_value = match base {
+            Reg::SP => self.sp,
+            Reg::X29 => frame.frame_pointer,
+            _ => None,
+        };
+
+        let store = is_store(ins.op());
+        let data = ops[..mem].iter().filter_map(|o| match o {
+            Operand::Reg { reg, .. } => Some(*reg),
+            _ => None,
+        });
+
+        if let Some(addr) = base_value.map(|b| b + offset) {
+            let size = access_size(ins.op(), data.clone().next());
+            let mut spilled = false;
+
+            for (n, reg) in data.clone().enumerate() {
+                let slot = addr + (n * size) as i64;
+
+                if store && self.in_prologue && is_callee_saved(reg) {
+                    frame.add_spill(reg, slot, ins.address());
+                    spilled = true;
+                } else if !frame.is_spill_slot(slot) {
+                    let access = if store { Access::Write } else { Access::Read };
+                    frame.add_var(slot, size, access);
+                }
+            }
+
+            if store && !spilled {
+                self.in_prologue = false;
+            }
+        }
+
+        if let Some(wb) = writeback {
+            match base {
+                Reg::SP => self.sp = self.sp.map(|sp| sp + wb),
+                Reg::X29 => frame.frame_pointer = frame.frame_pointer.map(|fp| fp + wb),
+                _ => (),
+            }
+        }
+
+        if !store {
+            for reg in data {
+                self.clobber(reg);
+            }
+        }
+    }
+}
+
+fn is_mem(o: &Operand) -> bool {
+    matches!(
+        o,
+        Operand::MemReg(_)
+            | Operand::MemOffset { .. }
+            | Operand::MemPreIdx { .. }
+            | Operand::MemPostIdxImm { .. }
+            | Operand::MemPostIdxReg(_)
+            | Operand::MemExt { .. }
+    )
+}
+
+fn is_load(op: Op) -> bool {
+    matches!(
+        op,
+        Op::LDR
+            | Op::LDRB
+            | Op::LDRH
+            | Op::LDRSB
+            | Op::LDRSH
+            | Op::LDRSW
+            | Op::LDUR
+            | Op::LDURB
+            | Op::LDURH
+            | Op::LDURSB
+            | Op::LDURSH
+            | Op::LDURSW
+            | Op::LDP
+            | Op::LDPSW
+            | Op::LDNP
+    )
+}
+
+fn is_store(op: Op) -> bool {
+    matches!(
+        op,
+        Op::STR | Op::STRB | Op::STRH | Op::STUR | Op::STURB | Op::STURH | Op::STP | Op::STNP
+    )
+}
+
+fn access_size(op: Op, reg: Option<Reg>) -> usize {
+    match op {
+        Op::LDRB | Op::LDRSB | Op::LDURB | Op::LDURSB | Op::STRB | Op::STURB => 1,
+        Op::LDRH | Op::LDRSH | Op::LDURH | Op::LDURSH | Op::STRH | Op::STURH => 2,
+        Op::LDRSW | Op::LDURSW | Op::LDPSW => 4,
+        _ => reg.map(|r| r.size()).unwrap_or(8),
+    }
+}
+
+fn in_range(reg: Reg, first: Reg, last: Reg) -> bool {
+    let r = reg.to_u32().unwrap();
+
+    r >= first.to_u32().unwrap() && r <= last.to_u32().unwrap()
+}
+
+/// Returns the index of a general purpose register, the same for `wN` and
+/// `xN`, and the mask of the bits it holds
+fn gpr(reg: Reg) -> Option<(usize, u64)> {
+    let r = reg.to_u32().unwrap();
+
+    if in_range(reg, Reg::X0, Reg::X30) {
+        Some(((r - Reg::X0.to_u32().unwrap()) as usize, u64::MAX))
+    } else if in_range(reg, Reg::W0, Reg::W30) {
+        Some(((r - Reg::W0.to_u32().unwrap()) as usize, u32::MAX as u64))
+    } else {
+        None
+    }
+}
+
+fn is_callee_saved(reg: Reg) -> bool {
+    in_range(reg, Reg::X19, Reg::X30) || in_range(reg, Reg::D8, Reg::D15)
+}
+
+fn imm_raw(imm: Imm) -> i64 {
+    match imm {
+        Imm::Signed(i) => i,
+        Imm::Unsigned(u) => u as i64,
+    }
+}
+
+fn imm_value(o: &Operand) -> Option<i64> {
+    match *o {
+        Operand::Imm32 { imm, shift } | Operand::Imm64 { imm, shift } => match shift {
+            None => Some(imm_raw(imm)),
+            Some(Shift::LSL(s)) => Some(imm_raw(imm) << s),
+            Some(_) => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,218 @@
+//! ROP and JOP gadget search
+//!
+//! A gadget is a short run of instructions ending in an indirect branch,
+//! which an attacker controlling memory or registers can chain together.
+//! [`find`] decodes backwards from every `RET`, `BR` and `BLR` (and their
+//! pointer authenticating forms) in a region, for up to a given number of
+//! instructions, stopping at anything which fails to decode or would leave
+//! the run: branches, calls and exception returns. Every suffix of a run is
+//! a gadget.
+//!
+//! Each [`Gadget`] reports what it needs and does:
+//!
+//! - the registers it reads before writing and the registers it writes,
+//!   tracked as in [`crate::dataflow::Access`]
+//! - how far it moves `sp`, using [`crate::symbolic`]
+//! - whether its branch authenticates the target, and whether it starts at a
+//!   `BTI` landing pad, to measure how much of the surface PAC and BTI remove
+//!
+//! # Example
+//! ```
+//! use bad64::Reg;
+//! use bad64::gadget::{find, GadgetKind};
+//!
+//! // mov x0, x19
+//! // ldp x29, x30, [sp], #16
+//! // ret
+//! let code = b"\xe0\x03\x13\xaa\xfd\x7b\xc1\xa8\xc0\x03\x5f\xd6";
+//! let gadgets = find(code, 0x1000, 4);
+//!
+//! assert_eq!(gadgets.len(), 3);
+//!
+//! let g = &gadgets[2];
+//! assert_eq!(g.address, 0x1000);
+//! assert_eq!(g.kind, GadgetKind::Return);
+//! assert_eq!(g.target, Reg::X30);
+//! assert_eq!(g.reads.to_string(), "{x19, sp}");
+//! assert_eq!(g.writes.to_string(), "{x0, fp, lr, sp}");
+//! assert_eq!(g.stack, Some(16));
+//! assert_eq!(g.to_string(), "mov x0, x19; ldp fp, lr, [sp], #0x10; ret");
+//! ```
+
+use alloc::vec::Vec;
+
+use core::convert::TryInto;
+use core::fmt;
+
+use crate::dataflow::{Access, RegSet};
+use crate::symbolic::{Expr, State};
+use crate::{Instruction, NextPc, Op, Operand, Reg, decode, next_pcs};
+
+/// How a gadget transfers control
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GadgetKind {
+    /// A return, for return-oriented programming
+    Return,
+    /// An indirect branch, for jump-oriented programming
+    Jump,
+    /// An indirect call, which also sets `lr`
+    Call,
+}
+
+/// A run of instructions ending in an indirect branch
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Gadget {
+    /// Address of the first instruction
+    pub address: u64,
+    /// The instructions, ending with the branch
+    pub instructions: Vec<Instruction>,
+    pub kind: GadgetKind,
+    /// The register holding the branch target
+    pub target: Reg,
+    /// If the branch authenticates its target, as `RETAA` or `BLRAA` do
+    pub authenticated: bool,
+    /// If the first instruction is a landing pad for indirect branches: a
+    /// `BTI`, or a `PACIASP` or `PACIBSP`, which are implicitly ones
+    pub landing_pad: bool,
+    /// Registers read before being written, including the branch's own
+    pub reads: RegSet,
+    /// Registers written before the branch
+    pub writes: RegSet,
+    /// How far `sp` moves before the branch, if by a constant
+    pub stack: Option<i64>,
+}
+
+impl Gadget {
+    fn new(instructions: &[Instruction], kind: GadgetKind, authenticated: bool) -> Self {
+        let (branch, body) = instructions.split_last().unwrap();
+
+        let target = match branch.operands().first() {
+            Some(Operand::Reg { reg, .. }) => *reg,
+            _ => Reg::X30,
+        };
+
+        let mut reads = RegSet::new();
+        let mut writes = RegSet::new();
+
+        for ins in body {
+            let access = Access::of(ins);
+
+            reads = reads | (access.uses - writes);
+            writes = writes | access.defs;
+        }
+
+        // the target, and the modifier of an authenticated branch
+        let operands = branch.operands().iter().filter_map(|o| match o {
+            Operand::Reg { reg, .. } => Some(*reg),
+            _ => None,
+        });
+        for reg in operands.chain(Some(target)) {
+            if !writes.contains(reg) {
+                reads.insert(reg);
+            }
+        }
+
+        let stack = match State::analyze(body).value(Reg::SP) {
+            Some(Expr::Reg(Reg::SP)) => Some(0),
+            Some(Expr::Add(a, b)) if *a == Expr::Reg(Reg::SP) => b.as_const().map(|c| c as i64),
+            _ => None,
+        };
+
+        let landing_pad = matches!(instructions[0].op(), Op::BTI | Op::PACIASP | Op::PACIBSP);
+
+        Self {
+            address: instructions[0].address(),
+            instructions: instructions.to_vec(),
+            kind,
+            target,
+            authenticated,
+            landing_pad,
+            reads,
+            writes,
+            stack,
+        }
+    }
+}
+
+impl fmt::Display for Gadget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, ins) in self.instructions.iter().enumerate() {
+            if n > 0 {
+                write!(f, "; ")?;
+            }
+
+            write!(f, "{}", ins)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the kind of gadget an instruction ends, and if it authenticates
+fn ending(op: Op) -> Option<(GadgetKind, bool)> {
+    match op {
+        Op::RET => Some((GadgetKind::Return, false)),
+        Op::RETAA | Op::RETAB | Op::RETAASPPC | Op::RETAASPPCR | Op::RETABSPPC | Op::RETABSPPCR => {
+            Some((GadgetKind::Return, true))
+        }
+        Op::BR => Some((GadgetKind::Jump, false)),
+        Op::BRAA | Op::BRAAZ | Op::BRAB | Op::BRABZ => Some((GadgetKind::Jump, true)),
+        Op::BLR => Some((GadgetKind::Call, false)),
+        Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ => Some((GadgetKind::Call, true)),
+        _ => None,
+    }
+}
+
+/// Find every gadget in code at `address` with at most `depth` instructions
+/// before its branch
+///
+/// Gadgets are returned in order of their branch's address, shortest first.
+pub fn find(code: &[u8], address: u64, depth: usize) -> Vec<Gadget> {
+    let decode_at = |n: usize| {
+        let word = u32::from_le_bytes(code[n * 4..n * 4 + 4].try_into().unwrap());
+
+        decode(word, address.wrapping_add(n as u64 * 4)).ok()
+    };
+
+    let mut gadgets = Vec::new();
+
+    for end in 0..code.len() / 4 {
+        let Some(branch) = decode_at(end) else {
+            continue;
+        };
+        let Some((kind, authenticated)) = ending(branch.op()) else {
+            continue;
+        };
+
+        // the gadget's instructions, backwards from the branch
+        let mut run = Vec::with_capacity(depth + 1);
+        run.push(branch);
+
+        loop {
+            let mut instructions = run.clone();
+            instructions.reverse();
+            gadgets.push(Gadget::new(&instructions, kind, authenticated));
+
+            let Some(n) = end.checked_sub(run.len()) else {
+                break;
+            };
+            if run.len() > depth {
+                break;
+            }
+
+            match decode_at(n) {
+                Some(ins) if falls_through(&ins) => run.push(ins),
+                _ => break,
+            }
+        }
+    }
+
+    gadgets
+}
+
+/// Returns if an instruction can only continue to the next one
+fn falls_through(ins: &Instruction) -> bool {
+    next_pcs(ins, &())
+        .iter()
+        .all(|pc| matches!(pc, NextPc::Fallthrough(_)))
+}
@@ -0,0 +1,289 @@
+//! PAC and BTI hardening audit
+//!
+//! Checks a function's instructions for the code generated by
+//! `-mbranch-protection=standard`:
+//!
+//! - the return address is signed (`PACIASP` or `PACIBSP`) before it is
+//!   saved to the stack, and authenticated (`AUTIASP` and `RET`, or `RETAA`)
+//!   with the same key on every return after it is reloaded
+//! - the entry is a landing pad for indirect calls: `BTI c` or `BTI jc`, or
+//!   a `PACIASP` or `PACIBSP`, which are implicitly `BTI c`
+//! - landing pads inside the function accept jumps, `BTI j` or `BTI jc`, as
+//!   only jumps through jump tables or after `setjmp` reach them
+//! - indirect calls and branches authenticate their target (`BLRAA` rather
+//!   than `BLR`)
+//!
+//! The instructions are checked in address order rather than by following
+//! control flow, so each return is checked against the most recent signing,
+//! authentication or reload of `lr` before it. Leaf functions which never
+//! save `lr` need not sign it. Functions which are only ever called directly
+//! need no landing pad, so compilers leave it out of static functions whose
+//! address is not taken.
+//!
+//! Jump table targets are not known from the instructions alone, so an
+//! interior landing pad of the wrong kind is found, but a missing one is not.
+//!
+//! # Example
+//! ```
+//! use bad64::disasm;
+//! use bad64::hardening::{Audit, Bti, Issue, Key};
+//!
+//! // paciasp
+//! // stp x29, x30, [sp, #-16]!
+//! // blr x8
+//! // ldp x29, x30, [sp], #16
+//! // retaa
+//! let code = b"\x3f\x23\x03\xd5\xfd\x7b\xbf\xa9\x00\x01\x3f\xd6\xfd\x7b\xc1\xa8\xff\x0b\x5f\xd6";
+//! let ins: Vec<_> = disasm(code, 0x1000).filter_map(Result::ok).collect();
+//!
+//! let audit = Audit::of(&ins);
+//!
+//! assert_eq!(audit.signed, Some(Key::A));
+//! assert_eq!(audit.landing_pad, Some(Bti::C));
+//! assert_eq!(audit.findings.len(), 1);
+//! assert_eq!(audit.findings[0].address, 0x1008);
+//! assert_eq!(audit.findings[0].issue, Issue::UnauthenticatedCall);
+//! ```
+
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use crate::{Instruction, Op, Operand, Reg};
+
+/// A pointer authentication key
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    A,
+    B,
+}
+
+/// The indirect branches a `BTI` instruction accepts
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Bti {
+    /// None: a bare `BTI`
+    None,
+    /// Calls: `BTI c`
+    C,
+    /// Jumps: `BTI j`
+    J,
+    /// Calls and jumps: `BTI jc`
+    JC,
+}
+
+impl Bti {
+    /// Returns the targets accepted by a `BTI` instruction
+    fn of(ins: &Instruction) -> Option<Self> {
+        if ins.op() != Op::BTI {
+            return None;
+        }
+
+        Some(match (ins.opcode() >> 6) & 3 {
+            0 => Bti::None,
+            1 => Bti::C,
+            2 => Bti::J,
+            _ => Bti::JC,
+        })
+    }
+
+    /// Returns if an indirect call may land here
+    pub fn accepts_calls(&self) -> bool {
+        matches!(self, Bti::C | Bti::JC)
+    }
+
+    /// Returns if an indirect jump may land here
+    pub fn accepts_jumps(&self) -> bool {
+        matches!(self, Bti::J | Bti::JC)
+    }
+}
+
+/// A hardening gap
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Issue {
+    /// The entry is not a landing pad
+    MissingLandingPad,
+    /// A landing pad for the wrong branches: one not accepting calls at the
+    /// entry, or one not accepting jumps inside the function
+    WrongLandingPad,
+    /// `lr` is saved to the stack without being signed
+    UnsignedReturnAddress,
+    /// A return without authenticating the signed `lr`
+    UnauthenticatedReturn,
+    /// `lr` is authenticated with a different key than it was signed with
+    KeyMismatch,
+    /// An indirect call without authentication: `BLR`
+    UnauthenticatedCall,
+    /// An indirect branch without authentication: `BR`
+    UnauthenticatedJump,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::MissingLandingPad => write!(f, "No landing pad at entry"),
+            Issue::WrongLandingPad => write!(f, "Landing pad of the wrong kind"),
+            Issue::UnsignedReturnAddress => write!(f, "Return address saved unsigned"),
+            Issue::UnauthenticatedReturn => write!(f, "Return without authentication"),
+            Issue::KeyMismatch => write!(f, "Return address authenticated with the wrong key"),
+            Issue::UnauthenticatedCall => write!(f, "Unauthenticated indirect call"),
+            Issue::UnauthenticatedJump => write!(f, "Unauthenticated indirect branch"),
+        }
+    }
+}
+
+/// A hardening gap at an instruction
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Finding {
+    pub address: u64,
+    pub issue: Issue,
+}
+
+/// The PAC and BTI hardening of a function
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Audit {
+    /// The key the return address is signed with, if it is
+    pub signed: Option<Key>,
+    /// The landing pad at entry, with `PACIASP` and `PACIBSP` as `BTI c`
+    pub landing_pad: Option<Bti>,
+    /// Every gap found, in order of address
+    pub findings: Vec<Finding>,
+}
+
+// the state of lr at a point in the function
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Lr {
+    Plain,
+    Signed(Key),
+}
+
+impl Audit {
+    /// Audit the instructions of a function, starting at its entry
+    pub fn of(ins: &[Instruction]) -> Self {
+        let mut audit = Self::default();
+
+        let Some(entry) = ins.first() else {
+            return audit;
+        };
+
+        audit.landing_pad = match entry.op() {
+            Op::PACIASP | Op::PACIBSP => Some(Bti::C),
+            _ => Bti::of(entry),
+        };
+
+        match audit.landing_pad {
+            None => audit.add(entry, Issue::MissingLandingPad),
+            Some(bti) if !bti.accepts_calls() => audit.add(entry, Issue::WrongLandingPad),
+            _ => (),
+        }
+
+        let mut lr = Lr::Plain;
+
+        for (n, i) in ins.iter().enumerate() {
+            let op = i.op();
+
+            if n > 0 && Bti::of(i).is_some_and(|bti| !bti.accepts_jumps()) {
+                audit.add(i, Issue::WrongLandingPad);
+                continue;
+            }
+
+            if let Some(key) = signs(op) {
+                audit.signed.get_or_insert(key);
+                lr = Lr::Signed(key);
+                continue;
+            }
+
+            if let Some(key) = authenticates(op) {
+                if matches!(lr, Lr::Signed(k) if k != key) {
+                    audit.add(i, Issue::KeyMismatch);
+                }
+
+                lr = Lr::Plain;
+                continue;
+            }
+
+            match op {
+                Op::RET if lr != Lr::Plain && returns_to_lr(i) => {
+                    audit.add(i, Issue::UnauthenticatedReturn)
+                }
+                Op::BLR => audit.add(i, Issue::UnauthenticatedCall),
+                Op::BR => audit.add(i, Issue::UnauthenticatedJump),
+                _ if uses_lr(i) && is_store(op) && lr == Lr::Plain => {
+                    audit.add(i, Issue::UnsignedReturnAddress)
+                }
+                // reloading lr brings back the signed value saved
+                _ if uses_lr(i) && is_load(op) => {
+                    if let Some(key) = audit.signed {
+                        lr = Lr::Signed(key);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        audit
+    }
+
+    /// Returns if no gaps were found
+    pub fn is_hardened(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    fn add(&mut self, ins: &Instruction, issue: Issue) {
+        self.findings.push(Finding {
+            address: ins.address(),
+            issue,
+        });
+    }
+}
+
+/// Returns the key an instruction signs `lr` with
+fn signs(op: Op) -> Option<Key> {
+    match op {
+        Op::PACIASP | Op::PACIAZ | Op::PACIASPPC => Some(Key::A),
+        Op::PACIBSP | Op::PACIBZ | Op::PACIBSPPC => Some(Key::B),
+        _ => None,
+    }
+}
+
+/// Returns the key an instruction authenticates `lr` with, including the
+/// authenticating returns
+fn authenticates(op: Op) -> Option<Key> {
+    match op {
+        Op::AUTIASP
+        | Op::AUTIAZ
+        | Op::AUTIASPPC
+        | Op::AUTIASPPCR
+        | Op::RETAA
+        | Op::RETAASPPC
+        | Op::RETAASPPCR => Some(Key::A),
+        Op::AUTIBSP
+        | Op::AUTIBZ
+        | Op::AUTIBSPPC
+        | Op::AUTIBSPPCR
+        | Op::RETAB
+        | Op::RETABSPPC
+        | Op::RETABSPPCR => Some(Key::B),
+        _ => None,
+    }
+}
+
+fn returns_to_lr(ins: &Instruction) -> bool {
+    matches!(
+        ins.operands().first(),
+        None | Some(Operand::Reg { reg: Reg::X30, .. })
+    )
+}
+
+fn uses_lr(ins: &Instruction) -> bool {
+    ins.operands()
+        .iter()
+        .any(|o| matches!(o, Operand::Reg { reg: Reg::X30, .. }))
+}
+
+fn is_load(op: Op) -> bool {
+    op.mnem().starts_with("ld")
+}
+
+fn is_store(op: Op) -> bool {
+    op.mnem().starts_with("st")
+}
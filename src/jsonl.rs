@@ -0,0 +1,121 @@
+//! JSON Lines export
+//!
+//! Writes disassembly one JSON object per line, for tools which want the
+//! full structure of each instruction rather than its text. Records are
+//! built from [`crate::disasm`] and the [`Instruction`] accessors, so the
+//! format follows the library:
+//!
+//! ```text
+//! {"address":4096,"bytes":"1f2003d5","mnemonic":"nop","operands":[],"text":"nop",
+//!  "flags_set":null,"targets":[],"error":null,"data":false}
+//! ```
+//!
+//! Operands, flag effects and errors use their `serde` representations.
+//! `targets` holds every possible next program counter except falling
+//! through to the next instruction. A word which fails to decode has only
+//! `address`, `bytes` and `error` set, and a word known to be data has only
+//! `address` and `bytes` set, with `data` true.
+//!
+//! # Example
+//! ```
+//! use bad64::jsonl;
+//!
+//! // b #8; nop
+//! let code = b"\x02\x00\x00\x14\x1f\x20\x03\xd5";
+//! let mut out = Vec::new();
+//!
+//! jsonl::write(&mut out, code, 0x1000).unwrap();
+//!
+//! let out = String::from_utf8(out).unwrap();
+//! let first: serde_json::Value = serde_json::from_str(out.lines().next().unwrap()).unwrap();
+//!
+//! assert_eq!(out.lines().count(), 2);
+//! assert_eq!(first["mnemonic"], "b");
+//! assert_eq!(first["targets"][0]["Taken"], 0x1008);
+//! ```
+
+use std::format;
+use std::io::{self, Write};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use serde::Serialize;
+
+use crate::{DecodeError, FlagEffect, Instruction, NextPc, Operand, disasm, next_pcs};
+
+/// One line of output, for an instruction or a word which failed to decode
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Record<'a> {
+    pub address: u64,
+    /// The bytes as they are in memory, in hex
+    pub bytes: String,
+    pub mnemonic: Option<&'static str>,
+    pub operands: &'a [Operand],
+    pub text: Option<String>,
+    pub flags_set: Option<FlagEffect>,
+    pub targets: Vec<NextPc>,
+    pub error: Option<DecodeError>,
+    /// If the bytes are data rather than an instruction
+    pub data: bool,
+}
+
+impl<'a> Record<'a> {
+    /// Build a record from the bytes decoded and the result
+    pub fn new(bytes: &[u8], result: &'a Result<Instruction, DecodeError>) -> Self {
+        let bytes = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        match result {
+            Ok(ins) => Self {
+                address: ins.address(),
+                bytes,
+                mnemonic: Some(ins.op().mnem()),
+                operands: ins.operands(),
+                text: Some(ins.to_string()),
+                flags_set: ins.flags_set(),
+                targets: next_pcs(ins, &())
+                    .iter()
+                    .filter(|pc| !matches!(pc, NextPc::Fallthrough(_)))
+                    .copied()
+                    .collect(),
+                error: None,
+                data: false,
+            },
+            Err(e) => Self {
+                address: e.address(),
+                bytes,
+                mnemonic: None,
+                operands: &[],
+                text: None,
+                flags_set: None,
+                targets: Vec::new(),
+                error: Some(*e),
+                data: false,
+            },
+        }
+    }
+
+    /// Build a record for bytes known to be data, such as a literal pool
+    pub fn data(address: u64, bytes: &[u8]) -> Self {
+        Self {
+            address,
+            bytes: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            mnemonic: None,
+            operands: &[],
+            text: None,
+            flags_set: None,
+            targets: Vec::new(),
+            error: None,
+            data: true,
+        }
+    }
+}
+
+/// Write the disassembly of a region as JSON Lines
+pub fn write<W: Write>(mut out: W, code: &[u8], address: u64) -> io::Result<()> {
+    for (bytes, result) in code.chunks(4).zip(disasm(code, address)) {
+        serde_json::to_writer(&mut out, &Record::new(bytes, &result))?;
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,662 @@
+//! Linux kernel loading
+//!
+//! Reads arm64 kernels, either as a raw `Image` or as a `vmlinux` ELF, and
+//! decodes the tables describing code the kernel patches at runtime: the
+//! exception table (`__ex_table`), alternatives (`.altinstructions`) and jump
+//! labels (`__jump_table`).
+//!
+//! A raw `Image` has no symbol table, so symbols are recovered from the
+//! compressed kallsyms tables, which also gives the address the `Image` is
+//! linked at. A `vmlinux` without a symbol table is searched the same way.
+//! Kallsyms are found in kernels from 4.6 on, using relative offsets.
+//!
+//! # Example
+//! ```no_run
+//! use bad64::kernel::Kernel;
+//!
+//! let buf = std::fs::read("Image").unwrap();
+//! let kernel = Kernel::from_image(&buf).unwrap();
+//!
+//! for alt in kernel.alternatives() {
+//!     println!("{:x}: feature {}", alt.original.start, alt.feature);
+//!
+//!     for ins in kernel.disasm(alt.original.clone()).filter_map(Result::ok) {
+//!         println!("  - {}", ins);
+//!     }
+//!
+//!     for ins in kernel.disasm(alt.replacement.clone()).filter_map(Result::ok) {
+//!         println!("  + {}", ins);
+//!     }
+//! }
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::elf::{Elf, ElfError, Endian};
+use crate::reader::{self, Truncated};
+use crate::{DecodeError, Instruction, disasm};
+
+const IMAGE_MAGIC: &[u8] = b"ARM\x64";
+
+const SHF_ALLOC: u64 = 0x2;
+
+// set in the feature of an alternative patched by a callback
+const ARM64_CB_BIT: u16 = 1 << 15;
+
+const JUMP_TYPE_TRUE: u64 = 1;
+const JUMP_TYPE_LINKED: u64 = 2;
+
+/// Kernel parsing errors
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum KernelError {
+    /// The file does not have the arm64 `Image` magic
+    BadMagic,
+    /// The header extends past the end of the file
+    Truncated,
+    /// The `vmlinux` could not be parsed
+    Elf(ElfError),
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KernelError::BadMagic => write!(f, "Bad magic"),
+            KernelError::Truncated => write!(f, "Truncated"),
+            KernelError::Elf(e) => write!(f, "ELF: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KernelError {}
+
+impl From<ElfError> for KernelError {
+    fn from(e: ElfError) -> Self {
+        KernelError::Elf(e)
+    }
+}
+
+impl Truncated for KernelError {
+    const TRUNCATED: Self = KernelError::Truncated;
+}
+
+type Reader<'a> = reader::Reader<'a, KernelError>;
+
+/// The arm64 `Image` header
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct ImageHeader {
+    /// The offset from a 2MB aligned base the image is loaded at
+    pub text_offset: u64,
+    /// The size of the loaded image, including bss
+    pub image_size: u64,
+    pub flags: u64,
+}
+
+impl ImageHeader {
+    /// Parse the header at the start of an `Image`
+    pub fn parse(data: &[u8]) -> Result<Self, KernelError> {
+        if data.len() < 64 {
+            return Err(KernelError::Truncated);
+        }
+
+        if &data[56..60] != IMAGE_MAGIC {
+            return Err(KernelError::BadMagic);
+        }
+
+        // the header is always little-endian
+        let r = Reader::new(data, false);
+
+        Ok(Self {
+            text_offset: r.u64(8)?,
+            image_size: r.u64(16)?,
+            flags: r.u64(24)?,
+        })
+    }
+
+    /// Returns the byte order of the kernel's data
+    pub fn endian(&self) -> Endian {
+        match self.flags & 1 {
+            0 => Endian::Little,
+            _ => Endian::Big,
+        }
+    }
+
+    /// Returns the kernel's page size, if it is given
+    pub fn page_size(&self) -> Option<u64> {
+        match (self.flags >> 1) & 3 {
+            1 => Some(0x1000),
+            2 => Some(0x4000),
+            3 => Some(0x10000),
+            _ => None,
+        }
+    }
+}
+
+/// A kernel symbol
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct KernelSymbol {
+    pub name: String,
+    /// The `nm` style type, such as `T` for a global function
+    pub kind: char,
+    pub address: u64,
+}
+
+impl KernelSymbol {
+    /// Returns if the symbol is in a text section
+    pub fn is_text(&self) -> bool {
+        matches!(self.kind, 't' | 'T' | 'w' | 'W')
+    }
+}
+
+/// A function
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Function<'a> {
+    pub name: &'a str,
+    pub address: u64,
+    /// The function's bytes, up to the next function
+    pub data: &'a [u8],
+}
+
+impl Function<'_> {
+    /// Disassemble the function
+    pub fn disasm(&self) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
+        disasm(self.data, self.address)
+    }
+}
+
+/// An exception table entry
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct ExEntry {
+    /// The instruction that may fault
+    pub insn: u64,
+    /// Where to continue after a fault
+    pub fixup: u64,
+    /// The fixup type (`EX_TYPE_*`), zero before 5.16
+    pub kind: u16,
+    /// Type specific data, such as the registers to fix up
+    pub data: i16,
+}
+
+/// An alternative instruction sequence
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Alternative {
+    /// The sequence in the image
+    pub original: Range<u64>,
+    /// The sequence patched in, or for a callback the callback's address and
+    /// an empty range
+    pub replacement: Range<u64>,
+    /// The CPU capability (`ARM64_*`) selecting the replacement
+    pub feature: u16,
+    /// The original is patched by a callback
+    pub callback: bool,
+}
+
+/// A jump label patch site
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct JumpEntry {
+    /// The `nop` or `b` patched between
+    pub code: u64,
+    /// The branch target
+    pub target: u64,
+    /// The `static_key` controlling the branch
+    pub key: u64,
+    /// The branch is taken when the key is enabled by default
+    pub branch: bool,
+    /// The site is in init code
+    pub init: bool,
+}
+
+/// A parsed kernel
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Kernel<'a> {
+    endian: Endian,
+    header: Option<ImageHeader>,
+    // named sections of a vmlinux, or the whole image
+    regions: Vec<(&'a str, u64, &'a [u8])>,
+    symbols: Vec<KernelSymbol>,
+}
+
+impl<'a> Kernel<'a> {
+    /// Parse a raw `Image`
+    ///
+    /// The image is placed at the address of `_text` from kallsyms, or at
+    /// zero if they are not found.
+    pub fn from_image(data: &'a [u8]) -> Result<Self, KernelError> {
+        let header = ImageHeader::parse(data)?;
+        let endian = header.endian();
+
+        let mut kernel = Self {
+            endian,
+            header: Some(header),
+            regions: Vec::new(),
+            symbols: kallsyms(data, endian).unwrap_or_default(),
+        };
+
+        let base = kernel
+            .symbol("_text")
+            .or_else(|| kernel.symbol("_head"))
+            .unwrap_or(0);
+        kernel.regions.push(("", base, data));
+
+        Ok(kernel)
+    }
+
+    /// Parse a `vmlinux`
+    ///
+    /// Symbols come from the symbol table, or from kallsyms if it has been
+    /// stripped. Symbol table types are `T` in executable sections, `D` in
+    /// other sections and `A` otherwise.
+    pub fn from_vmlinux(data: &'a [u8]) -> Result<Self, KernelError> {
+        let elf = Elf::parse(data)?;
+
+        let regions = elf
+            .sections()
+            .iter()
+            .filter(|s| s.flags & SHF_ALLOC != 0 && !s.data.is_empty())
+            .map(|s| (s.name, s.address, s.data))
+            .collect();
+
+        let mut symbols: Vec<_> = elf
+            .symbols()
+            .iter()
+            .filter(|s| !s.name.is_empty() && !s.name.starts_with('$'))
+            .map(|s| KernelSymbol {
+                name: s.name.into(),
+                kind: match s.section.and_then(|n| elf.sections().get(n)) {
+                    Some(section) if section.is_executable() => 'T',
+                    Some(_) => 'D',
+                    None => 'A',
+                },
+                address: s.address,
+            })
+            .collect();
+
+        if symbols.is_empty() {
+            symbols = kallsyms(data, elf.endian()).unwrap_or_default();
+        }
+
+        symbols.sort_by_key(|s| s.address);
+
+        Ok(Self {
+            endian: elf.endian(),
+            header: None,
+            regions,
+            symbols,
+        })
+    }
+
+    /// Returns the `Image` header, if parsed from an `Image`
+    pub fn header(&self) -> Option<&ImageHeader> {
+        self.header.as_ref()
+    }
+
+    /// Returns the byte order of the kernel's data
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Returns the symbols, sorted by address
+    pub fn symbols(&self) -> &[KernelSymbol] {
+        &self.symbols
+    }
+
+    /// Returns the address of a symbol
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.address)
+    }
+
+    /// Returns the name of the symbol at an address
+    pub fn symbol_at(&self, address: u64) -> Option<&str> {
+        let n = self.symbols.partition_point(|s| s.address < address);
+
+        match self.symbols.get(n) {
+            Some(s) if s.address == address => Some(&s.name),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes at an address
+    pub fn read(&self, address: u64, len: usize) -> Option<&'a [u8]> {
+        self.regions.iter().find_map(|(_, base, data)| {
+            let off = usize::try_from(address.checked_sub(*base)?).ok()?;
+            data.get(off..off.checked_add(len)?)
+        })
+    }
+
+    /// Disassemble a range of addresses, or nothing if it is not mapped
+    pub fn disasm(
+        &self,
+        range: Range<u64>,
+    ) -> impl Iterator<Item = Result<Instruction, DecodeError>> + 'a {
+        let len = range.end.saturating_sub(range.start) as usize;
+
+        disasm(self.read(range.start, len).unwrap_or(&[]), range.start)
+    }
+
+    /// Returns the functions, from the text symbols
+    ///
+    /// Each function extends to the next one, or the end of the mapped data.
+    pub fn functions(&self) -> Vec<Function<'_>> {
+        let text: Vec<_> = self.symbols.iter().filter(|s| s.is_text()).collect();
+
+        text.iter()
+            .enumerate()
+            .filter_map(|(n, s)| {
+                let (_, base, data) = self.regions.iter().find(|(_, base, data)| {
+                    s.address
+                        .checked_sub(*base)
+                        .is_some_and(|off| off < data.len() as u64)
+                })?;
+
+                let start = (s.address - base) as usize;
+                let end = text[n + 1..]
+                    .iter()
+                    .find(|next| next.address > s.address)
+                    .map_or(data.len(), |next| {
+                        ((next.address - base) as usize).min(data.len())
+                    });
+
+                Some(Function {
+                    name: &s.name,
+                    address: s.address,
+                    data: &data[start..end],
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a table's address and contents, by section or by the
+    /// symbols bounding it
+    fn table(&self, section: &str, start: &str, stop: &str) -> Option<(u64, &'a [u8])> {
+        if let Some((_, address, data)) = self.regions.iter().find(|(name, _, _)| *name == section)
+        {
+            return Some((*address, data));
+        }
+
+        let start = self.symbol(start)?;
+        let stop = self.symbol(stop)?;
+
+        Some((start, self.read(start, stop.checked_sub(start)? as usize)?))
+    }
+
+    /// Returns the exception table entries
+    pub fn ex_table(&self) -> Vec<ExEntry> {
+        let Some((address, data)) =
+            self.table("__ex_table", "__start___ex_table", "__stop___ex_table")
+        else {
+            return Vec::new();
+        };
+
+        let big = self.endian == Endian::Big;
+
+        // entries grew a type and data in 5.16, which are small when present
+        let typed = data.len() % 12 == 0
+            && data
+                .chunks_exact(12)
+                .all(|c| Reader::new(c, big).u16(8).is_ok_and(|t| t < 0x100));
+        let size = if typed { 12 } else { 8 };
+
+        data.chunks_exact(size)
+            .enumerate()
+            .filter_map(|(n, c)| {
+                let at = address.wrapping_add((n * size) as u64);
+                let r = Reader::new(c, big);
+
+                Some(ExEntry {
+                    insn: relative(at, r.u32(0).ok()? as i32 as i64),
+                    fixup: relative(at.wrapping_add(4), r.u32(4).ok()? as i32 as i64),
+                    kind: if typed { r.u16(8).ok()? } else { 0 },
+                    data: if typed { r.u16(10).ok()? as i16 } else { 0 },
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the alternatives
+    pub fn alternatives(&self) -> Vec<Alternative> {
+        let Some((address, data)) = self.table(
+            ".altinstructions",
+            "__alt_instructions",
+            "__alt_instructions_end",
+        ) else {
+            return Vec::new();
+        };
+
+        let big = self.endian == Endian::Big;
+
+        data.chunks_exact(12)
+            .enumerate()
+            .filter_map(|(n, c)| {
+                let at = address.wrapping_add((n * 12) as u64);
+                let r = Reader::new(c, big);
+
+                let original = relative(at, r.u32(0).ok()? as i32 as i64);
+                let replacement = relative(at.wrapping_add(4), r.u32(4).ok()? as i32 as i64);
+                let feature = r.u16(8).ok()?;
+
+                Some(Alternative {
+                    original: original..original.wrapping_add(c[10] as u64),
+                    replacement: replacement..replacement.wrapping_add(c[11] as u64),
+                    feature: feature & !ARM64_CB_BIT,
+                    callback: feature & ARM64_CB_BIT != 0,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the jump label entries
+    pub fn jump_entries(&self) -> Vec<JumpEntry> {
+        let Some((address, data)) = self.table(
+            "__jump_table",
+            "__start___jump_table",
+            "__stop___jump_table",
+        ) else {
+            return Vec::new();
+        };
+
+        let big = self.endian == Endian::Big;
+
+        data.chunks_exact(16)
+            .enumerate()
+            .filter_map(|(n, c)| {
+                let at = address.wrapping_add((n * 16) as u64);
+                let r = Reader::new(c, big);
+                let key = relative(at.wrapping_add(8), r.u64(8).ok()? as i64);
+
+                Some(JumpEntry {
+                    code: relative(at, r.u32(0).ok()? as i32 as i64),
+                    target: relative(at.wrapping_add(4), r.u32(4).ok()? as i32 as i64),
+                    key: key & !(JUMP_TYPE_TRUE | JUMP_TYPE_LINKED),
+                    branch: key & JUMP_TYPE_TRUE != 0,
+                    init: key & JUMP_TYPE_LINKED != 0,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Find and decode the kallsyms tables in a kernel
+///
+/// Finds the token table by its run of single digit tokens, then the names
+/// and markers before it, and the offsets either before the names or after
+/// the token index, depending on the kernel version.
+pub fn kallsyms(data: &[u8], endian: Endian) -> Option<Vec<KernelSymbol>> {
+    const DIGITS: &[u8] = b"0\x001\x002\x003\x004\x005\x006\x007\x008\x009\x00";
+
+    let mut from = 0;
+    while let Some(n) = find(&data[from..], DIGITS) {
+        let digits = from + n;
+        from = digits + 1;
+
+        if let Some(symbols) = kallsyms_at(Reader::new(data, endian == Endian::Big), digits) {
+            return Some(symbols);
+        }
+    }
+
+    None
+}
+
+fn kallsyms_at(r: Reader<'_>, digits: usize) -> Option<Vec<KernelSymbol>> {
+    let data = r.data;
+
+    // '0' is token 48, as are all bytes not used for longer tokens
+    let mut token_table = digits;
+    for _ in 0..b'0' {
+        let prev = token_table.checked_sub(1)?;
+        token_table = data[..prev].iter().rposition(|b| *b == 0)? + 1;
+    }
+
+    let mut tokens = Vec::with_capacity(256);
+    let mut off = token_table;
+    for _ in 0..256 {
+        let len = data.get(off..)?.iter().position(|b| *b == 0)?;
+        tokens.push(off..off + len);
+        off += len + 1;
+    }
+
+    let token_index = align(off, 8);
+    for (n, token) in tokens.iter().enumerate() {
+        if r.u16(token_index + n * 2).ok()? as usize != token.start - token_table {
+            return None;
+        }
+    }
+
+    // kallsyms_num_syms is padded to 8 bytes, then followed by the names
+    let mut num_syms = token_table & !7;
+    let (count, names, seqs) = loop {
+        num_syms = num_syms.checked_sub(8)?;
+
+        if token_table - num_syms > 0x400_0000 {
+            return None;
+        }
+
+        if let Some((count, seqs)) = names_at(r, num_syms, token_table) {
+            break (count, num_syms + 8, seqs);
+        }
+    };
+
+    let base_plausible = |base: u64| base >> 48 == 0xffff;
+
+    // before 6.4 the offsets and base precede kallsyms_num_syms, after they
+    // follow the token index
+    let after = align(token_index + 512, 8);
+    let after_base = after + align(count * 4, 8);
+
+    let (offsets, base) = match r.u64(after_base) {
+        Ok(base) if seqs && base_plausible(base) => (after, base),
+        _ => {
+            let base = r.u64(num_syms.checked_sub(8)?).ok()?;
+            let offsets = (num_syms - 8).checked_sub(align(count * 4, 8))?;
+
+            if !base_plausible(base) {
+                return None;
+            }
+
+            (offsets, base)
+        }
+    };
+
+    let mut symbols = Vec::with_capacity(count);
+    let mut off = names;
+
+    for n in 0..count {
+        let (len, at) = name_len(data, off)?;
+        let mut name = String::new();
+
+        for b in data.get(at..at + len)? {
+            let token = &data[tokens[*b as usize].clone()];
+            name.push_str(core::str::from_utf8(token).ok()?);
+        }
+
+        let mut chars = name.chars();
+        let kind = chars.next()?;
+
+        symbols.push(KernelSymbol {
+            name: chars.as_str().into(),
+            kind,
+            address: base.wrapping_add(r.u32(offsets + n * 4).ok()? as u64),
+        });
+
+        off = at + len;
+    }
+
+    symbols.sort_by_key(|s| s.address);
+
+    Some(symbols)
+}
+
+/// Checks for kallsyms_num_syms and the names and markers following it,
+/// returning the symbol count and if kallsyms_seqs_of_names follows
+fn names_at(r: Reader<'_>, num_syms: usize, token_table: usize) -> Option<(usize, bool)> {
+    let count = r.u32(num_syms).ok()? as usize;
+
+    if count == 0 || count > token_table - num_syms || r.u32(num_syms + 4).ok()? != 0 {
+        return None;
+    }
+
+    // the offset of every 256th name, as recorded in the markers
+    let mut marks = Vec::with_capacity(count.div_ceil(256));
+    let mut off = num_syms + 8;
+
+    for n in 0..count {
+        if n % 256 == 0 {
+            marks.push(off - num_syms - 8);
+        }
+
+        let (len, at) = name_len(r.data, off)?;
+        off = at + len;
+
+        if len == 0 || off > token_table {
+            return None;
+        }
+    }
+
+    let markers = align(off, 8);
+    for (n, mark) in marks.iter().enumerate() {
+        if r.u32(markers + n * 4).ok()? as usize != *mark {
+            return None;
+        }
+    }
+
+    let end = align(markers + marks.len() * 4, 8);
+
+    if end == token_table {
+        Some((count, false))
+    } else if align(end + count * 3, 8) == token_table {
+        Some((count, true))
+    } else {
+        None
+    }
+}
+
+/// Returns the length of a compressed name and where its tokens start
+///
+/// Since 6.1 long names have a two byte length.
+fn name_len(data: &[u8], off: usize) -> Option<(usize, usize)> {
+    let len = *data.get(off)? as usize;
+
+    match len & 0x80 {
+        0 => Some((len, off + 1)),
+        _ => Some(((len & 0x7f) | (*data.get(off + 1)? as usize) << 7, off + 2)),
+    }
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+fn align(off: usize, to: usize) -> usize {
+    off.next_multiple_of(to)
+}
+
+/// Returns the address a relative reference at an address points to
+fn relative(at: u64, offset: i64) -> u64 {
+    at.wrapping_add(offset as u64)
+}
@@ -71,6 +71,9 @@ extern crate num_derive;
 #[macro_use]
 extern crate static_assertions;
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 #[cfg(feature = "std")]
 extern crate std;
 
@@ -82,23 +85,62 @@ use num_traits::FromPrimitive;
 
 use bad64_sys::*;
 
+pub mod abi;
 mod arrspec;
 mod condition;
+#[cfg(feature = "lift")]
+pub mod dataflow;
+#[cfg(feature = "elf")]
+pub mod elf;
+#[cfg(feature = "emu")]
+pub mod emu;
 mod flageffect;
+#[cfg(feature = "alloc")]
+pub mod frame;
+#[cfg(feature = "lift")]
+pub mod gadget;
+#[cfg(feature = "alloc")]
+pub mod hardening;
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+#[cfg(feature = "kernel")]
+pub mod kernel;
+#[cfg(feature = "lift")]
+pub mod lift;
+#[cfg(feature = "macho")]
+pub mod macho;
+mod nextpc;
+mod nzcv;
 mod op;
 mod operand;
+#[cfg(feature = "pe")]
+pub mod pe;
+#[cfg(feature = "lift")]
+pub mod query;
+#[cfg(any(feature = "elf", feature = "macho", feature = "pe"))]
+mod reader;
 mod reg;
 mod shift;
+#[cfg(feature = "alloc")]
+pub mod signature;
+#[cfg(feature = "alloc")]
+pub mod sweep;
+#[cfg(feature = "alloc")]
+pub mod symbolic;
 mod sysreg;
+mod walk;
 
 pub use arrspec::ArrSpec;
 pub use condition::Condition;
 pub use flageffect::FlagEffect;
+pub use nextpc::{NextPc, NextPcs, RegisterView, next_pcs};
+pub use nzcv::Nzcv;
 pub use op::Op;
 pub use operand::{Imm, Operand};
 pub use reg::Reg;
 pub use shift::Shift;
 pub use sysreg::SysReg;
+pub use walk::{Alignment, Step, Walk};
 
 /// A decoded instruction
 #[derive(Clone)]
@@ -174,6 +216,89 @@ impl fmt::Debug for Instruction {
     }
 }
 
+// Serialized with only the operands in use, so the fixed array stays private
+#[cfg(feature = "serde")]
+impl serde::Serialize for Instruction {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut st = s.serialize_struct("Instruction", 5)?;
+        st.serialize_field("address", &self.address)?;
+        st.serialize_field("opcode", &self.opcode)?;
+        st.serialize_field("op", &self.op)?;
+        st.serialize_field("operands", self.operands())?;
+        st.serialize_field("flags_set", &self.flags_set)?;
+        st.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Instruction {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Instruction")]
+        struct Fields {
+            address: u64,
+            opcode: u32,
+            op: Op,
+            operands: Operands,
+            flags_set: Option<FlagEffect>,
+        }
+
+        // up to MAX_OPERANDS operands, without needing alloc
+        struct Operands([Operand; MAX_OPERANDS as usize], usize);
+
+        impl<'de> serde::Deserialize<'de> for Operands {
+            fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                struct Visitor;
+
+                impl<'de> serde::de::Visitor<'de> for Visitor {
+                    type Value = Operands;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "at most {} operands", MAX_OPERANDS)
+                    }
+
+                    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<Operands, A::Error> {
+                        let mut operands =
+                            Operands([Operand::Label(Imm::Unsigned(0)); MAX_OPERANDS as usize], 0);
+
+                        while let Some(o) = seq.next_element()? {
+                            if operands.1 == operands.0.len() {
+                                return Err(serde::de::Error::invalid_length(
+                                    operands.1 + 1,
+                                    &self,
+                                ));
+                            }
+
+                            operands.0[operands.1] = o;
+                            operands.1 += 1;
+                        }
+
+                        Ok(operands)
+                    }
+                }
+
+                d.deserialize_seq(Visitor)
+            }
+        }
+
+        let f = Fields::deserialize(d)?;
+
+        Ok(Self {
+            address: f.address,
+            opcode: f.opcode,
+            op: f.op,
+            num_operands: f.operands.1,
+            operands: f.operands.0,
+            flags_set: f.flags_set,
+        })
+    }
+}
+
 impl Instruction {
     /// Returns the instruction address
     ///
@@ -251,65 +376,230 @@ impl Instruction {
     pub fn flags_set(&self) -> Option<FlagEffect> {
         self.flags_set
     }
+
+    /// Returns the condition the instruction tests, if any
+    ///
+    /// This covers both `B.cond` branches and condition operands, such as
+    /// those of `CSEL` and `CCMP`.
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Condition};
+    ///
+    /// // b.eq 0x1008 - "\x40\x00\x00\x54"
+    /// let decoded = decode(0x54000040, 0x1000).unwrap();
+    /// assert_eq!(decoded.condition(), Some(Condition::EQ));
+    ///
+    /// // csel x0, x1, x2, ne - "\x20\x10\x82\x9a"
+    /// let decoded = decode(0x9a821020, 0x1000).unwrap();
+    /// assert_eq!(decoded.condition(), Some(Condition::NE));
+    /// ```
+    pub fn condition(&self) -> Option<Condition> {
+        condition::branch_condition(self.op).or_else(|| {
+            self.operands().iter().find_map(|o| match o {
+                Operand::Cond(c) => Some(*c),
+                _ => None,
+            })
+        })
+    }
+
+    /// Returns the condition flags the instruction reads
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Nzcv};
+    ///
+    /// // adc w0, w1, w2 - "\x20\x00\x02\x1a"
+    /// let decoded = decode(0x1a020020, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_read(), Nzcv::C);
+    ///
+    /// // b.gt 0x1008 - "\x4c\x00\x00\x54"
+    /// let decoded = decode(0x5400004c, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_read(), Nzcv::N | Nzcv::Z | Nzcv::V);
+    ///
+    /// // mrs x0, nzcv - "\x00\x42\x3b\xd5"
+    /// let decoded = decode(0xd53b4200, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_read(), Nzcv::ALL);
+    /// ```
+    pub fn flags_read(&self) -> Nzcv {
+        if let Some(cond) = self.condition() {
+            return cond.flags_read();
+        }
+
+        match self.op {
+            Op::ADC | Op::ADCS | Op::SBC | Op::SBCS | Op::NGC | Op::NGCS | Op::CFINV => Nzcv::C,
+            Op::AXFLAG | Op::XAFLAG => Nzcv::ALL,
+            Op::MRS if self.operands().contains(&Operand::SysReg(SysReg::NZCV)) => Nzcv::ALL,
+            _ => Nzcv::NONE,
+        }
+    }
+
+    /// Returns the condition flags the instruction writes
+    ///
+    /// Unlike [`Instruction::flags_set`], this accounts for instructions that
+    /// only update some of the flags.
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Nzcv};
+    ///
+    /// // cmp x0, #0x41 - "\x1f\x04\x01\xf1"
+    /// let decoded = decode(0xf101041f, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_written(), Nzcv::ALL);
+    ///
+    /// // setf8 w0 - "\x0d\x08\x00\x3a"
+    /// let decoded = decode(0x3a00080d, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_written(), Nzcv::N | Nzcv::Z | Nzcv::V);
+    ///
+    /// // rmif x0, #0x4, #0x6 - "\x06\x04\x02\xba"
+    /// let decoded = decode(0xba020406, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_written(), Nzcv::Z | Nzcv::C);
+    ///
+    /// // nop - "\x1f\x20\x03\xd5"
+    /// let decoded = decode(0xd503201f, 0x1000).unwrap();
+    /// assert_eq!(decoded.flags_written(), Nzcv::NONE);
+    /// ```
+    pub fn flags_written(&self) -> Nzcv {
+        match self.op {
+            Op::RMIF => match self.operands().get(2) {
+                Some(Operand::Imm32 {
+                    imm: Imm::Unsigned(mask),
+                    ..
+                }) => Nzcv::from_bits(*mask as u8),
+                _ => Nzcv::ALL,
+            },
+            Op::SETF8 | Op::SETF16 => Nzcv::N | Nzcv::Z | Nzcv::V,
+            Op::CFINV => Nzcv::C,
+            Op::AXFLAG | Op::XAFLAG => Nzcv::ALL,
+            Op::MSR if self.operands().first() == Some(&Operand::SysReg(SysReg::NZCV)) => Nzcv::ALL,
+            // restore pstate from spsr
+            Op::ERET | Op::ERETAA | Op::ERETAB | Op::DRPS => Nzcv::ALL,
+            _ if self.flags_set.is_some() => Nzcv::ALL,
+            _ => Nzcv::NONE,
+        }
+    }
 }
-/// Decoding errors types
+/// Why an instruction word failed to decode
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-#[repr(i32)]
-pub enum DecodeError {
-    Reserved(u64),
-    Unmatched(u64),
-    Unallocated(u64),
-    Undefined(u64),
-    EndOfInstruction(u64),
-    Lost(u64),
-    Unreachable(u64),
-    Short(u64),
-    ErrorOperands(u64),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecodeErrorKind {
+    Reserved,
+    Unmatched,
+    Unallocated,
+    Undefined,
+    EndOfInstruction,
+    Lost,
+    Unreachable,
+    AssertFailed,
+    /// Fewer than four bytes were left to decode
+    Short,
+    ErrorOperands,
+    /// A status code from the decoder this crate does not know
+    Unknown(i32),
 }
 
-impl DecodeError {
-    fn new(code: i32, address: u64) -> Self {
+impl DecodeErrorKind {
+    fn new(code: i32) -> Self {
         match code {
-            DECODE_STATUS_RESERVED => Self::Reserved(address),
-            DECODE_STATUS_UNMATCHED => Self::Unmatched(address),
-            DECODE_STATUS_UNALLOCATED => Self::Unallocated(address),
-            DECODE_STATUS_UNDEFINED => Self::Undefined(address),
-            DECODE_STATUS_END_OF_INSTRUCTION => Self::EndOfInstruction(address),
-            DECODE_STATUS_LOST => Self::Lost(address),
-            DECODE_STATUS_UNREACHABLE => Self::Unreachable(address),
-            DECODE_STATUS_ERROR_OPERANDS => Self::ErrorOperands(address),
-            _ => panic!("unknown decode error code"),
+            DECODE_STATUS_RESERVED => Self::Reserved,
+            DECODE_STATUS_UNMATCHED => Self::Unmatched,
+            DECODE_STATUS_UNALLOCATED => Self::Unallocated,
+            DECODE_STATUS_UNDEFINED => Self::Undefined,
+            DECODE_STATUS_END_OF_INSTRUCTION => Self::EndOfInstruction,
+            DECODE_STATUS_LOST => Self::Lost,
+            DECODE_STATUS_UNREACHABLE => Self::Unreachable,
+            DECODE_STATUS_ASSERT_FAILED => Self::AssertFailed,
+            DECODE_STATUS_ERROR_OPERANDS => Self::ErrorOperands,
+            _ => Self::Unknown(code),
         }
     }
 
-    pub fn address(&self) -> u64 {
+    /// Returns a human readable description of the failure
+    pub fn reason(&self) -> &'static str {
         match self {
-            Self::Reserved(a) => *a,
-            Self::Unmatched(a) => *a,
-            Self::Unallocated(a) => *a,
-            Self::Undefined(a) => *a,
-            Self::EndOfInstruction(a) => *a,
-            Self::Lost(a) => *a,
-            Self::Unreachable(a) => *a,
-            Self::Short(a) => *a,
-            Self::ErrorOperands(a) => *a,
+            Self::Reserved => "the encoding space is reserved",
+            Self::Unmatched => "no encoding matched",
+            Self::Unallocated => "the encoding space is unallocated",
+            Self::Undefined => "the encoding is undefined, or needs a missing feature",
+            Self::EndOfInstruction => "the encoding executes as a nop",
+            Self::Lost => "the encoding defers to another which did not match",
+            Self::Unreachable => "decoding reached unreachable pseudocode",
+            Self::AssertFailed => "decoding failed an assertion",
+            Self::Short => "too few bytes for an instruction",
+            Self::ErrorOperands => "the operands could not be decoded",
+            Self::Unknown(_) => "unknown decoder status",
+        }
+    }
+}
+
+/// Decoding errors types
+///
+/// Carries enough context to cluster failures by cause: what went wrong,
+/// the instruction word, and the encoding the decoder had matched, if any.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodeError {
+    kind: DecodeErrorKind,
+    address: u64,
+    opcode: Option<u32>,
+    encoding: Option<u32>,
+}
+
+impl DecodeError {
+    fn new(code: i32, address: u64, opcode: u32, encoding: u32) -> Self {
+        Self {
+            kind: DecodeErrorKind::new(code),
+            address,
+            opcode: Some(opcode),
+            encoding: (encoding != 0).then_some(encoding),
         }
     }
+
+    /// An error for fewer than four bytes left at `address`
+    pub fn short(address: u64) -> Self {
+        Self {
+            kind: DecodeErrorKind::Short,
+            address,
+            opcode: None,
+            encoding: None,
+        }
+    }
+
+    pub fn kind(&self) -> DecodeErrorKind {
+        self.kind
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Returns the instruction word, unless there were too few bytes for one
+    pub fn opcode(&self) -> Option<u32> {
+        self.opcode
+    }
+
+    /// Returns the decoder's `ENCODING` value reached before failing, if any
+    ///
+    /// These are the `ENCODING_ENC_*` constants in `bad64-sys`.
+    pub fn encoding(&self) -> Option<u32> {
+        self.encoding
+    }
+
+    /// Returns a human readable description of the failure
+    pub fn reason(&self) -> &'static str {
+        self.kind.reason()
+    }
 }
 
 impl fmt::Display for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            DecodeError::Reserved(x) => write!(f, "Reserved: {:#x}", x),
-            DecodeError::Unmatched(x) => write!(f, "Unmatched: {:#x}", x),
-            DecodeError::Unallocated(x) => write!(f, "Unallocated: {:#x}", x),
-            DecodeError::Undefined(x) => write!(f, "Undefined: {:#x}", x),
-            DecodeError::EndOfInstruction(x) => write!(f, "EndOfInstruction: {:#x}", x),
-            DecodeError::Lost(x) => write!(f, "Lost: {:#x}", x),
-            DecodeError::Unreachable(x) => write!(f, "Unreachable: {:#x}", x),
-            DecodeError::Short(x) => write!(f, "Short: {:#x}", x),
-            DecodeError::ErrorOperands(x) => write!(f, "ErrorOperands: {:#x}", x),
+        write!(f, "{:?}: {:#x}", self.kind, self.address)?;
+
+        if let Some(opcode) = self.opcode {
+            write!(f, ": {:#010x}", opcode)?;
         }
+
+        write!(f, " ({})", self.reason())
     }
 }
 
@@ -320,7 +610,7 @@ impl std::error::Error for DecodeError {}
 ///
 /// # Arguments
 ///
-/// * `ins` - A little endian u32 of code to be decoded
+/// * `ins` - The instruction word, as read little endian from memory
 /// * `address` - Location of code in memory
 ///
 /// # Examples
@@ -371,7 +661,7 @@ pub fn decode(ins: u32, address: u64) -> Result<Instruction, DecodeError> {
                 flags_set,
             })
         }
-        _ => Err(DecodeError::new(r, address)),
+        _ => Err(DecodeError::new(r, address, ins, decoded.encoding as u32)),
     }
 }
 
@@ -407,16 +697,74 @@ pub fn decode(ins: u32, address: u64) -> Result<Instruction, DecodeError> {
 pub fn disasm(
     code: &[u8],
     address: u64,
+) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
+    disasm_words(code, address, u32::from_le_bytes)
+}
+
+/// Disassemble byte slice of big endian instruction words
+///
+/// Instructions are always little endian in memory, even on a big endian
+/// system, but swapped words turn up in hex dumps and captures from big
+/// endian hosts.
+///
+/// # Arguments
+///
+/// * `code` - u8 slice to zero or more instructions
+/// * `address` - Location of code in memory
+///
+/// # Examples
+/// ```
+/// use bad64::{disasm, disasm_be, Op};
+///
+/// let le: Vec<_> = disasm(b"\x1f\x20\x03\xd5", 0x1000).collect();
+/// let be: Vec<_> = disasm_be(b"\xd5\x03\x20\x1f", 0x1000).collect();
+///
+/// assert_eq!(le, be);
+/// assert_eq!(be[0].as_ref().unwrap().op(), Op::NOP);
+/// ```
+pub fn disasm_be(
+    code: &[u8],
+    address: u64,
+) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
+    disasm_words(code, address, u32::from_be_bytes)
+}
+
+fn disasm_words(
+    code: &[u8],
+    address: u64,
+    word: fn([u8; 4]) -> u32,
 ) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
     (address..)
         .step_by(4)
         .zip(code.chunks(4))
-        .map(|(addr, bytes)| match bytes.try_into() {
-            Ok(v) => {
-                let vv = u32::from_le_bytes(v);
+        .map(move |(addr, bytes)| match bytes.try_into() {
+            Ok(v) => decode(word(v), addr),
+            Err(_) => Err(DecodeError::short(addr)),
+        })
+}
 
-                decode(vv, addr)
-            }
-            Err(_) => Err(DecodeError::Short(addr)),
+/// Disassemble a byte sequence, skipping ranges of data such as literal pools
+///
+/// `skip` holds sorted, non-overlapping address ranges.
+#[cfg(any(feature = "elf", feature = "macho"))]
+pub(crate) fn disasm_skipping<'a>(
+    code: &'a [u8],
+    address: u64,
+    skip: &'a [core::ops::Range<u64>],
+) -> impl Iterator<Item = Result<Instruction, DecodeError>> + 'a {
+    let end = address + code.len() as u64;
+
+    // the gaps between the skipped ranges
+    let starts = core::iter::once(address).chain(skip.iter().map(|r| r.end));
+    let ends = skip.iter().map(|r| r.start).chain(core::iter::once(end));
+
+    starts
+        .zip(ends)
+        .map(move |(start, stop)| start.clamp(address, end)..stop.clamp(address, end))
+        .filter(|r| !r.is_empty())
+        .flat_map(move |r| {
+            let bytes = &code[(r.start - address) as usize..(r.end - address) as usize];
+
+            disasm(bytes, r.start)
         })
 }
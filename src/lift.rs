@@ -0,0 +1,2314 @@
+//! Lifting instructions into a small intermediate language
+//!
+//! Each instruction is translated into a flat list of [`Stmt`]s operating on
+//! registers, flags, memory and single-assignment temporaries. Every
+//! temporary is written exactly once, so the output is already in SSA form
+//! within a lifted sequence.
+//!
+//! The integer, load/store and branch instructions are covered, along with
+//! scalar single and double precision floating point and the common AdvSIMD
+//! loads, stores, lane moves, bitwise operations and lane-wise additions.
+//! Everything else, including SVE, SME and most system instructions, is
+//! reported as [`LiftError::Unsupported`].
+//!
+//! Values are at most 128 bits wide, and constants are zero extended to the
+//! width of the operation they are used in.
+//!
+//! A few architectural details are simplified:
+//!
+//! * Pointer authentication instructions are treated as the identity, so
+//!   signing, authenticating and stripping leave pointers unchanged
+//! * Exclusive stores always succeed
+//! * Barriers, hints and prefetches produce no statements
+//!
+//! # Example
+//! ```
+//! use bad64::decode;
+//! use bad64::lift::lift;
+//!
+//! // ldr x0, [x1, #0x8]! - "\x20\x8c\x40\xf8"
+//! let decoded = decode(0xf8408c20, 0x1000).unwrap();
+//!
+//! let stmts = lift(&decoded).unwrap();
+//! let text: Vec<_> = stmts.iter().map(|s| s.to_string()).collect();
+//!
+//! assert_eq!(
+//!     text,
+//!     [
+//!         "t0 = x1",
+//!         "t1 = add.64 t0, #0x8",
+//!         "t2 = load.64 [t1]",
+//!         "x0 = t2",
+//!         "x1 = t1",
+//!     ]
+//! );
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::condition::branch_condition;
+use crate::{ArrSpec, Condition, Imm, Instruction, Op, Operand, Reg, Shift, SysReg};
+
+/// A temporary, assigned exactly once
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Temp(pub u32);
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "t{}", self.0)
+    }
+}
+
+/// A statement operand
+///
+/// Constants have no width of their own and take the width of the operation
+/// they are used in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Value {
+    Const(u64),
+    Temp(Temp),
+}
+
+impl From<Temp> for Value {
+    fn from(t: Temp) -> Self {
+        Self::Temp(t)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(c: u64) -> Self {
+        Self::Const(c)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Const(c) => write!(f, "#{:#x}", c),
+            Self::Temp(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+/// A condition flag
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Flag {
+    N,
+    Z,
+    C,
+    V,
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::N => write!(f, "n"),
+            Self::Z => write!(f, "z"),
+            Self::C => write!(f, "c"),
+            Self::V => write!(f, "v"),
+        }
+    }
+}
+
+/// A binary operation
+///
+/// Shift and rotate amounts are taken modulo the operation width. Division
+/// by zero yields zero. The comparisons produce a single bit, and the
+/// floating point operations interpret their operands as IEEE 754 values of
+/// the operation width.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// The high half of an unsigned double width multiply
+    UMulH,
+    /// The high half of a signed double width multiply
+    SMulH,
+    UDiv,
+    SDiv,
+    And,
+    Or,
+    Xor,
+    Shl,
+    LShr,
+    AShr,
+    Ror,
+    Eq,
+    Ne,
+    ULt,
+    ULe,
+    SLt,
+    SLe,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FMin,
+    FMax,
+    FMinNm,
+    FMaxNm,
+    FEq,
+    FLt,
+    /// Whether either operand is NaN
+    FUnord,
+}
+
+impl BinOp {
+    /// Returns if the operation produces a single bit
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Eq
+                | Self::Ne
+                | Self::ULt
+                | Self::ULe
+                | Self::SLt
+                | Self::SLe
+                | Self::FEq
+                | Self::FLt
+                | Self::FUnord
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Mul => "mul",
+            Self::UMulH => "umulh",
+            Self::SMulH => "smulh",
+            Self::UDiv => "udiv",
+            Self::SDiv => "sdiv",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Xor => "xor",
+            Self::Shl => "shl",
+            Self::LShr => "lshr",
+            Self::AShr => "ashr",
+            Self::Ror => "ror",
+            Self::Eq => "eq",
+            Self::Ne => "ne",
+            Self::ULt => "ult",
+            Self::ULe => "ule",
+            Self::SLt => "slt",
+            Self::SLe => "sle",
+            Self::FAdd => "fadd",
+            Self::FSub => "fsub",
+            Self::FMul => "fmul",
+            Self::FDiv => "fdiv",
+            Self::FMin => "fmin",
+            Self::FMax => "fmax",
+            Self::FMinNm => "fminnm",
+            Self::FMaxNm => "fmaxnm",
+            Self::FEq => "feq",
+            Self::FLt => "flt",
+            Self::FUnord => "funord",
+        }
+    }
+}
+
+/// A unary operation
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum UnOp {
+    Not,
+    Neg,
+    /// Count leading zero bits
+    Clz,
+    /// Count leading bits matching the sign bit, excluding the sign bit
+    Cls,
+    /// Reverse the bit order
+    Rbit,
+    /// Reverse the byte order
+    Rev,
+    FNeg,
+    FAbs,
+    FSqrt,
+}
+
+impl UnOp {
+    fn name(&self) -> &'static str {
+        match *self {
+            Self::Not => "not",
+            Self::Neg => "neg",
+            Self::Clz => "clz",
+            Self::Cls => "cls",
+            Self::Rbit => "rbit",
+            Self::Rev => "rev",
+            Self::FNeg => "fneg",
+            Self::FAbs => "fabs",
+            Self::FSqrt => "fsqrt",
+        }
+    }
+}
+
+/// A width or representation conversion
+///
+/// The integer extensions take the low `from` bits of their source, so they
+/// also serve as truncations. Conversions from floating point round toward
+/// zero and saturate, while conversions to floating point round to nearest.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CastOp {
+    ZeroExtend,
+    SignExtend,
+    SIntToFp,
+    UIntToFp,
+    FpToSInt,
+    FpToUInt,
+    FpToFp,
+}
+
+impl CastOp {
+    fn name(&self) -> &'static str {
+        match *self {
+            Self::ZeroExtend => "zext",
+            Self::SignExtend => "sext",
+            Self::SIntToFp => "scvtf",
+            Self::UIntToFp => "ucvtf",
+            Self::FpToSInt => "fcvtzs",
+            Self::FpToUInt => "fcvtzu",
+            Self::FpToFp => "fcvt",
+        }
+    }
+}
+
+/// The kind of an unconditional jump
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum JumpKind {
+    Jump,
+    /// A jump that has set the link register
+    Call,
+    Return,
+}
+
+/// An IR statement
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Stmt {
+    /// Read a register
+    ///
+    /// `W` registers read the low half of the matching `X` register, and the
+    /// `B`, `H`, `S` and `D` registers read the low bits of the matching `V`
+    /// register. The zero registers are never read, they appear as constants.
+    ReadReg { dst: Temp, reg: Reg },
+    /// Write a register
+    ///
+    /// Writes to `W` registers zero the upper half of the `X` register, and
+    /// writes to the `B`, `H`, `S` and `D` registers zero the rest of the `V`
+    /// register. Writes to the zero registers are dropped.
+    WriteReg { reg: Reg, src: Value },
+    /// Read a flag as a single bit
+    ReadFlag { dst: Temp, flag: Flag },
+    /// Write the low bit of a value to a flag
+    WriteFlag { flag: Flag, src: Value },
+    /// Load `bits` bits from memory
+    Load { dst: Temp, addr: Value, bits: u8 },
+    /// Store the low `bits` bits of a value to memory
+    Store { addr: Value, src: Value, bits: u8 },
+    Bin {
+        dst: Temp,
+        op: BinOp,
+        bits: u8,
+        lhs: Value,
+        rhs: Value,
+    },
+    Un {
+        dst: Temp,
+        op: UnOp,
+        bits: u8,
+        src: Value,
+    },
+    Cast {
+        dst: Temp,
+        op: CastOp,
+        from: u8,
+        to: u8,
+        src: Value,
+    },
+    /// Choose between two values depending on the low bit of `cond`
+    Select {
+        dst: Temp,
+        cond: Value,
+        if_true: Value,
+        if_false: Value,
+    },
+    /// Jump unconditionally
+    Jump { target: Value, kind: JumpKind },
+    /// Jump if the low bit of `cond` is set, otherwise fall through
+    Branch { cond: Value, target: u64 },
+    /// Raise a synchronous exception, such as `SVC` or `BRK`
+    Trap { op: Op, imm: u64 },
+}
+
+impl Stmt {
+    /// Returns the temporary the statement assigns, if any
+    pub fn dst(&self) -> Option<Temp> {
+        match *self {
+            Self::ReadReg { dst, .. }
+            | Self::ReadFlag { dst, .. }
+            | Self::Load { dst, .. }
+            | Self::Bin { dst, .. }
+            | Self::Un { dst, .. }
+            | Self::Cast { dst, .. }
+            | Self::Select { dst, .. } => Some(dst),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::ReadReg { dst, reg } => write!(f, "{} = {}", dst, reg),
+            Self::WriteReg { reg, src } => write!(f, "{} = {}", reg, src),
+            Self::ReadFlag { dst, flag } => write!(f, "{} = {}", dst, flag),
+            Self::WriteFlag { flag, src } => write!(f, "{} = {}", flag, src),
+            Self::Load { dst, addr, bits } => write!(f, "{} = load.{} [{}]", dst, bits, addr),
+            Self::Store { addr, src, bits } => write!(f, "store.{} [{}], {}", bits, addr, src),
+            Self::Bin {
+                dst,
+                op,
+                bits,
+                lhs,
+                rhs,
+            } => write!(f, "{} = {}.{} {}, {}", dst, op.name(), bits, lhs, rhs),
+            Self::Un { dst, op, bits, src } => {
+                write!(f, "{} = {}.{} {}", dst, op.name(), bits, src)
+            }
+            Self::Cast {
+                dst,
+                op,
+                from,
+                to,
+                src,
+            } => write!(f, "{} = {}.{}.{} {}", dst, op.name(), from, to, src),
+            Self::Select {
+                dst,
+                cond,
+                if_true,
+                if_false,
+            } => write!(f, "{} = {} ? {} : {}", dst, cond, if_true, if_false),
+            Self::Jump { target, kind } => match kind {
+                JumpKind::Jump => write!(f, "jump {}", target),
+                JumpKind::Call => write!(f, "call {}", target),
+                JumpKind::Return => write!(f, "ret {}", target),
+            },
+            Self::Branch { cond, target } => write!(f, "if {} jump {:#x}", cond, target),
+            Self::Trap { op, imm } => write!(f, "trap {} #{:#x}", op, imm),
+        }
+    }
+}
+
+/// Lifting errors
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum LiftError {
+    /// The operation has no IR translation
+    Unsupported(Op),
+    /// The operands are not in a form the lifter understands
+    Operands(Op),
+}
+
+impl LiftError {
+    /// Returns the operation that failed to lift
+    pub fn op(&self) -> Op {
+        match *self {
+            Self::Unsupported(op) | Self::Operands(op) => op,
+        }
+    }
+}
+
+impl fmt::Display for LiftError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LiftError::Unsupported(op) => write!(f, "Unsupported: {}", op),
+            LiftError::Operands(op) => write!(f, "Operands: {}", op),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LiftError {}
+
+/// Lifts a sequence of instructions
+///
+/// Temporaries are numbered across the whole sequence, so the statements of
+/// several instructions can be analyzed together.
+///
+/// # Example
+/// ```
+/// use bad64::decode;
+/// use bad64::lift::{Lifter, Stmt};
+///
+/// let mut lifter = Lifter::new();
+///
+/// // mov x0, #0x10000 - "\x20\x00\xa0\xd2"
+/// lifter.lift(&decode(0xd2a00020, 0x1000).unwrap()).unwrap();
+/// // movk x0, #0x1234 - "\x80\x46\x82\xf2"
+/// let movk = lifter.lift(&decode(0xf2824680, 0x1004).unwrap()).unwrap();
+///
+/// assert_eq!(movk[0].to_string(), "t0 = x0");
+/// assert_eq!(lifter.stmts().len(), 5);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Lifter {
+    stmts: Vec<Stmt>,
+    temps: u32,
+}
+
+impl Lifter {
+    /// Create an empty lifter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lift an instruction, returning the statements it produced
+    ///
+    /// On error nothing is added.
+    pub fn lift(&mut self, ins: &Instruction) -> Result<&[Stmt], LiftError> {
+        let start = self.stmts.len();
+        let temps = self.temps;
+
+        let mut b = Builder {
+            ins,
+            stmts: &mut self.stmts,
+            temps: &mut self.temps,
+        };
+
+        match b.lift() {
+            Ok(()) => Ok(&self.stmts[start..]),
+            Err(e) => {
+                self.stmts.truncate(start);
+                self.temps = temps;
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns all statements lifted so far
+    pub fn stmts(&self) -> &[Stmt] {
+        &self.stmts
+    }
+
+    /// Returns the number of temporaries allocated so far
+    pub fn temps(&self) -> u32 {
+        self.temps
+    }
+
+    /// Consumes the lifter, returning the lifted statements
+    pub fn into_stmts(self) -> Vec<Stmt> {
+        self.stmts
+    }
+
+    /// Removes all statements and restarts temporary numbering
+    pub fn clear(&mut self) {
+        self.stmts.clear();
+        self.temps = 0;
+    }
+}
+
+/// Lift a single instruction
+///
+/// # Example
+/// ```
+/// use bad64::decode;
+/// use bad64::lift::{lift, BinOp, Stmt, Temp, Value};
+/// use bad64::Reg;
+///
+/// // add x0, x1, #0x41 - "\x20\x04\x01\x91"
+/// let decoded = decode(0x91010420, 0x1000).unwrap();
+///
+/// assert_eq!(
+///     lift(&decoded).unwrap(),
+///     [
+///         Stmt::ReadReg { dst: Temp(0), reg: Reg::X1 },
+///         Stmt::Bin {
+///             dst: Temp(1),
+///             op: BinOp::Add,
+///             bits: 64,
+///             lhs: Value::Temp(Temp(0)),
+///             rhs: Value::Const(0x41),
+///         },
+///         Stmt::WriteReg { reg: Reg::X0, src: Value::Temp(Temp(1)) },
+///     ]
+/// );
+/// ```
+pub fn lift(ins: &Instruction) -> Result<Vec<Stmt>, LiftError> {
+    let mut lifter = Lifter::new();
+
+    lifter.lift(ins)?;
+
+    Ok(lifter.into_stmts())
+}
+
+type Result<T, E = LiftError> = core::result::Result<T, E>;
+
+fn mask(bits: u8) -> u64 {
+    if bits >= 64 { !0 } else { (1 << bits) - 1 }
+}
+
+fn imm_u64(imm: Imm) -> u64 {
+    match imm {
+        Imm::Signed(i) => i as u64,
+        Imm::Unsigned(u) => u,
+    }
+}
+
+struct Builder<'a> {
+    ins: &'a Instruction,
+    stmts: &'a mut Vec<Stmt>,
+    temps: &'a mut u32,
+}
+
+impl Builder<'_> {
+    fn unsupported<T>(&self) -> Result<T> {
+        Err(LiftError::Unsupported(self.ins.op()))
+    }
+
+    fn bad_operands<T>(&self) -> Result<T> {
+        Err(LiftError::Operands(self.ins.op()))
+    }
+
+    fn temp(&mut self) -> Temp {
+        let t = Temp(*self.temps);
+        *self.temps += 1;
+        t
+    }
+
+    fn bin(&mut self, op: BinOp, bits: u8, lhs: Value, rhs: Value) -> Value {
+        let dst = self.temp();
+        self.stmts.push(Stmt::Bin {
+            dst,
+            op,
+            bits,
+            lhs,
+            rhs,
+        });
+        dst.into()
+    }
+
+    fn un(&mut self, op: UnOp, bits: u8, src: Value) -> Value {
+        let dst = self.temp();
+        self.stmts.push(Stmt::Un { dst, op, bits, src });
+        dst.into()
+    }
+
+    fn cast(&mut self, op: CastOp, from: u8, to: u8, src: Value) -> Value {
+        let dst = self.temp();
+        self.stmts.push(Stmt::Cast {
+            dst,
+            op,
+            from,
+            to,
+            src,
+        });
+        dst.into()
+    }
+
+    fn select(&mut self, cond: Value, if_true: Value, if_false: Value) -> Value {
+        let dst = self.temp();
+        self.stmts.push(Stmt::Select {
+            dst,
+            cond,
+            if_true,
+            if_false,
+        });
+        dst.into()
+    }
+
+    fn not1(&mut self, v: Value) -> Value {
+        self.bin(BinOp::Xor, 1, v, Value::Const(1))
+    }
+
+    fn flag(&mut self, flag: Flag) -> Value {
+        let dst = self.temp();
+        self.stmts.push(Stmt::ReadFlag { dst, flag });
+        dst.into()
+    }
+
+    fn set_flag(&mut self, flag: Flag, src: Value) {
+        self.stmts.push(Stmt::WriteFlag { flag, src });
+    }
+
+    fn set_flags(&mut self, [n, z, c, v]: [Value; 4]) {
+        self.set_flag(Flag::N, n);
+        self.set_flag(Flag::Z, z);
+        self.set_flag(Flag::C, c);
+        self.set_flag(Flag::V, v);
+    }
+
+    fn load(&mut self, addr: Value, bits: u8) -> Value {
+        let dst = self.temp();
+        self.stmts.push(Stmt::Load { dst, addr, bits });
+        dst.into()
+    }
+
+    fn store(&mut self, addr: Value, src: Value, bits: u8) {
+        self.stmts.push(Stmt::Store { addr, src, bits });
+    }
+
+    fn jump(&mut self, target: Value, kind: JumpKind) {
+        self.stmts.push(Stmt::Jump { target, kind });
+    }
+
+    /// Returns the width in bits of a register the IR can hold
+    fn width(&self, reg: Reg) -> Result<u8> {
+        if reg.is_simd() || reg.is_sve() || reg.is_pred() || reg == Reg::ZT0 {
+            return self.unsupported();
+        }
+
+        match reg.size() {
+            n @ (1 | 2 | 4 | 8 | 16) => Ok(n as u8 * 8),
+            _ => self.unsupported(),
+        }
+    }
+
+    /// Returns the width in bits of a floating point register
+    fn fp_width(&self, reg: Reg) -> Result<u8> {
+        match self.width(reg)? {
+            bits @ (32 | 64) if is_fp(reg) => Ok(bits),
+            _ => self.unsupported(),
+        }
+    }
+
+    fn read(&mut self, reg: Reg) -> Result<Value> {
+        if matches!(reg, Reg::XZR | Reg::WZR) {
+            return Ok(Value::Const(0));
+        }
+
+        self.width(reg)?;
+
+        let dst = self.temp();
+        self.stmts.push(Stmt::ReadReg { dst, reg });
+        Ok(dst.into())
+    }
+
+    fn write(&mut self, reg: Reg, src: Value) -> Result<()> {
+        self.width(reg)?;
+
+        if !matches!(reg, Reg::XZR | Reg::WZR) {
+            self.stmts.push(Stmt::WriteReg { reg, src });
+        }
+
+        Ok(())
+    }
+
+    fn operand(&self, n: usize) -> Result<Operand> {
+        match self.ins.operands().get(n) {
+            Some(o) => Ok(*o),
+            None => self.bad_operands(),
+        }
+    }
+
+    fn reg(&self, n: usize) -> Result<Reg> {
+        match self.operand(n)? {
+            Operand::Reg { reg, arrspec: None } => Ok(reg),
+            Operand::Reg { .. } => self.unsupported(),
+            _ => self.bad_operands(),
+        }
+    }
+
+    fn imm(&self, n: usize) -> Result<u64> {
+        match self.operand(n)? {
+            Operand::Imm32 { imm, shift } | Operand::Imm64 { imm, shift } => match shift {
+                None => Ok(imm_u64(imm)),
+                Some(Shift::LSL(s)) => Ok(imm_u64(imm) << s),
+                Some(_) => self.bad_operands(),
+            },
+            _ => self.bad_operands(),
+        }
+    }
+
+    fn label(&self, n: usize) -> Result<u64> {
+        match self.operand(n)? {
+            Operand::Label(imm) => Ok(imm_u64(imm)),
+            _ => self.bad_operands(),
+        }
+    }
+
+    fn cond(&self, n: usize) -> Result<Condition> {
+        match self.operand(n)? {
+            Operand::Cond(c) => Ok(c),
+            _ => self.bad_operands(),
+        }
+    }
+
+    /// Evaluates a source operand at the given width
+    fn value(&mut self, n: usize, bits: u8) -> Result<Value> {
+        match self.operand(n)? {
+            Operand::Reg { arrspec: None, .. } => self.read(self.reg(n)?),
+            Operand::ShiftReg { reg, shift } => {
+                let v = self.read(reg)?;
+                self.shift(v, bits, shift)
+            }
+            Operand::Imm32 { .. } | Operand::Imm64 { .. } => {
+                Ok(Value::Const(self.imm(n)? & mask(bits)))
+            }
+            Operand::Label(imm) => Ok(Value::Const(imm_u64(imm))),
+            Operand::Reg { .. } => self.unsupported(),
+            _ => self.bad_operands(),
+        }
+    }
+
+    fn shift(&mut self, v: Value, bits: u8, shift: Shift) -> Result<Value> {
+        let (op, amount) = match shift {
+            Shift::LSL(a) => (BinOp::Shl, a),
+            Shift::LSR(a) => (BinOp::LShr, a),
+            Shift::ASR(a) => (BinOp::AShr, a),
+            Shift::ROR(a) => (BinOp::Ror, a),
+            Shift::UXTB(a) => return Ok(self.extend(v, bits, CastOp::ZeroExtend, 8, a)),
+            Shift::UXTH(a) => return Ok(self.extend(v, bits, CastOp::ZeroExtend, 16, a)),
+            Shift::UXTW(a) => return Ok(self.extend(v, bits, CastOp::ZeroExtend, 32, a)),
+            Shift::UXTX(a) => return Ok(self.extend(v, bits, CastOp::ZeroExtend, 64, a)),
+            Shift::SXTB(a) => return Ok(self.extend(v, bits, CastOp::SignExtend, 8, a)),
+            Shift::SXTH(a) => return Ok(self.extend(v, bits, CastOp::SignExtend, 16, a)),
+            Shift::SXTW(a) => return Ok(self.extend(v, bits, CastOp::SignExtend, 32, a)),
+            Shift::SXTX(a) => return Ok(self.extend(v, bits, CastOp::SignExtend, 64, a)),
+            Shift::MSL(_) => return self.unsupported(),
+        };
+
+        if amount == 0 {
+            return Ok(v);
+        }
+
+        Ok(self.bin(op, bits, v, Value::Const(amount as u64)))
+    }
+
+    fn extend(&mut self, v: Value, bits: u8, op: CastOp, from: u8, amount: u32) -> Value {
+        let v = if from < bits {
+            self.cast(op, from, bits, v)
+        } else {
+            v
+        };
+
+        if amount == 0 {
+            v
+        } else {
+            self.bin(BinOp::Shl, bits, v, Value::Const(amount as u64))
+        }
+    }
+
+    /// Computes a memory operand's address and any base register writeback
+    fn address(&mut self, n: usize) -> Result<(Value, Option<(Reg, Value)>)> {
+        match self.operand(n)? {
+            Operand::MemReg(reg) => Ok((self.read(reg)?, None)),
+            Operand::MemOffset {
+                reg,
+                offset,
+                mul_vl: false,
+                ..
+            } => {
+                let base = self.read(reg)?;
+
+                match imm_u64(offset) {
+                    0 => Ok((base, None)),
+                    off => Ok((self.bin(BinOp::Add, 64, base, Value::Const(off)), None)),
+                }
+            }
+            Operand::MemPreIdx { reg, imm } => {
+                let base = self.read(reg)?;
+                let addr = self.bin(BinOp::Add, 64, base, Value::Const(imm_u64(imm)));
+
+                Ok((addr, Some((reg, addr))))
+            }
+            Operand::MemPostIdxImm { reg, imm } => {
+                let base = self.read(reg)?;
+                let next = self.bin(BinOp::Add, 64, base, Value::Const(imm_u64(imm)));
+
+                Ok((base, Some((reg, next))))
+            }
+            Operand::MemPostIdxReg([reg, index]) => {
+                let base = self.read(reg)?;
+                let index = self.read(index)?;
+                let next = self.bin(BinOp::Add, 64, base, index);
+
+                Ok((base, Some((reg, next))))
+            }
+            Operand::MemExt {
+                regs: [reg, index],
+                shift,
+                arrspec: None,
+            } => {
+                let base = self.read(reg)?;
+                let index = self.read(index)?;
+                let index = match shift {
+                    Some(shift) => self.shift(index, 64, shift)?,
+                    None => index,
+                };
+
+                Ok((self.bin(BinOp::Add, 64, base, index), None))
+            }
+            Operand::Label(imm) => Ok((Value::Const(imm_u64(imm)), None)),
+            Operand::MemOffset { .. } | Operand::MemExt { .. } => self.unsupported(),
+            _ => self.bad_operands(),
+        }
+    }
+
+    fn writeback(&mut self, wb: Option<(Reg, Value)>) -> Result<()> {
+        match wb {
+            Some((reg, v)) => self.write(reg, v),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluates a condition to a single bit
+    fn condition(&mut self, cond: Condition) -> Value {
+        use Condition::*;
+
+        let base = match cond {
+            EQ | NE => self.flag(Flag::Z),
+            CS | CC => self.flag(Flag::C),
+            MI | PL => self.flag(Flag::N),
+            VS | VC => self.flag(Flag::V),
+            HI | LS => {
+                let c = self.flag(Flag::C);
+                let z = self.flag(Flag::Z);
+                let nz = self.not1(z);
+                self.bin(BinOp::And, 1, c, nz)
+            }
+            GE | LT => {
+                let n = self.flag(Flag::N);
+                let v = self.flag(Flag::V);
+                self.bin(BinOp::Eq, 1, n, v)
+            }
+            GT | LE => {
+                let z = self.flag(Flag::Z);
+                let n = self.flag(Flag::N);
+                let v = self.flag(Flag::V);
+                let ge = self.bin(BinOp::Eq, 1, n, v);
+                let nz = self.not1(z);
+                self.bin(BinOp::And, 1, ge, nz)
+            }
+            AL | NV => return Value::Const(1),
+        };
+
+        match cond {
+            NE | CC | PL | VC | LS | LT | LE => self.not1(base),
+            _ => base,
+        }
+    }
+
+    /// Computes `a + b + carry`
+    fn add_carry(&mut self, bits: u8, a: Value, b: Value, carry: Value) -> Value {
+        let sum = self.bin(BinOp::Add, bits, a, b);
+
+        match carry {
+            Value::Const(0) => sum,
+            _ => self.bin(BinOp::Add, bits, sum, carry),
+        }
+    }
+
+    /// Returns the NZCV flags of `r = a + b + carry`
+    fn add_flags(&mut self, bits: u8, a: Value, b: Value, carry: Value, r: Value) -> [Value; 4] {
+        let n = self.bin(BinOp::LShr, bits, r, Value::Const(bits as u64 - 1));
+        let z = self.bin(BinOp::Eq, bits, r, Value::Const(0));
+        let c = match carry {
+            Value::Const(0) => self.bin(BinOp::ULt, bits, r, a),
+            Value::Const(_) => self.bin(BinOp::ULe, bits, r, a),
+            Value::Temp(_) => {
+                let lt = self.bin(BinOp::ULt, bits, r, a);
+                let eq = self.bin(BinOp::Eq, bits, r, a);
+                let wrapped = self.bin(BinOp::And, 1, eq, carry);
+                self.bin(BinOp::Or, 1, lt, wrapped)
+            }
+        };
+        let ar = self.bin(BinOp::Xor, bits, a, r);
+        let br = self.bin(BinOp::Xor, bits, b, r);
+        let both = self.bin(BinOp::And, bits, ar, br);
+        let v = self.bin(BinOp::LShr, bits, both, Value::Const(bits as u64 - 1));
+
+        [n, z, c, v]
+    }
+
+    /// Computes `a + b + carry` along with its flags
+    fn adds(&mut self, bits: u8, a: Value, b: Value, carry: Value) -> (Value, [Value; 4]) {
+        let r = self.add_carry(bits, a, b, carry);
+        let flags = self.add_flags(bits, a, b, carry, r);
+
+        (r, flags)
+    }
+
+    /// Computes `a - b - !carry` as `a + !b + carry` along with its flags
+    fn subs(&mut self, bits: u8, a: Value, b: Value, carry: Value) -> (Value, [Value; 4]) {
+        let nb = self.invert(bits, b);
+
+        self.adds(bits, a, nb, carry)
+    }
+
+    fn invert(&mut self, bits: u8, v: Value) -> Value {
+        match v {
+            Value::Const(c) => Value::Const(!c & mask(bits)),
+            _ => self.un(UnOp::Not, bits, v),
+        }
+    }
+
+    /// Returns the flags set by the logical operations
+    fn logic_flags(&mut self, bits: u8, r: Value) -> [Value; 4] {
+        let n = self.bin(BinOp::LShr, bits, r, Value::Const(bits as u64 - 1));
+        let z = self.bin(BinOp::Eq, bits, r, Value::Const(0));
+
+        [n, z, Value::Const(0), Value::Const(0)]
+    }
+
+    /// Writes either the computed flags or the immediate ones, for `CCMP`
+    fn conditional_flags(&mut self, cond: Condition, flags: [Value; 4], nzcv: u64) {
+        let holds = self.condition(cond);
+
+        for (n, (flag, v)) in [Flag::N, Flag::Z, Flag::C, Flag::V]
+            .into_iter()
+            .zip(flags)
+            .enumerate()
+        {
+            let imm = Value::Const((nzcv >> (3 - n)) & 1);
+            let v = self.select(holds, v, imm);
+            self.set_flag(flag, v);
+        }
+    }
+
+    fn lift(&mut self) -> Result<()> {
+        let op = self.ins.op();
+
+        if self.ins.operands().iter().any(|o| {
+            matches!(
+                o,
+                Operand::Reg {
+                    arrspec: Some(_),
+                    ..
+                } | Operand::MultiReg { .. }
+            )
+        }) {
+            return self.lift_vector();
+        }
+
+        match op {
+            Op::NOP
+            | Op::YIELD
+            | Op::WFE
+            | Op::WFI
+            | Op::SEV
+            | Op::SEVL
+            | Op::HINT
+            | Op::BTI
+            | Op::DMB
+            | Op::DSB
+            | Op::ISB
+            | Op::SB
+            | Op::CSDB
+            | Op::SSBB
+            | Op::PSSBB
+            | Op::ESB
+            | Op::CLREX
+            | Op::PRFM
+            | Op::PRFUM => Ok(()),
+
+            // pointer authentication is the identity
+            Op::PACIASP
+            | Op::PACIBSP
+            | Op::PACIAZ
+            | Op::PACIBZ
+            | Op::PACIA1716
+            | Op::PACIB1716
+            | Op::AUTIASP
+            | Op::AUTIBSP
+            | Op::AUTIAZ
+            | Op::AUTIBZ
+            | Op::AUTIA1716
+            | Op::AUTIB1716
+            | Op::XPACLRI
+            | Op::PACIA
+            | Op::PACIB
+            | Op::PACDA
+            | Op::PACDB
+            | Op::PACIZA
+            | Op::PACIZB
+            | Op::PACDZA
+            | Op::PACDZB
+            | Op::AUTIA
+            | Op::AUTIB
+            | Op::AUTDA
+            | Op::AUTDB
+            | Op::AUTIZA
+            | Op::AUTIZB
+            | Op::AUTDZA
+            | Op::AUTDZB
+            | Op::XPACI
+            | Op::XPACD => Ok(()),
+
+            Op::SVC | Op::HVC | Op::SMC | Op::BRK | Op::HLT | Op::UDF => {
+                let imm = self.imm(0)?;
+                self.stmts.push(Stmt::Trap { op, imm });
+                Ok(())
+            }
+
+            Op::B => {
+                let target = self.label(0)?;
+                self.jump(Value::Const(target), JumpKind::Jump);
+                Ok(())
+            }
+            Op::BL => {
+                let target = self.label(0)?;
+                self.write(Reg::X30, Value::Const(self.ins.address().wrapping_add(4)))?;
+                self.jump(Value::Const(target), JumpKind::Call);
+                Ok(())
+            }
+            Op::BR | Op::BRAA | Op::BRAAZ | Op::BRAB | Op::BRABZ => {
+                let target = self.value(0, 64)?;
+                self.jump(target, JumpKind::Jump);
+                Ok(())
+            }
+            Op::BLR | Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ => {
+                let target = self.value(0, 64)?;
+                self.write(Reg::X30, Value::Const(self.ins.address().wrapping_add(4)))?;
+                self.jump(target, JumpKind::Call);
+                Ok(())
+            }
+            Op::RET | Op::RETAA | Op::RETAB => {
+                let reg = match self.ins.operands().first() {
+                    Some(_) => self.reg(0)?,
+                    None => Reg::X30,
+                };
+                let target = self.read(reg)?;
+                self.jump(target, JumpKind::Return);
+                Ok(())
+            }
+            Op::CBZ | Op::CBNZ => {
+                let reg = self.reg(0)?;
+                let bits = self.width(reg)?;
+                let v = self.read(reg)?;
+                let target = self.label(1)?;
+                let cmp = if op == Op::CBZ { BinOp::Eq } else { BinOp::Ne };
+                let cond = self.bin(cmp, bits, v, Value::Const(0));
+                self.stmts.push(Stmt::Branch { cond, target });
+                Ok(())
+            }
+            Op::TBZ | Op::TBNZ => {
+                let reg = self.reg(0)?;
+                let bits = self.width(reg)?;
+                let v = self.read(reg)?;
+                let bit = self.imm(1)?;
+                let target = self.label(2)?;
+                let v = self.bin(BinOp::LShr, bits, v, Value::Const(bit));
+                let cond = if op == Op::TBZ { self.not1(v) } else { v };
+                self.stmts.push(Stmt::Branch { cond, target });
+                Ok(())
+            }
+            _ if branch_condition(op).is_some() => {
+                let target = self.label(0)?;
+                let cond = self.condition(branch_condition(op).unwrap());
+                match cond {
+                    Value::Const(_) => self.jump(Value::Const(target), JumpKind::Jump),
+                    _ => self.stmts.push(Stmt::Branch { cond, target }),
+                }
+                Ok(())
+            }
+
+            Op::ADR | Op::ADRP => {
+                let target = self.label(1)?;
+                self.write(self.reg(0)?, Value::Const(target))
+            }
+
+            Op::MOV => self.lift_mov(),
+            Op::MOVZ | Op::MOVN | Op::MOVK => self.lift_movw(),
+            Op::MOVI => {
+                // only the scalar form, vectors are rejected by the width check
+                let dst = self.reg(0)?;
+                self.width(dst)?;
+                let imm = self.imm(1)?;
+                self.write(dst, Value::Const(imm))
+            }
+
+            Op::ADD | Op::ADDS | Op::SUB | Op::SUBS | Op::CMP | Op::CMN => self.lift_add_sub(),
+            Op::ADC | Op::ADCS | Op::SBC | Op::SBCS | Op::NGC | Op::NGCS => self.lift_carry(),
+            Op::NEG | Op::NEGS => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let b = self.value(1, bits)?;
+                if op == Op::NEG {
+                    let r = self.un(UnOp::Neg, bits, b);
+                    return self.write(dst, r);
+                }
+                let (r, flags) = self.subs(bits, Value::Const(0), b, Value::Const(1));
+                self.write(dst, r)?;
+                self.set_flags(flags);
+                Ok(())
+            }
+
+            Op::AND
+            | Op::ANDS
+            | Op::TST
+            | Op::ORR
+            | Op::ORN
+            | Op::EOR
+            | Op::EON
+            | Op::BIC
+            | Op::BICS => self.lift_logic(),
+            Op::MVN => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let v = self.value(1, bits)?;
+                let r = self.un(UnOp::Not, bits, v);
+                self.write(dst, r)
+            }
+
+            Op::LSL | Op::LSR | Op::ASR | Op::ROR | Op::LSLV | Op::LSRV | Op::ASRV | Op::RORV => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let a = self.value(1, bits)?;
+                let b = self.value(2, bits)?;
+                let shift = match op {
+                    Op::LSL | Op::LSLV => BinOp::Shl,
+                    Op::LSR | Op::LSRV => BinOp::LShr,
+                    Op::ASR | Op::ASRV => BinOp::AShr,
+                    _ => BinOp::Ror,
+                };
+                let r = self.bin(shift, bits, a, b);
+                self.write(dst, r)
+            }
+
+            Op::UBFX
+            | Op::SBFX
+            | Op::UBFIZ
+            | Op::SBFIZ
+            | Op::BFI
+            | Op::BFXIL
+            | Op::BFC
+            | Op::UBFM
+            | Op::SBFM
+            | Op::BFM => self.lift_bitfield(),
+            Op::SXTB | Op::SXTH | Op::SXTW | Op::UXTB | Op::UXTH => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let v = self.value(1, 32)?;
+                let (cast, from) = match op {
+                    Op::SXTB => (CastOp::SignExtend, 8),
+                    Op::SXTH => (CastOp::SignExtend, 16),
+                    Op::SXTW => (CastOp::SignExtend, 32),
+                    Op::UXTB => (CastOp::ZeroExtend, 8),
+                    _ => (CastOp::ZeroExtend, 16),
+                };
+                let r = self.cast(cast, from, bits, v);
+                self.write(dst, r)
+            }
+            Op::EXTR => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let hi = self.value(1, bits)?;
+                let lo = self.value(2, bits)?;
+                let lsb = self.imm(3)?;
+                let r = if lsb == 0 {
+                    lo
+                } else {
+                    let lo = self.bin(BinOp::LShr, bits, lo, Value::Const(lsb));
+                    let hi = self.bin(BinOp::Shl, bits, hi, Value::Const(bits as u64 - lsb));
+                    self.bin(BinOp::Or, bits, hi, lo)
+                };
+                self.write(dst, r)
+            }
+
+            Op::MUL | Op::MNEG | Op::MADD | Op::MSUB => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let a = self.value(1, bits)?;
+                let b = self.value(2, bits)?;
+                let p = self.bin(BinOp::Mul, bits, a, b);
+                let r = match op {
+                    Op::MUL => p,
+                    Op::MNEG => self.un(UnOp::Neg, bits, p),
+                    Op::MADD => {
+                        let acc = self.value(3, bits)?;
+                        self.bin(BinOp::Add, bits, acc, p)
+                    }
+                    _ => {
+                        let acc = self.value(3, bits)?;
+                        self.bin(BinOp::Sub, bits, acc, p)
+                    }
+                };
+                self.write(dst, r)
+            }
+            Op::SMULL
+            | Op::UMULL
+            | Op::SMNEGL
+            | Op::UMNEGL
+            | Op::SMADDL
+            | Op::UMADDL
+            | Op::SMSUBL
+            | Op::UMSUBL => {
+                let dst = self.reg(0)?;
+                let signed = matches!(op, Op::SMULL | Op::SMNEGL | Op::SMADDL | Op::SMSUBL);
+                let cast = if signed {
+                    CastOp::SignExtend
+                } else {
+                    CastOp::ZeroExtend
+                };
+                let a = self.value(1, 32)?;
+                let a = self.cast(cast, 32, 64, a);
+                let b = self.value(2, 32)?;
+                let b = self.cast(cast, 32, 64, b);
+                let p = self.bin(BinOp::Mul, 64, a, b);
+                let r = match op {
+                    Op::SMULL | Op::UMULL => p,
+                    Op::SMNEGL | Op::UMNEGL => self.un(UnOp::Neg, 64, p),
+                    Op::SMADDL | Op::UMADDL => {
+                        let acc = self.value(3, 64)?;
+                        self.bin(BinOp::Add, 64, acc, p)
+                    }
+                    _ => {
+                        let acc = self.value(3, 64)?;
+                        self.bin(BinOp::Sub, 64, acc, p)
+                    }
+                };
+                self.write(dst, r)
+            }
+            Op::SMULH | Op::UMULH | Op::SDIV | Op::UDIV => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let a = self.value(1, bits)?;
+                let b = self.value(2, bits)?;
+                let bin = match op {
+                    Op::SMULH => BinOp::SMulH,
+                    Op::UMULH => BinOp::UMulH,
+                    Op::SDIV => BinOp::SDiv,
+                    _ => BinOp::UDiv,
+                };
+                let r = self.bin(bin, bits, a, b);
+                self.write(dst, r)
+            }
+
+            Op::CLZ | Op::CLS | Op::RBIT | Op::REV | Op::REV64 => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let v = self.value(1, bits)?;
+                let un = match op {
+                    Op::CLZ => UnOp::Clz,
+                    Op::CLS => UnOp::Cls,
+                    Op::RBIT => UnOp::Rbit,
+                    _ => UnOp::Rev,
+                };
+                let r = self.un(un, bits, v);
+                self.write(dst, r)
+            }
+            Op::REV16 => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                let v = self.value(1, bits)?;
+                let lo = 0x00ff_00ff_00ff_00ff & mask(bits);
+                let hi = lo << 8;
+                let down = self.bin(BinOp::LShr, bits, v, Value::Const(8));
+                let down = self.bin(BinOp::And, bits, down, Value::Const(lo));
+                let up = self.bin(BinOp::Shl, bits, v, Value::Const(8));
+                let up = self.bin(BinOp::And, bits, up, Value::Const(hi));
+                let r = self.bin(BinOp::Or, bits, up, down);
+                self.write(dst, r)
+            }
+            Op::REV32 => {
+                let dst = self.reg(0)?;
+                let v = self.value(1, 64)?;
+                let r = self.un(UnOp::Rev, 64, v);
+                let r = self.bin(BinOp::Ror, 64, r, Value::Const(32));
+                self.write(dst, r)
+            }
+
+            Op::CSEL
+            | Op::CSINC
+            | Op::CSINV
+            | Op::CSNEG
+            | Op::CSET
+            | Op::CSETM
+            | Op::CINC
+            | Op::CINV
+            | Op::CNEG
+            | Op::FCSEL => self.lift_csel(),
+            Op::CCMP | Op::CCMN => {
+                let a = self.reg(0)?;
+                let bits = self.width(a)?;
+                let a = self.read(a)?;
+                let b = self.value(1, bits)?;
+                let nzcv = self.imm(2)?;
+                let cond = self.cond(3)?;
+                let (_, flags) = if op == Op::CCMP {
+                    self.subs(bits, a, b, Value::Const(1))
+                } else {
+                    self.adds(bits, a, b, Value::Const(0))
+                };
+                self.conditional_flags(cond, flags, nzcv);
+                Ok(())
+            }
+
+            Op::CFINV => {
+                let c = self.flag(Flag::C);
+                let c = self.not1(c);
+                self.set_flag(Flag::C, c);
+                Ok(())
+            }
+            Op::SETF8 | Op::SETF16 => {
+                let v = self.value(0, 32)?;
+                let msb = if op == Op::SETF8 { 7 } else { 15 };
+                let n = self.bin(BinOp::LShr, 32, v, Value::Const(msb));
+                let low = self.cast(CastOp::ZeroExtend, msb as u8 + 1, 32, v);
+                let z = self.bin(BinOp::Eq, 32, low, Value::Const(0));
+                let above = self.bin(BinOp::LShr, 32, v, Value::Const(msb + 1));
+                let ov = self.bin(BinOp::Xor, 1, above, n);
+                self.set_flag(Flag::N, n);
+                self.set_flag(Flag::Z, z);
+                self.set_flag(Flag::V, ov);
+                Ok(())
+            }
+            Op::RMIF => {
+                let v = self.value(0, 64)?;
+                let rot = self.imm(1)?;
+                let mask = self.imm(2)?;
+                let v = self.bin(BinOp::Ror, 64, v, Value::Const(rot));
+                for (n, flag) in [Flag::N, Flag::Z, Flag::C, Flag::V].into_iter().enumerate() {
+                    let bit = 3 - n as u64;
+                    if mask & (1 << bit) != 0 {
+                        let f = self.bin(BinOp::LShr, 64, v, Value::Const(bit));
+                        self.set_flag(flag, f);
+                    }
+                }
+                Ok(())
+            }
+            Op::AXFLAG => {
+                let z = self.flag(Flag::Z);
+                let c = self.flag(Flag::C);
+                let v = self.flag(Flag::V);
+                let nv = self.not1(v);
+                let z = self.bin(BinOp::Or, 1, z, v);
+                let c = self.bin(BinOp::And, 1, c, nv);
+                self.set_flags([Value::Const(0), z, c, Value::Const(0)]);
+                Ok(())
+            }
+            Op::XAFLAG => {
+                let z = self.flag(Flag::Z);
+                let c = self.flag(Flag::C);
+                let nz = self.not1(z);
+                let nc = self.not1(c);
+                let n = self.bin(BinOp::And, 1, nc, nz);
+                let v = self.bin(BinOp::And, 1, nc, z);
+                let new_z = self.bin(BinOp::And, 1, z, c);
+                let new_c = self.bin(BinOp::Or, 1, c, z);
+                self.set_flags([n, new_z, new_c, v]);
+                Ok(())
+            }
+            Op::MRS if self.operand(1)? == Operand::SysReg(SysReg::NZCV) => {
+                let dst = self.reg(0)?;
+                let mut r = Value::Const(0);
+                for (n, flag) in [Flag::N, Flag::Z, Flag::C, Flag::V].into_iter().enumerate() {
+                    let f = self.flag(flag);
+                    let f = self.cast(CastOp::ZeroExtend, 1, 64, f);
+                    let f = self.bin(BinOp::Shl, 64, f, Value::Const(31 - n as u64));
+                    r = match r {
+                        Value::Const(0) => f,
+                        _ => self.bin(BinOp::Or, 64, r, f),
+                    };
+                }
+                self.write(dst, r)
+            }
+            Op::MSR if self.operand(0)? == Operand::SysReg(SysReg::NZCV) => {
+                let v = self.value(1, 64)?;
+                for (n, flag) in [Flag::N, Flag::Z, Flag::C, Flag::V].into_iter().enumerate() {
+                    let f = self.bin(BinOp::LShr, 64, v, Value::Const(31 - n as u64));
+                    self.set_flag(flag, f);
+                }
+                Ok(())
+            }
+
+            Op::LDR
+            | Op::LDUR
+            | Op::LDAR
+            | Op::LDAPR
+            | Op::LDAPUR
+            | Op::LDLAR
+            | Op::LDXR
+            | Op::LDAXR
+            | Op::LDTR => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                self.lift_load(bits, None)
+            }
+            Op::LDRB
+            | Op::LDURB
+            | Op::LDARB
+            | Op::LDAPRB
+            | Op::LDAPURB
+            | Op::LDLARB
+            | Op::LDXRB
+            | Op::LDAXRB
+            | Op::LDTRB => self.lift_load(8, None),
+            Op::LDRH
+            | Op::LDURH
+            | Op::LDARH
+            | Op::LDAPRH
+            | Op::LDAPURH
+            | Op::LDLARH
+            | Op::LDXRH
+            | Op::LDAXRH
+            | Op::LDTRH => self.lift_load(16, None),
+            Op::LDRSB | Op::LDURSB | Op::LDAPURSB | Op::LDTRSB => {
+                self.lift_load(8, Some(CastOp::SignExtend))
+            }
+            Op::LDRSH | Op::LDURSH | Op::LDAPURSH | Op::LDTRSH => {
+                self.lift_load(16, Some(CastOp::SignExtend))
+            }
+            Op::LDRSW | Op::LDURSW | Op::LDAPURSW | Op::LDTRSW => {
+                self.lift_load(32, Some(CastOp::SignExtend))
+            }
+            Op::LDP | Op::LDNP | Op::LDXP | Op::LDAXP => {
+                let dst = self.reg(0)?;
+                let bits = self.width(dst)?;
+                self.lift_load_pair(bits, None)
+            }
+            Op::LDPSW => self.lift_load_pair(32, Some(CastOp::SignExtend)),
+
+            Op::STR | Op::STUR | Op::STLR | Op::STLLR | Op::STLUR | Op::STTR => {
+                let src = self.reg(0)?;
+                let bits = self.width(src)?;
+                self.lift_store(0, bits)
+            }
+            Op::STRB | Op::STURB | Op::STLRB | Op::STLLRB | Op::STLURB | Op::STTRB => {
+                self.lift_store(0, 8)
+            }
+            Op::STRH | Op::STURH | Op::STLRH | Op::STLLRH | Op::STLURH | Op::STTRH => {
+                self.lift_store(0, 16)
+            }
+            Op::STP | Op::STNP => {
+                let src = self.reg(0)?;
+                let bits = self.width(src)?;
+                self.lift_store_pair(0, bits)
+            }
+            Op::STXR | Op::STLXR => {
+                let src = self.reg(1)?;
+                let bits = self.width(src)?;
+                self.lift_store(1, bits)?;
+                self.write(self.reg(0)?, Value::Const(0))
+            }
+            Op::STXRB | Op::STLXRB => {
+                self.lift_store(1, 8)?;
+                self.write(self.reg(0)?, Value::Const(0))
+            }
+            Op::STXRH | Op::STLXRH => {
+                self.lift_store(1, 16)?;
+                self.write(self.reg(0)?, Value::Const(0))
+            }
+            Op::STXP | Op::STLXP => {
+                let src = self.reg(1)?;
+                let bits = self.width(src)?;
+                self.lift_store_pair(1, bits)?;
+                self.write(self.reg(0)?, Value::Const(0))
+            }
+
+            Op::FMOV => self.lift_fmov(),
+            Op::FADD
+            | Op::FSUB
+            | Op::FMUL
+            | Op::FDIV
+            | Op::FNMUL
+            | Op::FMIN
+            | Op::FMAX
+            | Op::FMINNM
+            | Op::FMAXNM => {
+                let dst = self.reg(0)?;
+                let bits = self.fp_width(dst)?;
+                let a = self.value(1, bits)?;
+                let b = self.value(2, bits)?;
+                let bin = match op {
+                    Op::FADD => BinOp::FAdd,
+                    Op::FSUB => BinOp::FSub,
+                    Op::FMUL | Op::FNMUL => BinOp::FMul,
+                    Op::FDIV => BinOp::FDiv,
+                    Op::FMIN => BinOp::FMin,
+                    Op::FMAX => BinOp::FMax,
+                    Op::FMINNM => BinOp::FMinNm,
+                    _ => BinOp::FMaxNm,
+                };
+                let r = self.bin(bin, bits, a, b);
+                let r = if op == Op::FNMUL {
+                    self.un(UnOp::FNeg, bits, r)
+                } else {
+                    r
+                };
+                self.write(dst, r)
+            }
+            Op::FNEG | Op::FABS | Op::FSQRT => {
+                let dst = self.reg(0)?;
+                let bits = self.fp_width(dst)?;
+                let v = self.value(1, bits)?;
+                let un = match op {
+                    Op::FNEG => UnOp::FNeg,
+                    Op::FABS => UnOp::FAbs,
+                    _ => UnOp::FSqrt,
+                };
+                let r = self.un(un, bits, v);
+                self.write(dst, r)
+            }
+            Op::FCMP | Op::FCMPE | Op::FCCMP | Op::FCCMPE => {
+                let a = self.reg(0)?;
+                let bits = self.fp_width(a)?;
+                let a = self.read(a)?;
+                let b = match self.operand(1)? {
+                    Operand::FImm32(0) => Value::Const(0),
+                    _ => self.value(1, bits)?,
+                };
+                let lt = self.bin(BinOp::FLt, bits, a, b);
+                let eq = self.bin(BinOp::FEq, bits, a, b);
+                let unord = self.bin(BinOp::FUnord, bits, a, b);
+                let ge = self.not1(lt);
+                let flags = [lt, eq, ge, unord];
+
+                if matches!(op, Op::FCMP | Op::FCMPE) {
+                    self.set_flags(flags);
+                } else {
+                    let nzcv = self.imm(2)?;
+                    let cond = self.cond(3)?;
+                    self.conditional_flags(cond, flags, nzcv);
+                }
+                Ok(())
+            }
+            Op::SCVTF | Op::UCVTF | Op::FCVTZS | Op::FCVTZU | Op::FCVT => {
+                // the fixed point forms carry an extra operand
+                if self.ins.operands().len() != 2 {
+                    return self.unsupported();
+                }
+
+                let dst = self.reg(0)?;
+                let src = self.reg(1)?;
+                let (cast, to, from) = match op {
+                    Op::SCVTF => (CastOp::SIntToFp, self.fp_width(dst)?, self.width(src)?),
+                    Op::UCVTF => (CastOp::UIntToFp, self.fp_width(dst)?, self.width(src)?),
+                    Op::FCVTZS => (CastOp::FpToSInt, self.width(dst)?, self.fp_width(src)?),
+                    Op::FCVTZU => (CastOp::FpToUInt, self.width(dst)?, self.fp_width(src)?),
+                    _ => (CastOp::FpToFp, self.fp_width(dst)?, self.fp_width(src)?),
+                };
+                let v = self.read(src)?;
+                let r = self.cast(cast, from, to, v);
+                self.write(dst, r)
+            }
+
+            _ => self.unsupported(),
+        }
+    }
+
+    fn vector(&self, n: usize) -> Result<Vector> {
+        match self.operand(n)? {
+            Operand::Reg {
+                reg,
+                arrspec: Some(spec),
+            } => {
+                let (bits, lane) = arrangement(spec);
+                let whole = vector_alias(reg, bits);
+                let element = vector_alias(reg, 128);
+
+                match (spec.lane(), whole, element) {
+                    (None, Some(reg), _) if bits >= 64 => Ok(Vector::Whole { reg, bits, lane }),
+                    (Some(index), _, Some(reg)) => Ok(Vector::Element { reg, lane, index }),
+                    _ => self.unsupported(),
+                }
+            }
+            _ => self.unsupported(),
+        }
+    }
+
+    /// Builds a constant wider than 64 bits
+    fn wide_const(&mut self, c: u128, bits: u8) -> Value {
+        let lo = Value::Const(c as u64);
+        let hi = (c >> 64) as u64;
+
+        if bits <= 64 || hi == 0 {
+            return lo;
+        }
+
+        let hi = self.cast(CastOp::ZeroExtend, 64, bits, Value::Const(hi));
+        let hi = self.bin(BinOp::Shl, bits, hi, Value::Const(64));
+        self.bin(BinOp::Or, bits, hi, lo)
+    }
+
+    /// Reads one element of a vector register, extended to `bits`
+    fn read_element(
+        &mut self,
+        reg: Reg,
+        lane: u8,
+        index: u32,
+        bits: u8,
+        cast: CastOp,
+    ) -> Result<Value> {
+        let v = self.read(reg)?;
+        let v = match index * lane as u32 {
+            0 => v,
+            shift => self.bin(BinOp::LShr, 128, v, Value::Const(shift as u64)),
+        };
+
+        Ok(self.cast(cast, lane, bits, v))
+    }
+
+    /// Replaces one element of a vector register
+    fn write_element(&mut self, reg: Reg, lane: u8, index: u32, v: Value) -> Result<()> {
+        let shift = index as u64 * lane as u64;
+        if shift + lane as u64 > 128 {
+            return self.bad_operands();
+        }
+        let keep = !(replicate(!0, lane, lane) << shift);
+
+        let old = self.read(reg)?;
+        let keep = self.wide_const(keep, 128);
+        let old = self.bin(BinOp::And, 128, old, keep);
+        let v = self.cast(CastOp::ZeroExtend, lane, 128, v);
+        let v = match shift {
+            0 => v,
+            _ => self.bin(BinOp::Shl, 128, v, Value::Const(shift)),
+        };
+        let r = self.bin(BinOp::Or, 128, old, v);
+
+        self.write(reg, r)
+    }
+
+    fn lift_vector(&mut self) -> Result<()> {
+        let op = self.ins.op();
+
+        match op {
+            Op::LD1 | Op::ST1 => return self.lift_vector_mem(),
+            Op::UMOV | Op::SMOV => return self.lift_vector_to_general(),
+            // element moves into scalar registers
+            _ if matches!(self.operand(0)?, Operand::Reg { arrspec: None, .. }) => {
+                return self.lift_vector_to_general();
+            }
+            _ => (),
+        }
+
+        let dst = self.vector(0)?;
+
+        match (op, dst) {
+            (Op::MOV | Op::INS | Op::FMOV, Vector::Element { reg, lane, index }) => {
+                let v = match self.operand(1)? {
+                    Operand::Reg { arrspec: None, .. } => self.value(1, lane)?,
+                    _ => match self.vector(1)? {
+                        Vector::Element {
+                            reg: src,
+                            lane: src_lane,
+                            index: src_index,
+                        } if src_lane == lane => {
+                            self.read_element(src, lane, src_index, lane, CastOp::ZeroExtend)?
+                        }
+                        _ => return self.bad_operands(),
+                    },
+                };
+
+                self.write_element(reg, lane, index, v)
+            }
+            (Op::MOV | Op::ORR, Vector::Whole { reg, .. })
+                if self.ins.operands().len() == 2
+                    && matches!(self.vector(1), Ok(Vector::Whole { .. })) =>
+            {
+                let Vector::Whole { reg: src, .. } = self.vector(1)? else {
+                    return self.bad_operands();
+                };
+                let v = self.read(src)?;
+                self.write(reg, v)
+            }
+            (
+                Op::AND | Op::ORR | Op::EOR | Op::BIC | Op::ORN | Op::ADD | Op::SUB,
+                Vector::Whole { reg, bits, lane },
+            ) if self.ins.operands().len() == 3 => {
+                let (Vector::Whole { reg: a, .. }, Vector::Whole { reg: b, .. }) =
+                    (self.vector(1)?, self.vector(2)?)
+                else {
+                    return self.bad_operands();
+                };
+                let a = self.read(a)?;
+                let b = self.read(b)?;
+
+                let r = match op {
+                    Op::ADD | Op::SUB => {
+                        let bin = if op == Op::ADD {
+                            BinOp::Add
+                        } else {
+                            BinOp::Sub
+                        };
+                        let mut r = None;
+
+                        for n in 0..(bits / lane) as u64 {
+                            let shift = n * lane as u64;
+                            let (x, y) = match shift {
+                                0 => (a, b),
+                                _ => (
+                                    self.bin(BinOp::LShr, bits, a, Value::Const(shift)),
+                                    self.bin(BinOp::LShr, bits, b, Value::Const(shift)),
+                                ),
+                            };
+                            let v = self.bin(bin, lane, x, y);
+                            let v = self.cast(CastOp::ZeroExtend, lane, bits, v);
+                            let v = match shift {
+                                0 => v,
+                                _ => self.bin(BinOp::Shl, bits, v, Value::Const(shift)),
+                            };
+                            r = Some(match r {
+                                Some(acc) => self.bin(BinOp::Or, bits, acc, v),
+                                None => v,
+                            });
+                        }
+
+                        r.unwrap()
+                    }
+                    _ => {
+                        let b = match op {
+                            Op::BIC | Op::ORN => self.un(UnOp::Not, bits, b),
+                            _ => b,
+                        };
+                        let bin = match op {
+                            Op::AND | Op::BIC => BinOp::And,
+                            Op::ORR | Op::ORN => BinOp::Or,
+                            _ => BinOp::Xor,
+                        };
+                        self.bin(bin, bits, a, b)
+                    }
+                };
+
+                self.write(reg, r)
+            }
+            (Op::MOVI | Op::MVNI | Op::ORR | Op::BIC, Vector::Whole { reg, bits, lane }) => {
+                let imm = self.imm(1)?;
+                let imm = match op {
+                    Op::MVNI | Op::BIC => !imm,
+                    _ => imm,
+                };
+                let c = replicate(imm, lane, bits);
+                let c = self.wide_const(c, bits);
+
+                let r = match op {
+                    Op::ORR => {
+                        let old = self.read(reg)?;
+                        self.bin(BinOp::Or, bits, old, c)
+                    }
+                    Op::BIC => {
+                        let old = self.read(reg)?;
+                        self.bin(BinOp::And, bits, old, c)
+                    }
+                    _ => c,
+                };
+
+                self.write(reg, r)
+            }
+            (Op::FMOV, Vector::Whole { reg, bits, lane }) => {
+                let Operand::FImm32(imm) = self.operand(1)? else {
+                    return self.bad_operands();
+                };
+                let imm = match lane {
+                    32 => imm as u64,
+                    64 => (f32::from_bits(imm) as f64).to_bits(),
+                    _ => return self.unsupported(),
+                };
+                let c = self.wide_const(replicate(imm, lane, bits), bits);
+
+                self.write(reg, c)
+            }
+            (Op::DUP, Vector::Whole { reg, bits, lane }) => {
+                let v = match self.operand(1)? {
+                    Operand::Reg { arrspec: None, .. } => {
+                        let v = self.value(1, lane)?;
+                        self.cast(CastOp::ZeroExtend, lane, bits, v)
+                    }
+                    _ => match self.vector(1)? {
+                        Vector::Element {
+                            reg: src,
+                            lane: src_lane,
+                            index,
+                        } if src_lane == lane => {
+                            self.read_element(src, lane, index, bits, CastOp::ZeroExtend)?
+                        }
+                        _ => return self.bad_operands(),
+                    },
+                };
+                let ones = self.wide_const(replicate(1, lane, bits), bits);
+                let r = self.bin(BinOp::Mul, bits, v, ones);
+
+                self.write(reg, r)
+            }
+            (Op::EXT, Vector::Whole { reg, bits, .. }) => {
+                let (Vector::Whole { reg: lo, .. }, Vector::Whole { reg: hi, .. }) =
+                    (self.vector(1)?, self.vector(2)?)
+                else {
+                    return self.bad_operands();
+                };
+                let shift = self.imm(3)? * 8;
+                let lo = self.read(lo)?;
+                let hi = self.read(hi)?;
+
+                let r = match shift {
+                    0 => lo,
+                    _ => {
+                        let lo = self.bin(BinOp::LShr, bits, lo, Value::Const(shift));
+                        let hi = self.bin(BinOp::Shl, bits, hi, Value::Const(bits as u64 - shift));
+                        self.bin(BinOp::Or, bits, hi, lo)
+                    }
+                };
+
+                self.write(reg, r)
+            }
+            _ => self.unsupported(),
+        }
+    }
+
+    /// Lifts `UMOV`, `SMOV` and their aliases that copy an element out
+    fn lift_vector_to_general(&mut self) -> Result<()> {
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        let Vector::Element { reg, lane, index } = self.vector(1)? else {
+            return self.bad_operands();
+        };
+        let cast = if self.ins.op() == Op::SMOV {
+            CastOp::SignExtend
+        } else {
+            CastOp::ZeroExtend
+        };
+
+        let v = self.read_element(reg, lane, index, bits, cast)?;
+        self.write(dst, v)
+    }
+
+    /// Lifts the multiple structure forms of `LD1` and `ST1`
+    fn lift_vector_mem(&mut self) -> Result<()> {
+        let Operand::MultiReg {
+            regs,
+            arrspec: Some(spec),
+        } = self.operand(0)?
+        else {
+            return self.bad_operands();
+        };
+
+        let (bits, _) = arrangement(spec);
+        if spec.lane().is_some() || bits < 64 {
+            return self.unsupported();
+        }
+
+        let mut aliases = [None; 4];
+        for (alias, reg) in aliases.iter_mut().zip(regs.iter().flatten()) {
+            match vector_alias(*reg, bits) {
+                Some(r) => *alias = Some(r),
+                None => return self.unsupported(),
+            }
+        }
+
+        let load = self.ins.op() == Op::LD1;
+        let mut values = [Value::Const(0); 4];
+        if !load {
+            for (v, reg) in values.iter_mut().zip(aliases.iter().flatten()) {
+                *v = self.read(*reg)?;
+            }
+        }
+
+        let (addr, wb) = self.address(1)?;
+
+        for (n, reg) in aliases.iter().flatten().enumerate() {
+            let at = match n {
+                0 => addr,
+                _ => self.bin(
+                    BinOp::Add,
+                    64,
+                    addr,
+                    Value::Const(n as u64 * bits as u64 / 8),
+                ),
+            };
+
+            if load {
+                let v = self.load(at, bits);
+                self.write(*reg, v)?;
+            } else {
+                self.store(at, values[n], bits);
+            }
+        }
+
+        self.writeback(wb)
+    }
+
+    fn lift_mov(&mut self) -> Result<()> {
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        let v = self.value(1, bits)?;
+        self.write(dst, v)
+    }
+
+    fn lift_movw(&mut self) -> Result<()> {
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        let (imm, shift) = match self.operand(1)? {
+            Operand::Imm32 { imm, shift } | Operand::Imm64 { imm, shift } => match shift {
+                None => (imm_u64(imm), 0),
+                Some(Shift::LSL(s)) => (imm_u64(imm), s),
+                Some(_) => return self.bad_operands(),
+            },
+            _ => return self.bad_operands(),
+        };
+
+        let r = match self.ins.op() {
+            Op::MOVZ => Value::Const((imm << shift) & mask(bits)),
+            Op::MOVN => Value::Const(!(imm << shift) & mask(bits)),
+            _ => {
+                let old = self.read(dst)?;
+                let keep = !(0xffff << shift) & mask(bits);
+                let old = self.bin(BinOp::And, bits, old, Value::Const(keep));
+                self.bin(BinOp::Or, bits, old, Value::Const((imm & 0xffff) << shift))
+            }
+        };
+
+        self.write(dst, r)
+    }
+
+    fn lift_add_sub(&mut self) -> Result<()> {
+        let op = self.ins.op();
+        let compare = matches!(op, Op::CMP | Op::CMN);
+        let first = if compare { 0 } else { 1 };
+
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        let a = self.value(first, bits)?;
+        let b = self.value(first + 1, bits)?;
+
+        if matches!(op, Op::ADD | Op::SUB) {
+            let bin = if op == Op::ADD {
+                BinOp::Add
+            } else {
+                BinOp::Sub
+            };
+            let r = self.bin(bin, bits, a, b);
+            return self.write(dst, r);
+        }
+
+        let (r, flags) = match op {
+            Op::ADDS | Op::CMN => self.adds(bits, a, b, Value::Const(0)),
+            _ => self.subs(bits, a, b, Value::Const(1)),
+        };
+
+        if !compare {
+            self.write(dst, r)?;
+        }
+
+        self.set_flags(flags);
+        Ok(())
+    }
+
+    fn lift_carry(&mut self) -> Result<()> {
+        let op = self.ins.op();
+
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        let (a, b) = match op {
+            Op::NGC | Op::NGCS => (Value::Const(0), self.value(1, bits)?),
+            _ => (self.value(1, bits)?, self.value(2, bits)?),
+        };
+        let carry = self.flag(Flag::C);
+        let b = match op {
+            Op::ADC | Op::ADCS => b,
+            _ => self.invert(bits, b),
+        };
+
+        let r = self.add_carry(bits, a, b, carry);
+        self.write(dst, r)?;
+
+        if matches!(op, Op::ADCS | Op::SBCS | Op::NGCS) {
+            let flags = self.add_flags(bits, a, b, carry, r);
+            self.set_flags(flags);
+        }
+
+        Ok(())
+    }
+
+    fn lift_logic(&mut self) -> Result<()> {
+        let op = self.ins.op();
+        let first = if op == Op::TST { 0 } else { 1 };
+
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        let a = self.value(first, bits)?;
+        let b = self.value(first + 1, bits)?;
+
+        let b = match (op, b) {
+            (Op::ORN | Op::EON | Op::BIC | Op::BICS, _) => self.invert(bits, b),
+            _ => b,
+        };
+
+        let bin = match op {
+            Op::ORR | Op::ORN => BinOp::Or,
+            Op::EOR | Op::EON => BinOp::Xor,
+            _ => BinOp::And,
+        };
+        let r = self.bin(bin, bits, a, b);
+
+        if op != Op::TST {
+            self.write(dst, r)?;
+        }
+
+        if matches!(op, Op::ANDS | Op::BICS | Op::TST) {
+            let flags = self.logic_flags(bits, r);
+            self.set_flags(flags);
+        }
+
+        Ok(())
+    }
+
+    fn lift_bitfield(&mut self) -> Result<()> {
+        let op = self.ins.op();
+
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+
+        // bfc has no source register
+        let (src, first) = match op {
+            Op::BFC => (Value::Const(0), 1),
+            _ => (self.value(1, bits)?, 2),
+        };
+        let a = self.imm(first)?;
+        let b = self.imm(first + 1)?;
+
+        // convert the raw forms to their aliases
+        let op = match op {
+            Op::UBFM if b >= a => Op::UBFX,
+            Op::UBFM => Op::UBFIZ,
+            Op::SBFM if b >= a => Op::SBFX,
+            Op::SBFM => Op::SBFIZ,
+            Op::BFM if b >= a => Op::BFXIL,
+            Op::BFM => Op::BFI,
+            op => op,
+        };
+        let (lsb, width) = match self.ins.op() {
+            Op::UBFM | Op::SBFM | Op::BFM if b >= a => (a, b - a + 1),
+            Op::UBFM | Op::SBFM | Op::BFM => (bits as u64 - a, b + 1),
+            _ => (a, b),
+        };
+
+        if width == 0 || lsb + width > bits as u64 {
+            return self.bad_operands();
+        }
+
+        let field = mask(width as u8);
+        let r = match op {
+            Op::UBFX => {
+                let v = self.bin(BinOp::LShr, bits, src, Value::Const(lsb));
+                self.bin(BinOp::And, bits, v, Value::Const(field))
+            }
+            Op::SBFX => {
+                let v = self.bin(
+                    BinOp::Shl,
+                    bits,
+                    src,
+                    Value::Const(bits as u64 - lsb - width),
+                );
+                self.bin(BinOp::AShr, bits, v, Value::Const(bits as u64 - width))
+            }
+            Op::UBFIZ => {
+                let v = self.bin(BinOp::And, bits, src, Value::Const(field));
+                self.bin(BinOp::Shl, bits, v, Value::Const(lsb))
+            }
+            Op::SBFIZ => {
+                let v = self.bin(BinOp::Shl, bits, src, Value::Const(bits as u64 - width));
+                self.bin(
+                    BinOp::AShr,
+                    bits,
+                    v,
+                    Value::Const(bits as u64 - width - lsb),
+                )
+            }
+            _ => {
+                // bfi, bfxil and bfc insert into the destination
+                let old = self.read(dst)?;
+                let v = match op {
+                    Op::BFXIL => self.bin(BinOp::LShr, bits, src, Value::Const(lsb)),
+                    _ => src,
+                };
+                let pos = if op == Op::BFXIL { 0 } else { lsb };
+                let v = self.bin(BinOp::And, bits, v, Value::Const(field));
+                let v = match pos {
+                    0 => v,
+                    _ => self.bin(BinOp::Shl, bits, v, Value::Const(pos)),
+                };
+                let keep = !(field << pos) & mask(bits);
+                let old = self.bin(BinOp::And, bits, old, Value::Const(keep));
+                self.bin(BinOp::Or, bits, old, v)
+            }
+        };
+
+        self.write(dst, r)
+    }
+
+    fn lift_csel(&mut self) -> Result<()> {
+        let op = self.ins.op();
+
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+        if op == Op::FCSEL {
+            self.fp_width(dst)?;
+        }
+
+        let r = match op {
+            Op::CSET | Op::CSETM => {
+                let cond = self.cond(1)?;
+                let c = self.condition(cond);
+                let set = if op == Op::CSET { 1 } else { mask(bits) };
+                self.select(c, Value::Const(set), Value::Const(0))
+            }
+            Op::CINC | Op::CINV | Op::CNEG => {
+                let v = self.value(1, bits)?;
+                let cond = self.cond(2)?;
+                let alt = match op {
+                    Op::CINC => self.bin(BinOp::Add, bits, v, Value::Const(1)),
+                    Op::CINV => self.un(UnOp::Not, bits, v),
+                    _ => self.un(UnOp::Neg, bits, v),
+                };
+                let c = self.condition(cond);
+                self.select(c, alt, v)
+            }
+            _ => {
+                let a = self.value(1, bits)?;
+                let b = self.value(2, bits)?;
+                let cond = self.cond(3)?;
+                let b = match op {
+                    Op::CSINC => self.bin(BinOp::Add, bits, b, Value::Const(1)),
+                    Op::CSINV => self.un(UnOp::Not, bits, b),
+                    Op::CSNEG => self.un(UnOp::Neg, bits, b),
+                    _ => b,
+                };
+                let c = self.condition(cond);
+                self.select(c, a, b)
+            }
+        };
+
+        self.write(dst, r)
+    }
+
+    fn lift_load(&mut self, bits: u8, ext: Option<CastOp>) -> Result<()> {
+        let dst = self.reg(0)?;
+        let width = self.width(dst)?;
+        let (addr, wb) = self.address(1)?;
+
+        let v = self.load(addr, bits);
+        let v = match ext {
+            Some(cast) => self.cast(cast, bits, width, v),
+            None => v,
+        };
+
+        self.write(dst, v)?;
+        self.writeback(wb)
+    }
+
+    fn lift_load_pair(&mut self, bits: u8, ext: Option<CastOp>) -> Result<()> {
+        let first = self.reg(0)?;
+        let second = self.reg(1)?;
+        let width = self.width(first)?;
+        let (addr, wb) = self.address(2)?;
+
+        let next = self.bin(BinOp::Add, 64, addr, Value::Const(bits as u64 / 8));
+        let a = self.load(addr, bits);
+        let b = self.load(next, bits);
+        let (a, b) = match ext {
+            Some(cast) => (
+                self.cast(cast, bits, width, a),
+                self.cast(cast, bits, width, b),
+            ),
+            None => (a, b),
+        };
+
+        self.write(first, a)?;
+        self.write(second, b)?;
+        self.writeback(wb)
+    }
+
+    fn lift_store(&mut self, n: usize, bits: u8) -> Result<()> {
+        let src = self.value(n, bits)?;
+        let (addr, wb) = self.address(n + 1)?;
+
+        self.store(addr, src, bits);
+        self.writeback(wb)
+    }
+
+    fn lift_store_pair(&mut self, n: usize, bits: u8) -> Result<()> {
+        let a = self.value(n, bits)?;
+        let b = self.value(n + 1, bits)?;
+        let (addr, wb) = self.address(n + 2)?;
+
+        let next = self.bin(BinOp::Add, 64, addr, Value::Const(bits as u64 / 8));
+        self.store(addr, a, bits);
+        self.store(next, b, bits);
+        self.writeback(wb)
+    }
+
+    fn lift_fmov(&mut self) -> Result<()> {
+        let dst = self.reg(0)?;
+        let bits = self.width(dst)?;
+
+        let v = match self.operand(1)? {
+            Operand::FImm32(imm) => match bits {
+                32 => Value::Const(imm as u64),
+                64 => Value::Const((f32::from_bits(imm) as f64).to_bits()),
+                _ => return self.unsupported(),
+            },
+            _ => {
+                let src = self.reg(1)?;
+                let from = self.width(src)?;
+                let v = self.read(src)?;
+                if from != bits {
+                    self.cast(CastOp::ZeroExtend, from.min(bits), bits, v)
+                } else {
+                    v
+                }
+            }
+        };
+
+        self.write(dst, v)
+    }
+}
+
+/// A vector register operand
+#[derive(Clone, Copy)]
+enum Vector {
+    /// A whole vector, through its `D` or `Q` alias
+    Whole { reg: Reg, bits: u8, lane: u8 },
+    /// A single element, through the `Q` alias
+    Element { reg: Reg, lane: u8, index: u32 },
+}
+
+fn arrangement(spec: ArrSpec) -> (u8, u8) {
+    match spec {
+        ArrSpec::Full(_) => (128, 128),
+        ArrSpec::TwoDoubles(_) => (128, 64),
+        ArrSpec::FourSingles(_) => (128, 32),
+        ArrSpec::EightHalves(_) => (128, 16),
+        ArrSpec::SixteenBytes(_) => (128, 8),
+        ArrSpec::OneDouble(_) => (64, 64),
+        ArrSpec::TwoSingles(_) => (64, 32),
+        ArrSpec::FourHalves(_) => (64, 16),
+        ArrSpec::EightBytes(_) => (64, 8),
+        ArrSpec::OneSingle(_) => (32, 32),
+        ArrSpec::TwoHalves(_) => (32, 16),
+        ArrSpec::FourBytes(_) => (32, 8),
+        ArrSpec::OneHalf(_) => (16, 16),
+        ArrSpec::OneByte(_) => (8, 8),
+    }
+}
+
+/// Returns the `D` or `Q` alias of a `V` register
+fn vector_alias(reg: Reg, bits: u8) -> Option<Reg> {
+    use num_traits::{FromPrimitive, ToPrimitive};
+
+    if !reg.is_simd() {
+        return None;
+    }
+
+    let n = reg.to_u32().unwrap() - Reg::V0.to_u32().unwrap();
+    let base = if bits == 64 { Reg::D0 } else { Reg::Q0 };
+
+    Reg::from_u32(base.to_u32().unwrap() + n)
+}
+
+/// Repeats a lane value across a vector
+fn replicate(value: u64, lane: u8, bits: u8) -> u128 {
+    let value = match lane {
+        128 => value as u128,
+        _ => value as u128 & ((1u128 << lane) - 1),
+    };
+
+    (0..bits / lane).fold(0, |acc, n| acc | value << (n * lane))
+}
+
+fn is_fp(reg: Reg) -> bool {
+    use num_traits::ToPrimitive;
+
+    let r = reg.to_u32().unwrap();
+
+    (Reg::B0.to_u32().unwrap()..=Reg::D31.to_u32().unwrap()).contains(&r)
+}
@@ -0,0 +1,601 @@
+//! Mach-O loading
+//!
+//! A minimal reader for 64-bit arm64 and arm64e Mach-O images, including
+//! universal (fat) binaries, which finds the executable sections and
+//! functions to pass to [`crate::disasm`].
+//!
+//! Function boundaries come from `LC_FUNCTION_STARTS`, or from the symbol
+//! table if it is missing, as it is in object files. Jump tables and other
+//! data marked by `LC_DATA_IN_CODE` are skipped. Names are resolved from
+//! the symbol table, and from the indirect symbol table for stubs.
+//!
+//! # Example
+//! ```no_run
+//! use bad64::macho::MachO;
+//!
+//! let buf = std::fs::read("a.out").unwrap();
+//! let macho = MachO::parse(&buf).unwrap();
+//!
+//! for func in macho.functions() {
+//!     println!("{}:", func.name.unwrap_or("?"));
+//!
+//!     for ins in func.disasm().filter_map(Result::ok) {
+//!         println!("    {:x}: {}", ins.address(), ins);
+//!     }
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::reader::{self, Truncated, string};
+use crate::{DecodeError, Instruction, disasm_skipping};
+
+const FAT_MAGIC: u32 = 0xcafe_babe;
+const FAT_MAGIC_64: u32 = 0xcafe_babf;
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+
+/// `CPU_TYPE_ARM64`
+pub const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+/// `CPU_SUBTYPE_ARM64E`
+pub const CPU_SUBTYPE_ARM64E: u32 = 2;
+const CPU_SUBTYPE_MASK: u32 = 0x00ff_ffff;
+
+const MH_OBJECT: u32 = 1;
+
+const LC_SYMTAB: u32 = 0x2;
+const LC_DYSYMTAB: u32 = 0xb;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_FUNCTION_STARTS: u32 = 0x26;
+const LC_DATA_IN_CODE: u32 = 0x29;
+
+const S_ZEROFILL: u32 = 0x1;
+const S_SYMBOL_STUBS: u32 = 0x8;
+const S_THREAD_LOCAL_ZEROFILL: u32 = 0x12;
+const S_ATTR_PURE_INSTRUCTIONS: u32 = 0x8000_0000;
+const S_ATTR_SOME_INSTRUCTIONS: u32 = 0x400;
+
+const N_STAB: u8 = 0xe0;
+const N_TYPE: u8 = 0x0e;
+const N_EXT: u8 = 0x01;
+const N_SECT: u8 = 0x0e;
+
+const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+/// Mach-O parsing errors
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum MachOError {
+    /// The file is not a 64-bit little-endian Mach-O or universal binary
+    BadMagic,
+    /// The image is not for arm64
+    BadCpuType(u32),
+    /// The universal binary has no arm64 slice
+    NoSlice,
+    /// A header, load command or table extends past the end of the file
+    Truncated,
+}
+
+impl fmt::Display for MachOError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MachOError::BadMagic => write!(f, "Bad magic"),
+            MachOError::BadCpuType(t) => write!(f, "Bad cpu type: {:#x}", t),
+            MachOError::NoSlice => write!(f, "No arm64 slice"),
+            MachOError::Truncated => write!(f, "Truncated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MachOError {}
+
+impl Truncated for MachOError {
+    const TRUNCATED: Self = MachOError::Truncated;
+}
+
+type Reader<'a> = reader::Reader<'a, MachOError>;
+
+/// An architecture slice of a universal binary
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Slice<'a> {
+    pub cpu_type: u32,
+    /// The CPU subtype, without the capability bits
+    pub cpu_subtype: u32,
+    /// The slice's Mach-O image
+    pub data: &'a [u8],
+}
+
+/// A section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Section<'a> {
+    pub segment: &'a str,
+    pub name: &'a str,
+    pub address: u64,
+    /// The section contents, empty for zero fill sections
+    pub data: &'a [u8],
+    /// The section flags, holding the type and attributes
+    pub flags: u32,
+    reserved1: u32,
+    reserved2: u32,
+}
+
+impl Section<'_> {
+    /// Returns if the section contains instructions
+    pub fn is_executable(&self) -> bool {
+        self.flags & (S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS) != 0
+    }
+
+    /// Returns the section type (`S_*`)
+    pub fn kind(&self) -> u32 {
+        self.flags & 0xff
+    }
+
+    /// Returns the address after the section's contents
+    fn end(&self) -> u64 {
+        self.address.saturating_add(self.data.len() as u64)
+    }
+}
+
+/// A symbol defined in a section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub address: u64,
+    /// Index into [`MachO::sections`]
+    pub section: usize,
+    /// The symbol is visible outside the image
+    pub external: bool,
+}
+
+/// A function
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Function<'a> {
+    pub name: Option<&'a str>,
+    pub address: u64,
+    /// The function's bytes
+    pub data: &'a [u8],
+    /// Index into [`MachO::sections`]
+    pub section: usize,
+    data_in_code: Vec<Range<u64>>,
+}
+
+impl Function<'_> {
+    /// Disassemble the function, skipping data in code
+    pub fn disasm(&self) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
+        disasm_skipping(self.data, self.address, &self.data_in_code)
+    }
+
+    /// Returns the address ranges holding data rather than instructions
+    pub fn data_in_code(&self) -> &[Range<u64>] {
+        &self.data_in_code
+    }
+}
+
+/// A parsed arm64 Mach-O image
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MachO<'a> {
+    cpu_subtype: u32,
+    file_type: u32,
+    sections: Vec<Section<'a>>,
+    symbols: Vec<Symbol<'a>>,
+    stubs: Vec<(u64, &'a str)>,
+    function_starts: Vec<u64>,
+    data_in_code: Vec<Range<u64>>,
+}
+
+/// Returns the architecture slices of a universal binary
+///
+/// A thin image is returned as a single slice.
+pub fn slices(data: &[u8]) -> Result<Vec<Slice<'_>>, MachOError> {
+    let be = Reader::new(data, true);
+
+    let (size, fat64) = match be.u32(0)? {
+        FAT_MAGIC => (20, false),
+        FAT_MAGIC_64 => (32, true),
+        _ => {
+            let le = Reader::new(data, false);
+            if le.u32(0)? != MH_MAGIC_64 {
+                return Err(MachOError::BadMagic);
+            }
+
+            return Ok(alloc::vec![Slice {
+                cpu_type: le.u32(4)?,
+                cpu_subtype: le.u32(8)? & CPU_SUBTYPE_MASK,
+                data,
+            }]);
+        }
+    };
+
+    (0..be.u32(4)? as usize)
+        .map(|n| {
+            let off = 8 + n * size;
+            let (offset, len) = match fat64 {
+                true => (be.u64(off + 8)?, be.u64(off + 16)?),
+                false => (be.u32(off + 8)? as u64, be.u32(off + 12)? as u64),
+            };
+
+            Ok(Slice {
+                cpu_type: be.u32(off)?,
+                cpu_subtype: be.u32(off + 4)? & CPU_SUBTYPE_MASK,
+                data: be.bytes(offset as usize, len as usize)?,
+            })
+        })
+        .collect()
+}
+
+impl<'a> MachO<'a> {
+    /// Parse a Mach-O image or universal binary
+    ///
+    /// From a universal binary the arm64e slice is picked, or the arm64
+    /// slice if there is none.
+    pub fn parse(data: &'a [u8]) -> Result<Self, MachOError> {
+        let slices = slices(data)?;
+
+        let slice = slices
+            .iter()
+            .filter(|s| s.cpu_type == CPU_TYPE_ARM64)
+            .max_by_key(|s| s.cpu_subtype == CPU_SUBTYPE_ARM64E)
+            .ok_or(match slices.as_slice() {
+                [thin] => MachOError::BadCpuType(thin.cpu_type),
+                _ => MachOError::NoSlice,
+            })?;
+
+        Self::parse_image(slice.data)
+    }
+
+    /// Parse a thin Mach-O image
+    pub fn parse_image(data: &'a [u8]) -> Result<Self, MachOError> {
+        let r = Reader::new(data, false);
+
+        if r.u32(0)? != MH_MAGIC_64 {
+            return Err(MachOError::BadMagic);
+        }
+
+        let cpu_type = r.u32(4)?;
+        if cpu_type != CPU_TYPE_ARM64 {
+            return Err(MachOError::BadCpuType(cpu_type));
+        }
+
+        let mut macho = Self {
+            cpu_subtype: r.u32(8)? & CPU_SUBTYPE_MASK,
+            file_type: r.u32(12)?,
+            sections: Vec::new(),
+            symbols: Vec::new(),
+            stubs: Vec::new(),
+            function_starts: Vec::new(),
+            data_in_code: Vec::new(),
+        };
+
+        let ncmds = r.u32(16)?;
+
+        // (vmaddr, fileoff, filesize) of each segment, for file offsets
+        let mut segments = Vec::new();
+        let mut text = None;
+        let mut symtab = None;
+        let mut indirect = None;
+        let mut function_starts = None;
+        let mut dice = None;
+
+        let mut off = 32;
+        for _ in 0..ncmds {
+            let cmd = r.u32(off)?;
+            let size = r.u32(off + 4)? as usize;
+
+            match cmd {
+                LC_SEGMENT_64 => {
+                    let segname = name(r.bytes(off + 8, 16)?);
+                    let vmaddr = r.u64(off + 24)?;
+                    segments.push((vmaddr, r.u64(off + 40)?, r.u64(off + 48)?));
+
+                    if segname == "__TEXT" {
+                        text = Some(vmaddr);
+                    }
+
+                    for n in 0..r.u32(off + 64)? as usize {
+                        let s = off + 72 + n * 80;
+                        let size = r.u64(s + 40)? as usize;
+                        let flags = r.u32(s + 64)?;
+
+                        let data = match flags & 0xff {
+                            S_ZEROFILL | S_THREAD_LOCAL_ZEROFILL => &data[..0],
+                            _ => r.bytes(r.u32(s + 48)? as usize, size)?,
+                        };
+
+                        // a section must not run past the end of the address space
+                        let address = r.u64(s + 32)?;
+                        address
+                            .checked_add(data.len() as u64)
+                            .ok_or(MachOError::Truncated)?;
+
+                        macho.sections.push(Section {
+                            name: name(r.bytes(s, 16)?),
+                            segment: name(r.bytes(s + 16, 16)?),
+                            address,
+                            data,
+                            flags,
+                            reserved1: r.u32(s + 68)?,
+                            reserved2: r.u32(s + 72)?,
+                        });
+                    }
+                }
+                LC_SYMTAB => {
+                    let (symoff, nsyms) = (r.u32(off + 8)? as usize, r.u32(off + 12)? as usize);
+                    let (stroff, strsize) = (r.u32(off + 16)? as usize, r.u32(off + 20)? as usize);
+
+                    let syms =
+                        r.bytes(symoff, nsyms.checked_mul(16).ok_or(MachOError::Truncated)?)?;
+                    symtab = Some((syms, r.bytes(stroff, strsize)?));
+                }
+                LC_DYSYMTAB => {
+                    let (off, n) = (r.u32(off + 56)? as usize, r.u32(off + 60)? as usize);
+                    indirect = Some(r.bytes(off, n.checked_mul(4).ok_or(MachOError::Truncated)?)?);
+                }
+                LC_FUNCTION_STARTS => {
+                    function_starts =
+                        Some(r.bytes(r.u32(off + 8)? as usize, r.u32(off + 12)? as usize)?);
+                }
+                LC_DATA_IN_CODE => {
+                    dice = Some(r.bytes(r.u32(off + 8)? as usize, r.u32(off + 12)? as usize)?);
+                }
+                _ => (),
+            }
+
+            off = off.checked_add(size).ok_or(MachOError::Truncated)?;
+        }
+
+        // name all symbols first, as stubs refer to undefined ones
+        let mut names = Vec::new();
+        if let Some((syms, strtab)) = symtab {
+            let syms = Reader::new(syms, false);
+
+            for n in 0..syms.data.len() / 16 {
+                let off = n * 16;
+                let kind = syms.data[off + 4];
+                let sect = syms.data[off + 5];
+                let name = string(strtab, syms.u32(off)? as usize);
+
+                names.push(name);
+
+                if kind & N_STAB != 0 || kind & N_TYPE != N_SECT || sect == 0 {
+                    continue;
+                }
+
+                // skip symbols in sections which do not exist
+                if sect as usize > macho.sections.len() {
+                    continue;
+                }
+
+                macho.symbols.push(Symbol {
+                    name,
+                    address: syms.u64(off + 8)?,
+                    section: sect as usize - 1,
+                    external: kind & N_EXT != 0,
+                });
+            }
+        }
+
+        // external symbols sort before local ones at the same address
+        macho.symbols.sort_by_key(|s| (s.address, !s.external));
+
+        if let Some(indirect) = indirect {
+            let indirect = Reader::new(indirect, false);
+
+            for s in macho.sections.iter().filter(|s| s.kind() == S_SYMBOL_STUBS) {
+                let stub_size = s.reserved2 as u64;
+                if stub_size == 0 {
+                    continue;
+                }
+
+                for n in 0..s.data.len() as u64 / stub_size {
+                    let Ok(index) = indirect.u32((s.reserved1 as usize + n as usize) * 4) else {
+                        break;
+                    };
+
+                    if index & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) != 0 {
+                        continue;
+                    }
+
+                    if let Some(name) = names.get(index as usize) {
+                        macho.stubs.push((s.address + n * stub_size, name));
+                    }
+                }
+            }
+        }
+
+        if let (Some(starts), Some(text)) = (function_starts, text) {
+            let mut address = text;
+            let mut bytes = starts.iter();
+
+            while let Some(delta) = uleb128(&mut bytes) {
+                if delta == 0 {
+                    break;
+                }
+
+                address = address.checked_add(delta).ok_or(MachOError::Truncated)?;
+                macho.function_starts.push(address);
+            }
+        }
+
+        if let Some(dice) = dice {
+            let dice = Reader::new(dice, false);
+
+            for n in 0..dice.data.len() / 8 {
+                let offset = dice.u32(n * 8)? as u64;
+                let len = dice.u16(n * 8 + 4)? as u64;
+
+                // objects hold addresses, linked images file offsets
+                let address = match macho.file_type {
+                    MH_OBJECT => Some(offset),
+                    _ => segments
+                        .iter()
+                        .find(|(_, fileoff, filesize)| {
+                            offset.checked_sub(*fileoff).is_some_and(|o| o < *filesize)
+                        })
+                        .map(|(vmaddr, fileoff, _)| {
+                            vmaddr
+                                .checked_add(offset - fileoff)
+                                .ok_or(MachOError::Truncated)
+                        })
+                        .transpose()?,
+                };
+
+                if let Some(address) = address {
+                    let end = address.checked_add(len).ok_or(MachOError::Truncated)?;
+                    macho.data_in_code.push(address..end);
+                }
+            }
+
+            macho.data_in_code.sort_by_key(|r| r.start);
+        }
+
+        Ok(macho)
+    }
+
+    /// Returns if the image is arm64e, using pointer authentication
+    pub fn is_arm64e(&self) -> bool {
+        self.cpu_subtype == CPU_SUBTYPE_ARM64E
+    }
+
+    /// Returns the file type (`MH_*`)
+    pub fn file_type(&self) -> u32 {
+        self.file_type
+    }
+
+    /// Returns every section
+    pub fn sections(&self) -> &[Section<'a>] {
+        &self.sections
+    }
+
+    /// Returns the sections containing instructions
+    pub fn executable_sections(&self) -> impl Iterator<Item = &Section<'a>> {
+        self.sections.iter().filter(|s| s.is_executable())
+    }
+
+    /// Returns a section by segment and section name, such as `__TEXT` and
+    /// `__text`
+    pub fn section_by_name(&self, segment: &str, name: &str) -> Option<&Section<'a>> {
+        self.sections
+            .iter()
+            .find(|s| s.segment == segment && s.name == name)
+    }
+
+    /// Returns the symbols defined in sections, sorted by address
+    pub fn symbols(&self) -> &[Symbol<'a>] {
+        &self.symbols
+    }
+
+    /// Returns the address and target name of each symbol stub
+    pub fn stubs(&self) -> &[(u64, &'a str)] {
+        &self.stubs
+    }
+
+    /// Returns the name of the symbol or stub at an address
+    ///
+    /// External symbols are preferred over local ones.
+    pub fn symbol_at(&self, address: u64) -> Option<&'a str> {
+        let n = self.symbols.partition_point(|s| s.address < address);
+
+        match self.symbols.get(n) {
+            Some(s) if s.address == address => Some(s.name),
+            _ => self
+                .stubs
+                .iter()
+                .find(|(a, _)| *a == address)
+                .map(|(_, name)| *name),
+        }
+    }
+
+    /// Returns the ranges marked as data by `LC_DATA_IN_CODE`
+    pub fn data_in_code(&self) -> &[Range<u64>] {
+        &self.data_in_code
+    }
+
+    /// Disassemble a section, skipping data in code
+    pub fn disasm<'s>(
+        &'s self,
+        section: &'s Section<'a>,
+    ) -> impl Iterator<Item = Result<Instruction, DecodeError>> + 's {
+        disasm_skipping(section.data, section.address, &self.data_in_code)
+    }
+
+    /// Returns the functions in executable sections, sorted by address
+    ///
+    /// Each function extends to the next one or the end of its section.
+    pub fn functions(&self) -> Vec<Function<'a>> {
+        let mut starts: Vec<u64> = match self.function_starts.is_empty() {
+            false => self.function_starts.clone(),
+            true => self
+                .symbols
+                .iter()
+                .filter(|s| {
+                    self.sections
+                        .get(s.section)
+                        .is_some_and(|x| x.is_executable())
+                })
+                .map(|s| s.address)
+                .collect(),
+        };
+
+        starts.sort();
+        starts.dedup();
+
+        starts
+            .iter()
+            .enumerate()
+            .filter_map(|(n, start)| {
+                let (index, section) = self
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| s.is_executable())
+                    .find(|(_, s)| (s.address..s.end()).contains(start))?;
+
+                let section_end = section.end();
+                let end = starts
+                    .get(n + 1)
+                    .map_or(section_end, |e| (*e).min(section_end));
+
+                let data = &section.data
+                    [(start - section.address) as usize..(end - section.address) as usize];
+
+                let data_in_code = self
+                    .data_in_code
+                    .iter()
+                    .filter(|r| r.start < end && r.end > *start)
+                    .map(|r| r.start.max(*start)..r.end.min(end))
+                    .collect();
+
+                Some(Function {
+                    name: self.symbol_at(*start),
+                    address: *start,
+                    data,
+                    section: index,
+                    data_in_code,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns a fixed size, nul padded name
+fn name(bytes: &[u8]) -> &str {
+    string(bytes, 0)
+}
+
+fn uleb128<'a>(bytes: &mut impl Iterator<Item = &'a u8>) -> Option<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let b = *bytes.next()?;
+        value |= ((b & 0x7f) as u64) << shift;
+
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,302 @@
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::condition::branch_condition;
+use crate::{Imm, Instruction, Nzcv, Op, Operand, Reg};
+
+/// A read-only view of register state
+///
+/// Implementations return `None` for anything they do not know, in which
+/// case [`next_pcs`] reports every possibility instead of guessing.
+pub trait RegisterView {
+    /// Returns the value of a 64-bit general purpose register (`X0`-`X30` or `SP`)
+    fn reg(&self, reg: Reg) -> Option<u64>;
+
+    /// Returns the condition flags
+    fn nzcv(&self) -> Option<Nzcv>;
+}
+
+/// A view which knows nothing, for every possible next program counter
+impl RegisterView for () {
+    fn reg(&self, _reg: Reg) -> Option<u64> {
+        None
+    }
+
+    fn nzcv(&self) -> Option<Nzcv> {
+        None
+    }
+}
+
+/// A possible next program counter
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NextPc {
+    /// The next sequential instruction
+    Fallthrough(u64),
+    /// The target of a direct branch
+    Taken(u64),
+    /// The target of a register-indirect branch, if the register is known
+    Indirect { reg: Reg, target: Option<u64> },
+    /// Control passes to an exception vector or exception return address
+    Exception,
+}
+
+impl NextPc {
+    /// Returns the target address, if known
+    pub fn address(&self) -> Option<u64> {
+        match *self {
+            Self::Fallthrough(a) | Self::Taken(a) => Some(a),
+            Self::Indirect { target, .. } => target,
+            Self::Exception => None,
+        }
+    }
+}
+
+/// The set of possible next program counters of an instruction
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NextPcs {
+    pcs: [NextPc; 2],
+    len: usize,
+}
+
+impl NextPcs {
+    fn one(pc: NextPc) -> Self {
+        Self {
+            pcs: [pc, NextPc::Exception],
+            len: 1,
+        }
+    }
+
+    fn two(a: NextPc, b: NextPc) -> Self {
+        Self {
+            pcs: [a, b],
+            len: 2,
+        }
+    }
+
+    /// Returns the possible next program counters as a slice
+    pub fn as_slice(&self) -> &[NextPc] {
+        &self.pcs[..self.len]
+    }
+
+    /// Returns an iterator over the possible next program counters
+    pub fn iter(&self) -> core::slice::Iter<'_, NextPc> {
+        self.as_slice().iter()
+    }
+
+    /// Returns the number of possible next program counters
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns if there are no possible next program counters
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'a> IntoIterator for &'a NextPcs {
+    type Item = &'a NextPc;
+    type IntoIter = core::slice::Iter<'a, NextPc>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Compute the possible next program counters of an instruction
+///
+/// Conditional branches are resolved against the view when the flags or
+/// registers they test are known; otherwise both the taken and fallthrough
+/// successors are returned. Pointer authentication codes in indirect targets
+/// are reported as read from the register, not stripped.
+///
+/// `SVC`, `HVC` and `SMC` fall through, as that is where execution resumes.
+///
+/// # Example
+/// ```
+/// use bad64::{decode, next_pcs, NextPc, Nzcv, Reg, RegisterView};
+///
+/// struct Regs {
+///     x: [u64; 31],
+///     nzcv: Nzcv,
+/// }
+///
+/// impl RegisterView for Regs {
+///     fn reg(&self, reg: Reg) -> Option<u64> {
+///         match reg {
+///             Reg::X0 => Some(self.x[0]),
+///             Reg::X30 => Some(self.x[30]),
+///             _ => None,
+///         }
+///     }
+///
+///     fn nzcv(&self) -> Option<Nzcv> {
+///         Some(self.nzcv)
+///     }
+/// }
+///
+/// let mut regs = Regs { x: [0; 31], nzcv: Nzcv::new(false, true, false, false) };
+/// regs.x[30] = 0x4000;
+///
+/// // b.eq 0x1008
+/// let beq = decode(0x54000040, 0x1000).unwrap();
+/// assert_eq!(next_pcs(&beq, &regs).as_slice(), &[NextPc::Taken(0x1008)]);
+///
+/// // tbz w0, #3, 0x1008
+/// let tbz = decode(0x36180040, 0x1000).unwrap();
+/// regs.x[0] = 0b1000;
+/// assert_eq!(next_pcs(&tbz, &regs).as_slice(), &[NextPc::Fallthrough(0x1004)]);
+///
+/// // ret
+/// let ret = decode(0xd65f03c0, 0x1000).unwrap();
+/// assert_eq!(
+///     next_pcs(&ret, &regs).as_slice(),
+///     &[NextPc::Indirect { reg: Reg::X30, target: Some(0x4000) }]
+/// );
+/// ```
+pub fn next_pcs<V: RegisterView + ?Sized>(ins: &Instruction, view: &V) -> NextPcs {
+    let fallthrough = NextPc::Fallthrough(ins.address().wrapping_add(4));
+    let ops = ins.operands();
+
+    let label = || {
+        ops.iter().rev().find_map(|o| match *o {
+            Operand::Label(imm) => Some(NextPc::Taken(imm_u64(imm))),
+            _ => None,
+        })
+    };
+
+    let indirect = |reg: Reg| NextPc::Indirect {
+        reg,
+        target: read_reg(view, reg),
+    };
+
+    let branch = |taken: Option<bool>| match (label(), taken) {
+        (Some(target), Some(true)) => NextPcs::one(target),
+        (Some(_), Some(false)) | (None, _) => NextPcs::one(fallthrough),
+        (Some(target), None) => NextPcs::two(target, fallthrough),
+    };
+
+    if let Some(cond) = branch_condition(ins.op()) {
+        return branch(view.nzcv().map(|nzcv| cond.holds(nzcv)));
+    }
+
+    match ins.op() {
+        Op::B | Op::BL => match label() {
+            Some(target) => NextPcs::one(target),
+            None => NextPcs::one(fallthrough),
+        },
+        Op::CBZ | Op::CBNZ => {
+            let value = match ops.first() {
+                Some(Operand::Reg { reg, .. }) => read_reg(view, *reg),
+                _ => None,
+            };
+
+            branch(value.map(|v| (v == 0) == (ins.op() == Op::CBZ)))
+        }
+        // FEAT_CMPBR compare and branch, whose outcome is not evaluated
+        Op::CBBEQ
+        | Op::CBBGE
+        | Op::CBBGT
+        | Op::CBBHI
+        | Op::CBBHS
+        | Op::CBBLE
+        | Op::CBBLO
+        | Op::CBBLS
+        | Op::CBBLT
+        | Op::CBBNE
+        | Op::CBEQ
+        | Op::CBGE
+        | Op::CBGT
+        | Op::CBHEQ
+        | Op::CBHGE
+        | Op::CBHGT
+        | Op::CBHHI
+        | Op::CBHHS
+        | Op::CBHI
+        | Op::CBHLE
+        | Op::CBHLO
+        | Op::CBHLS
+        | Op::CBHLT
+        | Op::CBHNE
+        | Op::CBHS
+        | Op::CBLE
+        | Op::CBLO
+        | Op::CBLS
+        | Op::CBLT
+        | Op::CBNE => branch(None),
+        Op::TBZ | Op::TBNZ => {
+            let value = match ops.first() {
+                Some(Operand::Reg { reg, .. }) => read_reg(view, *reg),
+                _ => None,
+            };
+            let bit = match ops.get(1) {
+                Some(Operand::Imm32 { imm, .. } | Operand::Imm64 { imm, .. }) => {
+                    Some(imm_u64(*imm))
+                }
+                _ => None,
+            };
+
+            branch(
+                value
+                    .zip(bit)
+                    .map(|(v, bit)| ((v >> bit) & 1 == 0) == (ins.op() == Op::TBZ)),
+            )
+        }
+        Op::BR
+        | Op::BLR
+        | Op::BRAA
+        | Op::BRAAZ
+        | Op::BRAB
+        | Op::BRABZ
+        | Op::BLRAA
+        | Op::BLRAAZ
+        | Op::BLRAB
+        | Op::BLRABZ => match ops.first() {
+            Some(Operand::Reg { reg, .. }) => NextPcs::one(indirect(*reg)),
+            _ => NextPcs::one(NextPc::Exception),
+        },
+        Op::RET => match ops.first() {
+            Some(Operand::Reg { reg, .. }) => NextPcs::one(indirect(*reg)),
+            _ => NextPcs::one(indirect(Reg::X30)),
+        },
+        Op::RETAA | Op::RETAB | Op::RETAASPPC | Op::RETAASPPCR | Op::RETABSPPC | Op::RETABSPPCR => {
+            NextPcs::one(indirect(Reg::X30))
+        }
+        Op::ERET
+        | Op::ERETAA
+        | Op::ERETAB
+        | Op::DRPS
+        | Op::BRK
+        | Op::HLT
+        | Op::UDF
+        | Op::DCPS1
+        | Op::DCPS2
+        | Op::DCPS3 => NextPcs::one(NextPc::Exception),
+        _ => NextPcs::one(fallthrough),
+    }
+}
+
+fn read_reg<V: RegisterView + ?Sized>(view: &V, reg: Reg) -> Option<u64> {
+    let r = reg.to_u32().unwrap();
+    let w0 = Reg::W0.to_u32().unwrap();
+    let x0 = Reg::X0.to_u32().unwrap();
+
+    match reg {
+        Reg::XZR | Reg::WZR => Some(0),
+        Reg::SP => view.reg(Reg::SP),
+        Reg::WSP => view.reg(Reg::SP).map(|v| v & 0xffff_ffff),
+        _ if (w0..=Reg::W30.to_u32().unwrap()).contains(&r) => {
+            let x = Reg::from_u32(x0 + (r - w0)).unwrap();
+
+            view.reg(x).map(|v| v & 0xffff_ffff)
+        }
+        _ => view.reg(reg),
+    }
+}
+
+fn imm_u64(imm: Imm) -> u64 {
+    match imm {
+        Imm::Signed(i) => i as u64,
+        Imm::Unsigned(u) => u,
+    }
+}
@@ -0,0 +1,145 @@
+use core::fmt;
+use core::ops::{BitAnd, BitOr, Not};
+
+/// The NZCV condition flags
+///
+/// This is used both for flag values and for sets of flags, such as the
+/// flags a condition depends on.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Nzcv(u8);
+
+impl Nzcv {
+    /// No flags
+    pub const NONE: Self = Self(0);
+    /// The negative flag
+    pub const N: Self = Self(0b1000);
+    /// The zero flag
+    pub const Z: Self = Self(0b0100);
+    /// The carry flag
+    pub const C: Self = Self(0b0010);
+    /// The overflow flag
+    pub const V: Self = Self(0b0001);
+    /// All four flags
+    pub const ALL: Self = Self(0b1111);
+
+    /// Create the flags from individual values
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Nzcv;
+    ///
+    /// let flags = Nzcv::new(false, true, true, false);
+    ///
+    /// assert!(flags.z());
+    /// assert!(flags.c());
+    /// assert_eq!(flags.bits(), 0b0110);
+    /// ```
+    pub fn new(n: bool, z: bool, c: bool, v: bool) -> Self {
+        Self((n as u8) << 3 | (z as u8) << 2 | (c as u8) << 1 | v as u8)
+    }
+
+    /// Create the flags from a 4-bit value, with N as bit 3 and V as bit 0
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits & 0xf)
+    }
+
+    /// Create the flags from a PSTATE, SPSR or `NZCV` system register value
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Nzcv;
+    ///
+    /// // N and V set
+    /// let flags = Nzcv::from_pstate(0x9000_03c5);
+    ///
+    /// assert!(flags.n());
+    /// assert!(!flags.z());
+    /// assert!(!flags.c());
+    /// assert!(flags.v());
+    /// ```
+    pub fn from_pstate(pstate: u64) -> Self {
+        Self::from_bits((pstate >> 28) as u8)
+    }
+
+    /// Returns the flags as a 4-bit value, with N as bit 3 and V as bit 0
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns the negative flag
+    pub fn n(&self) -> bool {
+        self.0 & 0b1000 != 0
+    }
+
+    /// Returns the zero flag
+    pub fn z(&self) -> bool {
+        self.0 & 0b0100 != 0
+    }
+
+    /// Returns the carry flag
+    pub fn c(&self) -> bool {
+        self.0 & 0b0010 != 0
+    }
+
+    /// Returns the overflow flag
+    pub fn v(&self) -> bool {
+        self.0 & 0b0001 != 0
+    }
+
+    /// Returns if every flag set in `other` is also set in `self`
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::Nzcv;
+    ///
+    /// assert!(Nzcv::ALL.contains(Nzcv::Z | Nzcv::C));
+    /// assert!(!Nzcv::Z.contains(Nzcv::C));
+    /// ```
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns if no flags are set
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Nzcv {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Nzcv {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Not for Nzcv {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0 & 0xf)
+    }
+}
+
+impl fmt::Display for Nzcv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set, c: char| if set { c } else { c.to_ascii_lowercase() };
+
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(self.n(), 'N'),
+            flag(self.z(), 'Z'),
+            flag(self.c(), 'C'),
+            flag(self.v(), 'V')
+        )
+    }
+}
@@ -9,6 +9,7 @@ use num_traits::ToPrimitive;
 
 /// An instruction operation
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(non_camel_case_types)]
 pub enum Op {
@@ -13,6 +13,7 @@ use crate::SysReg;
 
 /// A slice indicator
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SliceIndicator {
     Horizontal,
     Vertical,
@@ -44,6 +45,7 @@ impl fmt::Display for SliceIndicator {
 
 /// An instruction immediate
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Imm {
     Signed(i64),
     Unsigned(u64),
@@ -70,6 +72,7 @@ impl From<&bad64_sys::InstructionOperand> for Imm {
 
 /// An instruction operand
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operand {
     Imm32 {
         imm: Imm,
@@ -143,8 +146,9 @@ pub enum Operand {
         o2: u8,
     },
     Cond(Condition),
-    Name([u8; MAX_NAME as usize]),
+    Name(#[cfg_attr(feature = "serde", serde(with = "name"))] [u8; MAX_NAME as usize]),
     StrImm {
+        #[cfg_attr(feature = "serde", serde(with = "name"))]
         str: [u8; MAX_NAME as usize],
         imm: u64,
     },
@@ -419,3 +423,45 @@ impl fmt::Display for Operand {
         }
     }
 }
+
+/// (De)serializes a nul terminated name buffer as a string
+#[cfg(feature = "serde")]
+mod name {
+    use bad64_sys::MAX_NAME;
+    use serde::{Deserializer, Serializer, de};
+
+    type Name = [u8; MAX_NAME as usize];
+
+    pub fn serialize<S: Serializer>(name: &Name, s: S) -> Result<S::Ok, S::Error> {
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        let name = core::str::from_utf8(&name[..len]).map_err(serde::ser::Error::custom)?;
+
+        s.serialize_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Name, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Name;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(f, "a string of at most {} bytes", MAX_NAME - 1)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Name, E> {
+                // leave room for the terminator
+                if v.len() >= MAX_NAME as usize || v.contains('\0') {
+                    return Err(E::invalid_value(de::Unexpected::Str(v), &self));
+                }
+
+                let mut name = [0; MAX_NAME as usize];
+                name[..v.len()].copy_from_slice(v.as_bytes());
+
+                Ok(name)
+            }
+        }
+
+        d.deserialize_str(Visitor)
+    }
+}
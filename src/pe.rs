@@ -0,0 +1,846 @@
+//! PE loading
+//!
+//! A minimal reader for Windows ARM64 PE images, which finds the executable
+//! sections and functions to pass to [`crate::disasm`].
+//!
+//! Function boundaries come from the `.pdata` runtime function entries in
+//! the exception directory, and names from the export table. Each entry's
+//! unwind data, either packed into the entry or a full `.xdata` record, is
+//! decoded into [`UnwindCode`]s.
+//!
+//! ARM64X images, which hold both ARM64 and ARM64EC code, and ARM64EC
+//! images are recognised by their CHPE metadata, whose code map is returned
+//! by [`Pe::code_ranges`]. The exception directory of an ARM64EC image
+//! describes its x64 code, so no functions are found in it.
+//!
+//! # Example
+//! ```no_run
+//! use bad64::pe::Pe;
+//!
+//! let buf = std::fs::read("driver.sys").unwrap();
+//! let pe = Pe::parse(&buf).unwrap();
+//!
+//! for func in pe.functions() {
+//!     println!("{}:", func.name.unwrap_or("?"));
+//!
+//!     for ins in func.disasm().filter_map(Result::ok) {
+//!         println!("    {:x}: {}", ins.address(), ins);
+//!     }
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::ops::Range;
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::reader::{self, Truncated, string};
+use crate::{DecodeError, Instruction, Reg, disasm};
+
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+const IMAGE_FILE_MACHINE_ARM64EC: u16 = 0xa641;
+const IMAGE_FILE_MACHINE_ARM64X: u16 = 0xa64e;
+
+const PE32_PLUS: u16 = 0x20b;
+
+const DIRECTORY_EXPORT: usize = 0;
+const DIRECTORY_EXCEPTION: usize = 3;
+const DIRECTORY_LOAD_CONFIG: usize = 10;
+
+// offset of CHPEMetadataPointer in IMAGE_LOAD_CONFIG_DIRECTORY64
+const LOAD_CONFIG_CHPE_METADATA: usize = 0xc8;
+
+const IMAGE_SCN_CNT_CODE: u32 = 0x20;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+
+/// PE parsing errors
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum PeError {
+    /// The file does not have the MZ or PE signatures
+    BadMagic,
+    /// The optional header is not PE32+
+    BadOptionalHeader,
+    /// The image is not for ARM64
+    BadMachine(u16),
+    /// A header, table or unwind record extends past the end of the file
+    Truncated,
+}
+
+impl fmt::Display for PeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PeError::BadMagic => write!(f, "Bad magic"),
+            PeError::BadOptionalHeader => write!(f, "Bad optional header"),
+            PeError::BadMachine(m) => write!(f, "Bad machine: {:#x}", m),
+            PeError::Truncated => write!(f, "Truncated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PeError {}
+
+impl Truncated for PeError {
+    const TRUNCATED: Self = PeError::Truncated;
+}
+
+type Reader<'a> = reader::Reader<'a, PeError>;
+
+/// The kind of ARM64 image
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum Machine {
+    Arm64,
+    /// ARM64EC code, interoperable with x64
+    Arm64Ec,
+    /// Both ARM64 and ARM64EC code
+    Arm64X,
+}
+
+/// The architecture of a range of code in an ARM64X or ARM64EC image
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum CodeKind {
+    Arm64,
+    Arm64Ec,
+    X64,
+}
+
+/// A range of code from the CHPE code map
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CodeRange {
+    pub range: Range<u64>,
+    pub kind: CodeKind,
+}
+
+/// A section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub address: u64,
+    /// The section contents in the file, which may be shorter than in memory
+    pub data: &'a [u8],
+    /// The section characteristics (`IMAGE_SCN_*`)
+    pub characteristics: u32,
+}
+
+impl Section<'_> {
+    /// Returns if the section contains instructions
+    pub fn is_executable(&self) -> bool {
+        self.characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) != 0
+    }
+
+    /// Returns the address after the section's contents
+    fn end(&self) -> u64 {
+        self.address.saturating_add(self.data.len() as u64)
+    }
+}
+
+/// A single step of a prolog or epilog
+///
+/// Codes are listed in the order they are undone, so a prolog's are the
+/// reverse of its instructions. Writeback offsets are negative, as in the
+/// pre-indexed stores of a prolog; an epilog undoes them with post-indexed
+/// loads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnwindCode {
+    /// `sub sp, sp, #n`
+    Alloc(u32),
+    /// Allocate a multiple of the SVE vector length
+    AllocZ(u8),
+    /// `str reg, [sp, #offset]`, or with writeback
+    SaveReg {
+        reg: Reg,
+        offset: i32,
+        writeback: bool,
+    },
+    /// `stp first, second, [sp, #offset]`, or with writeback
+    SavePair {
+        first: Reg,
+        second: Reg,
+        offset: i32,
+        writeback: bool,
+    },
+    /// Save the pair after the one saved by the next code
+    SaveNext,
+    /// `mov x29, sp`
+    SetFp,
+    /// `add x29, sp, #n`
+    AddFp(u32),
+    Nop,
+    /// The end of the codes, implying a `ret` in an epilog
+    End,
+    /// The end of the codes in a chained scope
+    EndC,
+    /// `pacibsp`
+    PacSignLr,
+    /// A trap frame was pushed
+    TrapFrame,
+    /// A machine frame was pushed
+    MachineFrame,
+    /// A `CONTEXT` was pushed
+    Context,
+    /// An `ARM64EC_NT_CONTEXT` was pushed
+    EcContext,
+    /// Unwinding to a call, not a faulting instruction
+    ClearUnwoundToCall,
+    /// An unknown or invalid code, by its first byte
+    Reserved(u8),
+}
+
+/// Unwind data packed into a `.pdata` entry
+///
+/// It describes a canonical prolog, and an epilog mirroring it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PackedUnwind {
+    /// The function length in bytes
+    pub function_length: u32,
+    /// The function is a fragment, with no prolog or epilog
+    pub fragment: bool,
+    /// The number of saved `D` registers, minus one, if any are saved
+    pub reg_f: u8,
+    /// The number of saved `X` registers, from `X19`
+    pub reg_i: u8,
+    /// The argument registers are homed on the stack
+    pub homes_params: bool,
+    /// How the frame pointer and link register are saved
+    pub cr: u8,
+    /// The size of the stack frame in bytes
+    pub frame_size: u32,
+}
+
+impl PackedUnwind {
+    /// Returns if the link register is saved
+    pub fn saves_lr(&self) -> bool {
+        self.cr != 0
+    }
+
+    /// Returns if the return address is signed with `pacibsp`
+    pub fn signs_lr(&self) -> bool {
+        self.cr == 2
+    }
+
+    /// Returns if the function is chained, saving `x29` and setting it
+    /// up as a frame pointer
+    pub fn has_frame_pointer(&self) -> bool {
+        self.cr >= 2
+    }
+}
+
+/// An epilog scope in an `.xdata` record
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Epilog {
+    /// The start of the epilog, relative to the function, or `None` for a
+    /// single epilog ending the function
+    pub offset: Option<u32>,
+    /// The index of its first unwind code byte
+    pub index: usize,
+}
+
+/// A full `.xdata` unwind record
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnwindInfo<'a> {
+    /// The function length in bytes
+    pub function_length: u32,
+    pub version: u8,
+    pub epilogs: Vec<Epilog>,
+    /// The encoded unwind codes
+    pub codes: &'a [u8],
+    /// The exception handler's address, if there is one
+    pub handler: Option<u64>,
+}
+
+impl UnwindInfo<'_> {
+    /// Returns the prolog's unwind codes
+    pub fn prolog(&self) -> Vec<UnwindCode> {
+        decode_codes(self.codes)
+    }
+
+    /// Returns an epilog's unwind codes
+    pub fn epilog(&self, epilog: &Epilog) -> Vec<UnwindCode> {
+        decode_codes(self.codes.get(epilog.index..).unwrap_or(&[]))
+    }
+}
+
+/// The unwind data of a runtime function
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Unwind<'a> {
+    Packed(PackedUnwind),
+    Full(UnwindInfo<'a>),
+}
+
+impl Unwind<'_> {
+    /// Returns the function length in bytes
+    pub fn function_length(&self) -> u32 {
+        match self {
+            Unwind::Packed(p) => p.function_length,
+            Unwind::Full(f) => f.function_length,
+        }
+    }
+}
+
+/// A `.pdata` entry
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuntimeFunction<'a> {
+    pub address: u64,
+    pub unwind: Unwind<'a>,
+}
+
+/// A function
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Function<'a> {
+    pub name: Option<&'a str>,
+    pub address: u64,
+    /// The function's bytes
+    pub data: &'a [u8],
+    pub unwind: Unwind<'a>,
+}
+
+impl Function<'_> {
+    /// Disassemble the function
+    pub fn disasm(&self) -> impl Iterator<Item = Result<Instruction, DecodeError>> + '_ {
+        disasm(self.data, self.address)
+    }
+}
+
+/// A parsed ARM64 PE image
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pe<'a> {
+    machine: Machine,
+    image_base: u64,
+    entry: u64,
+    sections: Vec<Section<'a>>,
+    exports: Vec<(u64, &'a str)>,
+    runtime_functions: Vec<RuntimeFunction<'a>>,
+    code_ranges: Vec<CodeRange>,
+}
+
+impl<'a> Pe<'a> {
+    /// Parse a PE image
+    pub fn parse(data: &'a [u8]) -> Result<Self, PeError> {
+        let r = Reader::new(data, false);
+
+        if r.bytes(0, 2)? != b"MZ" {
+            return Err(PeError::BadMagic);
+        }
+
+        let pe = r.u32(0x3c)? as usize;
+        if r.bytes(pe, 4)? != b"PE\0\0" {
+            return Err(PeError::BadMagic);
+        }
+
+        let machine = r.u16(pe + 4)?;
+        let nsections = r.u16(pe + 6)? as usize;
+        let optional = pe + 24;
+        let optional_size = r.u16(pe + 20)? as usize;
+
+        if r.u16(optional)? != PE32_PLUS {
+            return Err(PeError::BadOptionalHeader);
+        }
+
+        let image_base = r.u64(optional + 24)?;
+        let entry = r.u32(optional + 16)?;
+
+        // the address of an rva, failing rather than wrapping past the top of
+        // the address space
+        let va = |rva: u64| image_base.checked_add(rva).ok_or(PeError::Truncated);
+        let ndirectories = r.u32(optional + 108)? as usize;
+
+        let directory = |n: usize| -> Result<Option<(u64, u32)>, PeError> {
+            if n >= ndirectories {
+                return Ok(None);
+            }
+
+            let rva = r.u32(optional + 112 + n * 8)?;
+            let size = r.u32(optional + 116 + n * 8)?;
+
+            Ok((rva != 0 && size != 0).then_some((rva as u64, size)))
+        };
+
+        let mut sections = Vec::new();
+        // (rva, size in memory, file offset, size in file)
+        let mut mappings = Vec::new();
+
+        for n in 0..nsections {
+            let s = optional + optional_size + n * 40;
+
+            let vsize = r.u32(s + 8)?;
+            let rva = r.u32(s + 12)?;
+            let raw_size = r.u32(s + 16)?;
+            let raw = r.u32(s + 20)?;
+
+            let vsize = if vsize == 0 { raw_size } else { vsize };
+            let len = raw_size.min(vsize) as usize;
+
+            let data = r.bytes(raw as usize, len)?;
+
+            // the whole section must be addressable, not just its start
+            let address = va(rva as u64)?;
+            va(rva as u64 + len as u64)?;
+
+            sections.push(Section {
+                name: string(r.bytes(s, 8)?, 0),
+                address,
+                data,
+                characteristics: r.u32(s + 36)?,
+            });
+
+            mappings.push((rva, vsize, raw as usize, len));
+        }
+
+        let image = Image {
+            r,
+            mappings: &mappings,
+        };
+
+        let chpe = match directory(DIRECTORY_LOAD_CONFIG)? {
+            Some((rva, _)) => {
+                let size = image.u32(rva)? as usize;
+
+                if size >= LOAD_CONFIG_CHPE_METADATA + 8 {
+                    let va = image.u64(rva + LOAD_CONFIG_CHPE_METADATA as u64)?;
+                    va.checked_sub(image_base)
+                        .filter(|rva| *rva != 0 && *rva <= u32::MAX as u64)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+
+        let machine = match (machine, chpe) {
+            (IMAGE_FILE_MACHINE_ARM64, None) => Machine::Arm64,
+            (IMAGE_FILE_MACHINE_ARM64, Some(_)) | (IMAGE_FILE_MACHINE_ARM64X, _) => Machine::Arm64X,
+            (IMAGE_FILE_MACHINE_ARM64EC, _) | (IMAGE_FILE_MACHINE_AMD64, Some(_)) => {
+                Machine::Arm64Ec
+            }
+            (m, _) => return Err(PeError::BadMachine(m)),
+        };
+
+        let mut code_ranges = Vec::new();
+        if let Some(metadata) = chpe {
+            let map = image.u32(metadata + 4)? as u64;
+            let count = image.u32(metadata + 8)? as u64;
+
+            for n in 0..count {
+                let start = image.u32(map + n * 8)?;
+                let len = image.u32(map + n * 8 + 4)?;
+
+                let kind = match start & 3 {
+                    0 => CodeKind::Arm64,
+                    1 => CodeKind::Arm64Ec,
+                    _ => CodeKind::X64,
+                };
+
+                let start = va((start & !3) as u64)?;
+                let end = start.checked_add(len as u64).ok_or(PeError::Truncated)?;
+                code_ranges.push(CodeRange {
+                    range: start..end,
+                    kind,
+                });
+            }
+        }
+
+        let mut exports = Vec::new();
+        if let Some((rva, _)) = directory(DIRECTORY_EXPORT)? {
+            let nnames = image.u32(rva + 24)? as u64;
+            let functions = image.u32(rva + 28)? as u64;
+            let names = image.u32(rva + 32)? as u64;
+            let ordinals = image.u32(rva + 36)? as u64;
+
+            for n in 0..nnames {
+                let ordinal = image.u16(ordinals + n * 2)? as u64;
+                let address = image.u32(functions + ordinal * 4)?;
+                let name = image.string(image.u32(names + n * 4)? as u64);
+
+                exports.push((va(address as u64)?, name));
+            }
+
+            exports.sort();
+        }
+
+        let mut runtime_functions = Vec::new();
+        if let Some((rva, size)) = directory(DIRECTORY_EXCEPTION)?
+            && machine != Machine::Arm64Ec
+        {
+            for n in 0..size as u64 / 8 {
+                let begin = image.u32(rva + n * 8)?;
+                let unwind = image.u32(rva + n * 8 + 4)?;
+
+                let unwind = match unwind & 3 {
+                    0 => Unwind::Full(image.unwind_info(unwind as u64, image_base)?),
+                    flag @ (1 | 2) => Unwind::Packed(packed(unwind, flag == 2)),
+                    _ => continue,
+                };
+
+                runtime_functions.push(RuntimeFunction {
+                    address: va(begin as u64)?,
+                    unwind,
+                });
+            }
+
+            runtime_functions.sort_by_key(|f| f.address);
+        }
+
+        Ok(Self {
+            machine,
+            image_base,
+            entry: va(entry as u64)?,
+            sections,
+            exports,
+            runtime_functions,
+            code_ranges,
+        })
+    }
+
+    /// Returns the kind of image
+    pub fn machine(&self) -> Machine {
+        self.machine
+    }
+
+    /// Returns the preferred load address
+    pub fn image_base(&self) -> u64 {
+        self.image_base
+    }
+
+    /// Returns the entry point's address
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    /// Returns every section
+    pub fn sections(&self) -> &[Section<'a>] {
+        &self.sections
+    }
+
+    /// Returns the sections containing instructions
+    pub fn executable_sections(&self) -> impl Iterator<Item = &Section<'a>> {
+        self.sections.iter().filter(|s| s.is_executable())
+    }
+
+    /// Returns a section by name
+    pub fn section_by_name(&self, name: &str) -> Option<&Section<'a>> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Returns the address and name of each export, sorted by address
+    pub fn exports(&self) -> &[(u64, &'a str)] {
+        &self.exports
+    }
+
+    /// Returns the name of the export at an address
+    pub fn symbol_at(&self, address: u64) -> Option<&'a str> {
+        let n = self.exports.partition_point(|(a, _)| *a < address);
+
+        match self.exports.get(n) {
+            Some((a, name)) if *a == address => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the `.pdata` entries, sorted by address
+    pub fn runtime_functions(&self) -> &[RuntimeFunction<'a>] {
+        &self.runtime_functions
+    }
+
+    /// Returns the CHPE code map of an ARM64X or ARM64EC image
+    pub fn code_ranges(&self) -> &[CodeRange] {
+        &self.code_ranges
+    }
+
+    /// Disassemble a section
+    pub fn disasm<'s>(
+        &'s self,
+        section: &'s Section<'a>,
+    ) -> impl Iterator<Item = Result<Instruction, DecodeError>> + 's {
+        disasm(section.data, section.address)
+    }
+
+    /// Returns the functions described by `.pdata`, sorted by address
+    ///
+    /// A function split into fragments has an entry for each fragment.
+    pub fn functions(&self) -> Vec<Function<'a>> {
+        self.runtime_functions
+            .iter()
+            .filter_map(|f| {
+                let section = self
+                    .executable_sections()
+                    .find(|s| (s.address..s.end()).contains(&f.address))?;
+
+                let start = (f.address - section.address) as usize;
+                let end = (start + f.unwind.function_length() as usize).min(section.data.len());
+
+                Some(Function {
+                    name: self.symbol_at(f.address),
+                    address: f.address,
+                    data: &section.data[start..end],
+                    unwind: f.unwind.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn packed(word: u32, fragment: bool) -> PackedUnwind {
+    PackedUnwind {
+        function_length: ((word >> 2) & 0x7ff) * 4,
+        fragment,
+        reg_f: ((word >> 13) & 7) as u8,
+        reg_i: ((word >> 16) & 0xf) as u8,
+        homes_params: (word >> 20) & 1 != 0,
+        cr: ((word >> 21) & 3) as u8,
+        frame_size: ((word >> 23) & 0x1ff) * 16,
+    }
+}
+
+fn x(n: u32) -> Option<Reg> {
+    (n <= 30).then(|| Reg::from_u32(Reg::X0.to_u32().unwrap() + n).unwrap())
+}
+
+fn d(n: u32) -> Option<Reg> {
+    (n <= 31).then(|| Reg::from_u32(Reg::D0.to_u32().unwrap() + n).unwrap())
+}
+
+fn q(n: u32) -> Option<Reg> {
+    (n <= 31).then(|| Reg::from_u32(Reg::Q0.to_u32().unwrap() + n).unwrap())
+}
+
+fn save(reg: Option<Reg>, offset: i32, writeback: bool) -> Option<UnwindCode> {
+    Some(UnwindCode::SaveReg {
+        reg: reg?,
+        offset,
+        writeback,
+    })
+}
+
+fn pair(
+    first: Option<Reg>,
+    second: Option<Reg>,
+    offset: i32,
+    writeback: bool,
+) -> Option<UnwindCode> {
+    Some(UnwindCode::SavePair {
+        first: first?,
+        second: second?,
+        offset,
+        writeback,
+    })
+}
+
+/// Decode unwind codes up to and including the first end code
+fn decode_codes(bytes: &[u8]) -> Vec<UnwindCode> {
+    let mut codes = Vec::new();
+    let mut n = 0;
+
+    while let Some(&b) = bytes.get(n) {
+        let len = match b {
+            0xc0..=0xdf | 0xe2 => 2,
+            0xe7 => 3,
+            0xe0 => 4,
+            _ => 1,
+        };
+
+        let Some(code) = bytes.get(n..n + len) else {
+            break;
+        };
+        n += len;
+
+        let b1 = code.get(1).copied().unwrap_or(0) as u32;
+        let b = b as u32;
+
+        // the register and offset fields of the two byte saves
+        let x4 = ((b & 3) << 2) | (b1 >> 6);
+        let x3 = ((b & 1) << 2) | (b1 >> 6);
+        let z6 = (b1 & 0x3f) as i32;
+        let xx = ((b & 1) << 3) | (b1 >> 5);
+        let z5 = (b1 & 0x1f) as i32;
+
+        let decoded = match b {
+            0x00..=0x1f => Some(UnwindCode::Alloc(b * 16)),
+            0x20..=0x3f => pair(x(19), x(20), -((b & 0x1f) as i32) * 8, true),
+            0x40..=0x7f => pair(x(29), x(30), (b & 0x3f) as i32 * 8, false),
+            0x80..=0xbf => pair(x(29), x(30), -((b & 0x3f) as i32 + 1) * 8, true),
+            0xc0..=0xc7 => Some(UnwindCode::Alloc((((b & 7) << 8) | b1) * 16)),
+            0xc8..=0xcb => pair(x(19 + x4), x(20 + x4), z6 * 8, false),
+            0xcc..=0xcf => pair(x(19 + x4), x(20 + x4), -(z6 + 1) * 8, true),
+            0xd0..=0xd3 => save(x(19 + x4), z6 * 8, false),
+            0xd4..=0xd5 => save(x(19 + xx), -(z5 + 1) * 8, true),
+            0xd6..=0xd7 => pair(x(19 + 2 * x3), x(30), z6 * 8, false),
+            0xd8..=0xd9 => pair(d(8 + x3), d(9 + x3), z6 * 8, false),
+            0xda..=0xdb => pair(d(8 + x3), d(9 + x3), -(z6 + 1) * 8, true),
+            0xdc..=0xdd => save(d(8 + x3), z6 * 8, false),
+            0xde => save(d(8 + (b1 >> 5)), -(z5 + 1) * 8, true),
+            0xdf => Some(UnwindCode::AllocZ(b1 as u8)),
+            0xe0 => {
+                let size = (b1 << 16) | ((code[2] as u32) << 8) | code[3] as u32;
+                Some(UnwindCode::Alloc(size * 16))
+            }
+            0xe1 => Some(UnwindCode::SetFp),
+            0xe2 => Some(UnwindCode::AddFp(b1 * 8)),
+            0xe3 => Some(UnwindCode::Nop),
+            0xe4 => Some(UnwindCode::End),
+            0xe5 => Some(UnwindCode::EndC),
+            0xe6 => Some(UnwindCode::SaveNext),
+            0xe7 => save_any(b1, code[2] as u32),
+            0xe8 => Some(UnwindCode::TrapFrame),
+            0xe9 => Some(UnwindCode::MachineFrame),
+            0xea => Some(UnwindCode::Context),
+            0xeb => Some(UnwindCode::EcContext),
+            0xec => Some(UnwindCode::ClearUnwoundToCall),
+            0xfc => Some(UnwindCode::PacSignLr),
+            _ => None,
+        };
+
+        let code = decoded.unwrap_or(UnwindCode::Reserved(b as u8));
+        codes.push(code);
+
+        if matches!(code, UnwindCode::End | UnwindCode::EndC) {
+            break;
+        }
+    }
+
+    codes
+}
+
+/// Decode `save_any_reg`
+fn save_any(b1: u32, b2: u32) -> Option<UnwindCode> {
+    let paired = b1 & 0x40 != 0;
+    let writeback = b1 & 0x20 != 0;
+    let r = b1 & 0x1f;
+    let mode = b2 >> 6;
+
+    if b1 & 0x80 != 0 {
+        return None;
+    }
+
+    let reg: fn(u32) -> Option<Reg> = match mode {
+        0 => x,
+        1 => d,
+        2 => q,
+        _ => return None,
+    };
+
+    let scale = if writeback || paired || mode == 2 {
+        16
+    } else {
+        8
+    };
+    let offset = (b2 & 0x3f) as i32 * scale;
+    let offset = if writeback { -offset } else { offset };
+
+    match paired {
+        true => pair(reg(r), reg(r + 1), offset, writeback),
+        false => save(reg(r), offset, writeback),
+    }
+}
+
+/// Reads by relative virtual address
+struct Image<'a, 'm> {
+    r: Reader<'a>,
+    mappings: &'m [(u32, u32, usize, usize)],
+}
+
+impl<'a> Image<'a, '_> {
+    /// Returns the file offset of an address, and the bytes left in its
+    /// section
+    fn offset(&self, rva: u64) -> Result<(usize, usize), PeError> {
+        self.mappings
+            .iter()
+            .find(|(start, size, _, _)| {
+                rva.checked_sub(*start as u64)
+                    .is_some_and(|delta| delta < *size as u64)
+            })
+            .map(|(start, _, raw, len)| {
+                let delta = (rva - *start as u64) as usize;
+                (raw + delta, len.saturating_sub(delta))
+            })
+            .ok_or(PeError::Truncated)
+    }
+
+    fn bytes(&self, rva: u64, len: usize) -> Result<&'a [u8], PeError> {
+        let (off, left) = self.offset(rva)?;
+
+        if len > left {
+            return Err(PeError::Truncated);
+        }
+
+        self.r.bytes(off, len)
+    }
+
+    fn u16(&self, rva: u64) -> Result<u16, PeError> {
+        Ok(u16::from_le_bytes(self.bytes(rva, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, rva: u64) -> Result<u32, PeError> {
+        Ok(u32::from_le_bytes(self.bytes(rva, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, rva: u64) -> Result<u64, PeError> {
+        Ok(u64::from_le_bytes(self.bytes(rva, 8)?.try_into().unwrap()))
+    }
+
+    /// Returns the nul terminated string at an address, or "" if it is
+    /// invalid
+    fn string(&self, rva: u64) -> &'a str {
+        match self.offset(rva) {
+            Ok((off, left)) => string(self.r.bytes(off, left).unwrap_or(&[]), 0),
+            Err(_) => "",
+        }
+    }
+
+    fn unwind_info(&self, rva: u64, image_base: u64) -> Result<UnwindInfo<'a>, PeError> {
+        let header = self.u32(rva)?;
+        let mut off = rva + 4;
+
+        let mut epilog_count = header >> 22 & 0x1f;
+        let mut code_words = header >> 27;
+
+        if epilog_count == 0 && code_words == 0 {
+            let extension = self.u32(off)?;
+            epilog_count = extension & 0xffff;
+            code_words = extension >> 16 & 0xff;
+            off += 4;
+        }
+
+        let mut epilogs = Vec::new();
+
+        // with E set the count is the index of the only epilog's codes
+        if header & (1 << 21) != 0 {
+            epilogs.push(Epilog {
+                offset: None,
+                index: epilog_count as usize,
+            });
+        } else {
+            for _ in 0..epilog_count {
+                let scope = self.u32(off)?;
+                off += 4;
+
+                epilogs.push(Epilog {
+                    offset: Some((scope & 0x3ffff) * 4),
+                    index: (scope >> 22) as usize,
+                });
+            }
+        }
+
+        let codes = self.bytes(off, code_words as usize * 4)?;
+        off += code_words as u64 * 4;
+
+        let handler = match header & (1 << 20) != 0 {
+            true => Some(
+                image_base
+                    .checked_add(self.u32(off)? as u64)
+                    .ok_or(PeError::Truncated)?,
+            ),
+            false => None,
+        };
+
+        Ok(UnwindInfo {
+            function_length: (header & 0x3ffff) * 4,
+            version: (header >> 18 & 3) as u8,
+            epilogs,
+            codes,
+            handler,
+        })
+    }
+}
@@ -0,0 +1,849 @@
+//! Instruction pattern queries
+//!
+//! A [`Pattern`] is compiled from text written like the disassembly it
+//! matches, one instruction per line or separated by `;`:
+//!
+//! ```text
+//! stp fp, lr, [sp, #-?]!
+//! ldr $r, [$r, #?]
+//! @call ?; cbz|cbnz x0, ?
+//! ```
+//!
+//! Each instruction pattern is an op, then its operands:
+//!
+//! - an op is a mnemonic such as `ldr` or `b.eq`, `*` for any op, or a group:
+//!   `@load`, `@store`, `@branch`, `@call` or `@ret`. Alternatives are
+//!   separated by `|`
+//! - `?` matches any one operand, and a trailing `...` any remaining ones
+//! - a register name matches that register, as does `$name`, which captures
+//!   the register on its first use and must be the same register after. The
+//!   comparison is exact, so `w0` and `x0` are different registers
+//! - `#5`, `#-0x10`, `#?` (any value) and `#-?` (any negative value) match
+//!   immediates and branch targets
+//! - `[base]`, `[base, #imm]`, `[base, #imm]!`, `[base], #imm`,
+//!   `[base], index` and `[base, index, ...]` match the memory operand of
+//!   the same form. Any extend or shift of the index is ignored
+//!
+//! Mnemonics and register names are not case sensitive. The instructions of
+//! a multi-instruction pattern must be at consecutive addresses, unless
+//! separated by a gap: `... 5` on its own skips up to five instructions, and
+//! `...` any number. A register captured before a gap must not be written by
+//! any instruction in it, including through an alias such as `w8` for `x8`.
+//! Writes are found by lifting (see [`crate::dataflow::Access`]), so calls
+//! redefine the caller-saved registers.
+//!
+//! ```text
+//! adrp $x, ?
+//! ... 5
+//! ldr $y, [$x, #?]
+//! ```
+//!
+//! # Example
+//! ```
+//! use bad64::{disasm, Reg};
+//! use bad64::query::Pattern;
+//!
+//! // stp fp, lr, [sp, #-0x20]!
+//! // mov fp, sp
+//! // ldr x8, [x8, #0x10]
+//! let code = b"\xfd\x7b\xbe\xa9\xfd\x03\x00\x91\x08\x09\x40\xf9";
+//! let ins: Vec<_> = disasm(code, 0x1000).filter_map(Result::ok).collect();
+//!
+//! let prologue = Pattern::compile("stp fp, lr, [sp, #-?]!; mov fp, sp").unwrap();
+//! let found: Vec<_> = prologue.find(&ins).collect();
+//! assert_eq!(found.len(), 1);
+//! assert_eq!(found[0].address, 0x1000);
+//!
+//! let deref = Pattern::compile("ldr $r, [$r, #?]").unwrap();
+//! let found = deref.find(&ins).next().unwrap();
+//! assert_eq!(found.address, 0x1008);
+//! assert_eq!(found.capture("r"), Some(Reg::X8));
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use num_traits::FromPrimitive;
+
+use bad64_sys::{Operation_ARM64_ZIPQ2, Register_REG_END};
+
+use crate::dataflow::Access;
+use crate::{Imm, Instruction, Op, Operand, Reg, disasm};
+
+/// An error compiling a [`Pattern`], with the byte offset it was found at
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum QueryError {
+    /// The pattern, or one of its instructions, is empty
+    Empty(usize),
+    /// A character or the end of the pattern was not expected
+    Unexpected(usize),
+    /// No op has this mnemonic
+    UnknownOp(usize),
+    /// No op group has this name
+    UnknownGroup(usize),
+    /// No register has this name
+    UnknownReg(usize),
+    /// A number could not be parsed
+    BadNumber(usize),
+}
+
+impl QueryError {
+    /// Returns the byte offset into the pattern of the error
+    pub fn offset(&self) -> usize {
+        match self {
+            QueryError::Empty(n)
+            | QueryError::Unexpected(n)
+            | QueryError::UnknownOp(n)
+            | QueryError::UnknownGroup(n)
+            | QueryError::UnknownReg(n)
+            | QueryError::BadNumber(n) => *n,
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::Empty(n) => write!(f, "Empty pattern at {}", n),
+            QueryError::Unexpected(n) => write!(f, "Unexpected input at {}", n),
+            QueryError::UnknownOp(n) => write!(f, "Unknown op at {}", n),
+            QueryError::UnknownGroup(n) => write!(f, "Unknown op group at {}", n),
+            QueryError::UnknownReg(n) => write!(f, "Unknown register at {}", n),
+            QueryError::BadNumber(n) => write!(f, "Bad number at {}", n),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for QueryError {}
+
+/// A compiled instruction pattern
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pattern {
+    insns: Vec<Insn>,
+    names: Vec<String>,
+}
+
+/// A match of a [`Pattern`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Match<'p> {
+    /// Address of the first instruction matched
+    pub address: u64,
+    /// Number of instructions matched, including any skipped by gaps
+    pub len: usize,
+    /// Address of the instruction matched by each instruction pattern
+    pub addresses: Vec<u64>,
+    /// The registers captured, by name
+    pub captures: Vec<(&'p str, Reg)>,
+}
+
+impl Match<'_> {
+    /// Returns the register captured as `$name`
+    pub fn capture(&self, name: &str) -> Option<Reg> {
+        self.captures
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, reg)| *reg)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Insn {
+    // the most instructions which may be skipped before this one
+    gap: usize,
+    ops: Vec<OpPat>,
+    args: Vec<Arg>,
+    // whether `...` ends the operands
+    rest: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OpPat {
+    Any,
+    Op(Op),
+    Group(Group),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Group {
+    Load,
+    Store,
+    Branch,
+    Call,
+    Ret,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Arg {
+    Any,
+    Reg(RegPat),
+    Imm(ImmPat),
+    Mem(RegPat, Mem),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RegPat {
+    Any,
+    Reg(Reg),
+    Capture(usize),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ImmPat {
+    Any,
+    Negative,
+    Value(i64),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mem {
+    Base,
+    Offset(ImmPat),
+    PreIdx(ImmPat),
+    PostIdxImm(ImmPat),
+    PostIdxReg(RegPat),
+    Ext(RegPat),
+}
+
+impl Pattern {
+    /// Compile a pattern
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::query::{Pattern, QueryError};
+    ///
+    /// assert!(Pattern::compile("ldr|ldur $r, [sp, #?]").is_ok());
+    /// assert_eq!(Pattern::compile("lodr x0, [x1]"), Err(QueryError::UnknownOp(0)));
+    /// assert_eq!(Pattern::compile("ldr x0, [x1, #8"), Err(QueryError::Unexpected(15)));
+    /// ```
+    pub fn compile(src: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            names: Vec::new(),
+        };
+
+        let mut insns: Vec<Insn> = Vec::new();
+        let mut gap = None;
+
+        loop {
+            parser.skip_space();
+
+            match parser.peek() {
+                None => break,
+                Some(';') | Some('\n') | Some('\r') => parser.pos += 1,
+                Some('.') if parser.src[parser.pos..].starts_with("...") => {
+                    // a gap needs an instruction on either side
+                    if insns.is_empty() {
+                        return Err(QueryError::Unexpected(parser.pos));
+                    }
+
+                    let skip = parser.gap()?;
+                    gap = Some(gap.map_or(skip, |g: usize| g.saturating_add(skip)));
+                }
+                Some(_) => {
+                    let mut insn = parser.insn()?;
+                    insn.gap = gap.take().unwrap_or(0);
+                    insns.push(insn);
+                }
+            }
+        }
+
+        if insns.is_empty() {
+            return Err(QueryError::Empty(0));
+        }
+
+        if gap.is_some() {
+            return Err(QueryError::Unexpected(src.len()));
+        }
+
+        Ok(Self {
+            insns,
+            names: parser.names,
+        })
+    }
+
+    /// Match the pattern against the start of a list of instructions
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Reg};
+    /// use bad64::query::Pattern;
+    ///
+    /// let ins = [
+    ///     decode(0xb0000008, 0x1000).unwrap(), // adrp x8, 0x2000
+    ///     decode(0xd2800020, 0x1004).unwrap(), // mov x0, #1
+    ///     decode(0xf9400909, 0x1008).unwrap(), // ldr x9, [x8, #0x10]
+    /// ];
+    ///
+    /// let load = Pattern::compile("adrp $x, ?; ... 5; ldr $y, [$x, #?]").unwrap();
+    /// let found = load.matches(&ins).unwrap();
+    ///
+    /// assert_eq!(found.addresses, [0x1000, 0x1008]);
+    /// assert_eq!(found.len, 3);
+    /// assert_eq!(found.capture("y"), Some(Reg::X9));
+    ///
+    /// // unless the adrp is followed by the load, without a gap
+    /// let load = Pattern::compile("adrp $x, ?; ldr $y, [$x, #?]").unwrap();
+    /// assert!(load.matches(&ins).is_none());
+    /// ```
+    pub fn matches<'p>(&'p self, ins: &[Instruction]) -> Option<Match<'p>> {
+        let mut regs = vec![None; self.names.len()];
+
+        self.matches_with(ins, &mut regs)
+    }
+
+    /// Returns every match in a list of instructions, such as the output of
+    /// [`crate::disasm`], in order of address. Matches may overlap.
+    pub fn find<'p, 'a>(&'p self, ins: &'a [Instruction]) -> impl Iterator<Item = Match<'p>> + 'a
+    where
+        'p: 'a,
+    {
+        let mut regs = vec![None; self.names.len()];
+
+        (0..ins.len()).filter_map(move |n| self.matches_with(&ins[n..], &mut regs))
+    }
+
+    /// Disassemble a region and return every match in it
+    pub fn scan(&self, code: &[u8], address: u64) -> Vec<Match<'_>> {
+        let ins: Vec<_> = disasm(code, address).filter_map(Result::ok).collect();
+
+        self.find(&ins).collect()
+    }
+
+    fn matches_with<'p>(
+        &'p self,
+        ins: &[Instruction],
+        regs: &mut [Option<Reg>],
+    ) -> Option<Match<'p>> {
+        if ins.len() < self.insns.len() {
+            return None;
+        }
+
+        regs.fill(None);
+
+        let mut at = Vec::with_capacity(self.insns.len());
+        if !self.matches_from(ins, 0, 0, regs, &mut at) {
+            return None;
+        }
+
+        let captures = self
+            .names
+            .iter()
+            .zip(regs.iter())
+            .filter_map(|(name, reg)| Some((name.as_str(), (*reg)?)))
+            .collect();
+
+        Some(Match {
+            address: ins[0].address(),
+            len: at[at.len() - 1] + 1,
+            addresses: at.iter().map(|n| ins[*n].address()).collect(),
+            captures,
+        })
+    }
+
+    /// Matches the instruction patterns from `k` on against the instructions
+    /// from `n` on, pushing the index of each instruction matched to `at`
+    fn matches_from(
+        &self,
+        ins: &[Instruction],
+        k: usize,
+        n: usize,
+        regs: &mut [Option<Reg>],
+        at: &mut Vec<usize>,
+    ) -> bool {
+        let Some(pat) = self.insns.get(k) else {
+            return true;
+        };
+
+        // the first instruction pattern is anchored at the start
+        let gap = if k == 0 { 0 } else { pat.gap };
+        let saved = regs.to_vec();
+
+        for m in n..ins.len() {
+            if m - n > gap {
+                break;
+            }
+
+            if m > 0 && ins[m].address() != ins[m - 1].address().wrapping_add(4) {
+                break;
+            }
+
+            // the previous instruction was skipped, so must not have
+            // written any register captured so far
+            if m > n && regs.iter().any(Option::is_some) {
+                let defs = Access::of(&ins[m - 1]).defs;
+
+                if regs.iter().flatten().any(|r| defs.contains(*r)) {
+                    break;
+                }
+            }
+
+            if pat.matches(&ins[m], regs) {
+                at.push(m);
+
+                if self.matches_from(ins, k + 1, m + 1, regs, at) {
+                    return true;
+                }
+
+                at.pop();
+            }
+
+            regs.copy_from_slice(&saved);
+        }
+
+        false
+    }
+}
+
+impl Insn {
+    fn matches(&self, ins: &Instruction, regs: &mut [Option<Reg>]) -> bool {
+        let op = ins.op();
+        if !self.ops.iter().any(|p| p.matches(op)) {
+            return false;
+        }
+
+        let operands = ins.operands();
+        let count_ok = if self.rest {
+            operands.len() >= self.args.len()
+        } else {
+            operands.len() == self.args.len()
+        };
+
+        count_ok
+            && self
+                .args
+                .iter()
+                .zip(operands)
+                .all(|(arg, operand)| arg.matches(operand, regs))
+    }
+}
+
+impl OpPat {
+    fn matches(&self, op: Op) -> bool {
+        match self {
+            OpPat::Any => true,
+            OpPat::Op(o) => *o == op,
+            OpPat::Group(g) => g.contains(op),
+        }
+    }
+}
+
+impl Group {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "load" => Some(Group::Load),
+            "store" => Some(Group::Store),
+            "branch" => Some(Group::Branch),
+            "call" => Some(Group::Call),
+            "ret" => Some(Group::Ret),
+            _ => None,
+        }
+    }
+
+    fn contains(&self, op: Op) -> bool {
+        let mnem = op.mnem();
+
+        match self {
+            Group::Load => mnem.starts_with("ld"),
+            Group::Store => mnem.starts_with("st"),
+            Group::Call => matches!(
+                op,
+                Op::BL | Op::BLR | Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ
+            ),
+            Group::Ret => mnem.starts_with("ret"),
+            Group::Branch => {
+                Group::Call.contains(op)
+                    || Group::Ret.contains(op)
+                    || mnem.starts_with("b.")
+                    || mnem.starts_with("bc.")
+                    || matches!(
+                        op,
+                        Op::B
+                            | Op::BR
+                            | Op::BRAA
+                            | Op::BRAAZ
+                            | Op::BRAB
+                            | Op::BRABZ
+                            | Op::CBZ
+                            | Op::CBNZ
+                            | Op::TBZ
+                            | Op::TBNZ
+                            | Op::ERET
+                            | Op::ERETAA
+                            | Op::ERETAB
+                    )
+            }
+        }
+    }
+}
+
+impl Arg {
+    fn matches(&self, operand: &Operand, regs: &mut [Option<Reg>]) -> bool {
+        match (self, operand) {
+            (Arg::Any, _) => true,
+            (
+                Arg::Reg(pat),
+                Operand::Reg { reg, .. }
+                | Operand::ShiftReg { reg, .. }
+                | Operand::QualReg { reg, .. },
+            ) => pat.matches(*reg, regs),
+            (
+                Arg::Imm(pat),
+                Operand::Imm32 { imm, .. } | Operand::Imm64 { imm, .. } | Operand::Label(imm),
+            ) => pat.matches(imm),
+            (Arg::Mem(base, mem), _) => mem.matches(*base, operand, regs),
+            _ => false,
+        }
+    }
+}
+
+impl RegPat {
+    fn matches(&self, reg: Reg, regs: &mut [Option<Reg>]) -> bool {
+        match self {
+            RegPat::Any => true,
+            RegPat::Reg(r) => *r == reg,
+            RegPat::Capture(n) => *regs[*n].get_or_insert(reg) == reg,
+        }
+    }
+}
+
+impl ImmPat {
+    fn matches(&self, imm: &Imm) -> bool {
+        let value = match imm {
+            Imm::Signed(v) => *v,
+            Imm::Unsigned(v) => *v as i64,
+        };
+
+        match self {
+            ImmPat::Any => true,
+            ImmPat::Negative => value < 0,
+            ImmPat::Value(v) => *v == value,
+        }
+    }
+}
+
+impl Mem {
+    fn matches(&self, base: RegPat, operand: &Operand, regs: &mut [Option<Reg>]) -> bool {
+        const ZERO: ImmPat = ImmPat::Value(0);
+
+        match (self, operand) {
+            (Mem::Base, Operand::MemReg(reg)) => base.matches(*reg, regs),
+            (Mem::Base, Operand::MemOffset { reg, offset, .. }) => {
+                ZERO.matches(offset) && base.matches(*reg, regs)
+            }
+            (Mem::Offset(imm), Operand::MemReg(reg)) => {
+                imm.matches(&Imm::Signed(0)) && base.matches(*reg, regs)
+            }
+            (Mem::Offset(imm), Operand::MemOffset { reg, offset, .. }) => {
+                imm.matches(offset) && base.matches(*reg, regs)
+            }
+            (Mem::PreIdx(pat), Operand::MemPreIdx { reg, imm })
+            | (Mem::PostIdxImm(pat), Operand::MemPostIdxImm { reg, imm }) => {
+                pat.matches(imm) && base.matches(*reg, regs)
+            }
+            (Mem::PostIdxReg(index), Operand::MemPostIdxReg(r))
+            | (Mem::Ext(index), Operand::MemExt { regs: r, .. }) => {
+                base.matches(r[0], regs) && index.matches(r[1], regs)
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+    // capture names, in order of first use
+    names: Vec<String>,
+}
+
+impl<'s> Parser<'s> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips spaces, then consumes `c` if it is next
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(QueryError::Unexpected(self.pos))
+        }
+    }
+
+    /// Skips spaces, then returns the offset and text of a word
+    fn word(&mut self) -> (usize, &'s str) {
+        self.skip_space();
+
+        let start = self.pos;
+        let len = self.src[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+            .unwrap_or(self.src.len() - start);
+        self.pos += len;
+
+        (start, &self.src[start..self.pos])
+    }
+
+    /// Returns if the current instruction pattern has ended
+    fn at_end(&mut self) -> bool {
+        self.skip_space();
+
+        matches!(self.peek(), None | Some(';') | Some('\n') | Some('\r'))
+    }
+
+    fn insn(&mut self) -> Result<Insn, QueryError> {
+        let mut ops = Vec::new();
+
+        loop {
+            ops.extend(self.op()?);
+
+            if !self.eat('|') {
+                break;
+            }
+        }
+
+        let mut args = Vec::new();
+        let mut rest = false;
+
+        if !self.at_end() {
+            loop {
+                self.skip_space();
+
+                if self.src[self.pos..].starts_with("...") {
+                    self.pos += 3;
+                    rest = true;
+                    break;
+                }
+
+                args.push(self.arg()?);
+
+                if !self.eat(',') {
+                    break;
+                }
+            }
+        }
+
+        if self.at_end() {
+            Ok(Insn {
+                gap: 0,
+                ops,
+                args,
+                rest,
+            })
+        } else {
+            Err(QueryError::Unexpected(self.pos))
+        }
+    }
+
+    /// Parses a gap, returning the most instructions it may skip
+    fn gap(&mut self) -> Result<usize, QueryError> {
+        self.pos += 3;
+
+        if self.at_end() {
+            return Ok(usize::MAX);
+        }
+
+        let (at, digits) = self.word();
+        let skip = digits.parse().map_err(|_| QueryError::BadNumber(at))?;
+
+        if self.at_end() {
+            Ok(skip)
+        } else {
+            Err(QueryError::Unexpected(self.pos))
+        }
+    }
+
+    fn op(&mut self) -> Result<Vec<OpPat>, QueryError> {
+        if self.eat('*') {
+            return Ok(vec![OpPat::Any]);
+        }
+
+        if self.eat('@') {
+            let (at, name) = self.word();
+
+            return match Group::from_name(&name.to_ascii_lowercase()) {
+                Some(g) => Ok(vec![OpPat::Group(g)]),
+                None => Err(QueryError::UnknownGroup(at)),
+            };
+        }
+
+        let (at, name) = self.word();
+        if name.is_empty() {
+            return Err(QueryError::Empty(at));
+        }
+
+        let name = name.to_ascii_lowercase();
+        let ops: Vec<_> = (0..=Operation_ARM64_ZIPQ2 as u32)
+            .filter_map(Op::from_u32)
+            .filter(|op| op.mnem() == name)
+            .map(OpPat::Op)
+            .collect();
+
+        if ops.is_empty() {
+            Err(QueryError::UnknownOp(at))
+        } else {
+            Ok(ops)
+        }
+    }
+
+    fn arg(&mut self) -> Result<Arg, QueryError> {
+        self.skip_space();
+
+        match self.peek() {
+            Some('?') => {
+                self.pos += 1;
+                Ok(Arg::Any)
+            }
+            Some('#') => {
+                self.pos += 1;
+                Ok(Arg::Imm(self.imm()?))
+            }
+            Some('-') => Ok(Arg::Imm(self.imm()?)),
+            Some(c) if c.is_ascii_digit() => Ok(Arg::Imm(self.imm()?)),
+            Some('[') => {
+                self.pos += 1;
+                self.mem()
+            }
+            _ => Ok(Arg::Reg(self.reg()?)),
+        }
+    }
+
+    fn imm(&mut self) -> Result<ImmPat, QueryError> {
+        self.skip_space();
+
+        let negative = self.eat('-');
+        if self.eat('?') {
+            return Ok(if negative {
+                ImmPat::Negative
+            } else {
+                ImmPat::Any
+            });
+        }
+
+        let (at, digits) = self.word();
+        let value = match digits.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => digits.parse(),
+        }
+        .map_err(|_| QueryError::BadNumber(at))?;
+
+        Ok(ImmPat::Value(if negative {
+            (value as i64).wrapping_neg()
+        } else {
+            value as i64
+        }))
+    }
+
+    fn reg(&mut self) -> Result<RegPat, QueryError> {
+        if self.eat('?') {
+            return Ok(RegPat::Any);
+        }
+
+        if self.eat('$') {
+            let (at, name) = self.word();
+            if name.is_empty() {
+                return Err(QueryError::Unexpected(at));
+            }
+
+            let n = match self.names.iter().position(|n| n == name) {
+                Some(n) => n,
+                None => {
+                    self.names.push(name.to_string());
+                    self.names.len() - 1
+                }
+            };
+
+            return Ok(RegPat::Capture(n));
+        }
+
+        let (at, name) = self.word();
+        let name = name.to_ascii_lowercase();
+
+        let reg = match name.as_str() {
+            "" => return Err(QueryError::Unexpected(at)),
+            "x29" | "fp" => Some(Reg::X29),
+            "x30" | "lr" => Some(Reg::X30),
+            _ => (0..Register_REG_END as u32)
+                .filter_map(Reg::from_u32)
+                .find(|r| r.name() == name),
+        };
+
+        reg.map(RegPat::Reg).ok_or(QueryError::UnknownReg(at))
+    }
+
+    fn mem(&mut self) -> Result<Arg, QueryError> {
+        let base = self.reg()?;
+
+        let mem = match self.eat(',') {
+            false => Mem::Base,
+            true => {
+                self.skip_space();
+
+                match self.peek() {
+                    Some('#') => {
+                        self.pos += 1;
+                        Mem::Offset(self.imm()?)
+                    }
+                    _ => {
+                        let index = self.reg()?;
+
+                        // skip any extend or shift
+                        if self.eat(',') {
+                            let end = self.src[self.pos..]
+                                .find([']', ';', '\n'])
+                                .map_or(self.src.len(), |n| self.pos + n);
+                            self.pos = end;
+                        }
+
+                        Mem::Ext(index)
+                    }
+                }
+            }
+        };
+
+        self.expect(']')?;
+
+        let mem = match mem {
+            Mem::Offset(imm) if self.eat('!') => Mem::PreIdx(imm),
+            Mem::Base if self.eat(',') => {
+                self.skip_space();
+
+                match self.peek() {
+                    Some('#') => {
+                        self.pos += 1;
+                        Mem::PostIdxImm(self.imm()?)
+                    }
+                    Some('?') => {
+                        self.pos += 1;
+                        Mem::PostIdxImm(ImmPat::Any)
+                    }
+                    _ => Mem::PostIdxReg(self.reg()?),
+                }
+            }
+            mem => mem,
+        };
+
+        Ok(Arg::Mem(base, mem))
+    }
+}
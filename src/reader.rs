@@ -0,0 +1,73 @@
+//! Bounds checked reads of file headers and tables, shared by the loaders
+
+use core::convert::TryInto;
+use core::marker::PhantomData;
+
+/// A loader's error type, with a variant for reads past the end of the data
+pub(crate) trait Truncated {
+    const TRUNCATED: Self;
+}
+
+/// Reads integers in either byte order, failing with the loader's
+/// [`Truncated`] error past the end of the data
+#[derive(Clone, Copy)]
+pub(crate) struct Reader<'a, E> {
+    pub data: &'a [u8],
+    big: bool,
+    error: PhantomData<E>,
+}
+
+impl<'a, E: Truncated> Reader<'a, E> {
+    pub fn new(data: &'a [u8], big: bool) -> Self {
+        Self {
+            data,
+            big,
+            error: PhantomData,
+        }
+    }
+
+    pub fn bytes(&self, off: usize, len: usize) -> Result<&'a [u8], E> {
+        off.checked_add(len)
+            .and_then(|end| self.data.get(off..end))
+            .ok_or(E::TRUNCATED)
+    }
+
+    fn array<const N: usize>(&self, off: usize) -> Result<[u8; N], E> {
+        Ok(self.bytes(off, N)?.try_into().unwrap())
+    }
+
+    pub fn u16(&self, off: usize) -> Result<u16, E> {
+        let b = self.array(off)?;
+
+        Ok(match self.big {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        })
+    }
+
+    pub fn u32(&self, off: usize) -> Result<u32, E> {
+        let b = self.array(off)?;
+
+        Ok(match self.big {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        })
+    }
+
+    pub fn u64(&self, off: usize) -> Result<u64, E> {
+        let b = self.array(off)?;
+
+        Ok(match self.big {
+            true => u64::from_be_bytes(b),
+            false => u64::from_le_bytes(b),
+        })
+    }
+}
+
+/// Returns the nul terminated string at an offset, or "" if it is invalid
+pub(crate) fn string(table: &[u8], off: usize) -> &str {
+    let s = table.get(off..).unwrap_or(&[]);
+    let len = s.iter().position(|b| *b == 0).unwrap_or(s.len());
+
+    core::str::from_utf8(&s[..len]).unwrap_or("")
+}
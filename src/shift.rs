@@ -5,6 +5,7 @@ use bad64_sys::*;
 
 /// A shift applied to a register or immediate
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 pub enum Shift {
     LSL(u32),
@@ -0,0 +1,503 @@
+//! Masked byte signatures
+//!
+//! A [`Signature`] is a run of instruction words, each with a mask of the
+//! bits which must match. Generating one from code wildcards the fields which
+//! change between builds of the same source, so it can be found again in
+//! code linked at other addresses or with other data layouts:
+//!
+//! - immediates, such as `add` constants and load and store offsets
+//! - labels: the PC-relative offsets of branches, `adr`, `adrp` and literal
+//!   loads
+//! - optionally, registers
+//!
+//! The decoder does not say which bits of an opcode hold which field, so
+//! [`Fields::of`] finds out by flipping each bit and decoding the result.
+//! A bit whose change alters the op or the shape of the operands is always
+//! kept, so a signature never matches a different kind of instruction.
+//!
+//! Signatures print and parse as a list of hex words, with a mask after any
+//! which are not matched exactly:
+//!
+//! ```text
+//! a9bf7bfd 910003fd 94000000/fc000000
+//! ```
+//!
+//! # Example
+//! ```
+//! use bad64::signature::{Signature, Wildcards};
+//!
+//! // bl 0x1100
+//! // mov w0, #0
+//! let code = b"\x40\x00\x00\x94\x00\x00\x80\x52";
+//! let sig = Signature::new(code, 0x1000, Wildcards::default());
+//!
+//! assert_eq!(sig.to_string(), "94000000/fc000000 12800000/bfe0001f");
+//!
+//! // bl 0x5000, at another address
+//! // mov w0, #1
+//! let other = b"\x1f\x20\x03\xd5\xff\x0f\x00\x94\x20\x00\x80\x52";
+//! assert_eq!(sig.scan(other, 0x1000).collect::<Vec<_>>(), [0x1004]);
+//! ```
+
+use alloc::vec::Vec;
+
+use core::convert::TryInto;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::{Imm, Instruction, Operand, Reg, Shift, decode};
+
+/// An error parsing a [`Signature`]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum SignatureError {
+    /// The word at this index is not hex, or has bits set outside its mask
+    BadWord(usize),
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::BadWord(n) => write!(f, "Bad word: {}", n),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignatureError {}
+
+/// The bits of an opcode holding each kind of field
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Fields {
+    /// Immediate values, offsets and shift amounts
+    pub immediates: u32,
+    /// PC-relative offsets
+    pub labels: u32,
+    /// Register numbers
+    pub registers: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Field {
+    Immediate,
+    Label,
+    Register,
+}
+
+impl Fields {
+    /// Returns the bits of an instruction's opcode holding each kind of field
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::decode;
+    /// use bad64::signature::Fields;
+    ///
+    /// // ldr x0, [x1, #8]
+    /// let fields = Fields::of(&decode(0xf9400420, 0x1000).unwrap());
+    ///
+    /// assert_eq!(fields.immediates, 0x003ffc00);
+    /// assert_eq!(fields.labels, 0);
+    /// assert_eq!(fields.registers, 0x000003ff);
+    /// ```
+    pub fn of(ins: &Instruction) -> Self {
+        let mut fields = Self::default();
+
+        for bit in 0..32 {
+            match field(ins, bit) {
+                Some(Field::Immediate) => fields.immediates |= 1 << bit,
+                Some(Field::Label) => fields.labels |= 1 << bit,
+                Some(Field::Register) => fields.registers |= 1 << bit,
+                None => (),
+            }
+        }
+
+        fields
+    }
+}
+
+/// The kinds of field a generated [`Signature`] does not match
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Wildcards {
+    pub immediates: bool,
+    pub labels: bool,
+    pub registers: bool,
+}
+
+impl Default for Wildcards {
+    /// Immediates and labels, but not registers
+    fn default() -> Self {
+        Self {
+            immediates: true,
+            labels: true,
+            registers: false,
+        }
+    }
+}
+
+impl Wildcards {
+    /// Returns the bits of an opcode to match, given its fields
+    fn mask(&self, fields: Fields) -> u32 {
+        let mut wild = 0;
+
+        if self.immediates {
+            wild |= fields.immediates;
+        }
+        if self.labels {
+            wild |= fields.labels;
+        }
+        if self.registers {
+            wild |= fields.registers;
+        }
+
+        !wild
+    }
+}
+
+/// A masked byte signature of a run of instructions
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Signature {
+    // the value and mask of each word, with no value bits outside the mask
+    words: Vec<(u32, u32)>,
+    // the index of the word with the most bits to match, checked first
+    anchor: usize,
+}
+
+impl Signature {
+    /// Generate a signature from code at `address`
+    ///
+    /// Words which fail to decode are matched exactly. A trailing partial word
+    /// is ignored.
+    pub fn new(code: &[u8], address: u64, wildcards: Wildcards) -> Self {
+        let words = code
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(n, w)| {
+                let word = u32::from_le_bytes(w.try_into().unwrap());
+                let mask = match decode(word, address.wrapping_add(n as u64 * 4)) {
+                    Ok(ins) => wildcards.mask(Fields::of(&ins)),
+                    Err(_) => !0,
+                };
+
+                (word & mask, mask)
+            })
+            .collect();
+
+        Self::from_words(words)
+    }
+
+    fn from_words(words: Vec<(u32, u32)>) -> Self {
+        let anchor = (0..words.len())
+            .max_by_key(|n| (words[*n].1.count_ones(), usize::MAX - n))
+            .unwrap_or(0);
+
+        Self { words, anchor }
+    }
+
+    /// Returns the value and mask of each word
+    pub fn words(&self) -> &[(u32, u32)] {
+        &self.words
+    }
+
+    /// Returns the length of the signature in bytes
+    pub fn len(&self) -> usize {
+        self.words.len() * 4
+    }
+
+    /// Returns if the signature has no words, and so matches anywhere
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Returns if code starts with the signature
+    pub fn matches(&self, code: &[u8]) -> bool {
+        if code.len() < self.len() {
+            return false;
+        }
+
+        let word = |n: usize| u32::from_le_bytes(code[n * 4..n * 4 + 4].try_into().unwrap());
+        let check = |n: usize| word(n) & self.words[n].1 == self.words[n].0;
+
+        self.is_empty() || (check(self.anchor) && (0..self.words.len()).all(check))
+    }
+
+    /// Returns the address of every match in code at `address`, at aligned
+    /// addresses only
+    pub fn scan<'a>(&'a self, code: &'a [u8], address: u64) -> impl Iterator<Item = u64> + 'a {
+        let first = (address.wrapping_neg() % 4) as usize;
+        let last = code.len().checked_sub(self.len());
+
+        (first..=last.unwrap_or(0))
+            .step_by(4)
+            .filter(move |n| last.is_some() && self.matches(&code[*n..]))
+            .map(move |n| address.wrapping_add(n as u64))
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, (value, mask)) in self.words.iter().enumerate() {
+            if n > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{:08x}", value)?;
+
+            if *mask != !0 {
+                write!(f, "/{:08x}", mask)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Signature {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s
+            .split_whitespace()
+            .enumerate()
+            .map(|(n, w)| {
+                let (value, mask) = w.split_once('/').unwrap_or((w, "ffffffff"));
+                let hex = |h: &str| {
+                    (h.len() == 8)
+                        .then(|| u32::from_str_radix(h, 16).ok())
+                        .flatten()
+                };
+
+                match (hex(value), hex(mask)) {
+                    (Some(value), Some(mask)) if value & !mask == 0 => Ok((value, mask)),
+                    _ => Err(SignatureError::BadWord(n)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self::from_words(words))
+    }
+}
+
+/// Returns the kind of field a bit of an instruction's opcode is part of
+fn field(ins: &Instruction, bit: u32) -> Option<Field> {
+    let other = decode(ins.opcode() ^ (1 << bit), ins.address()).ok()?;
+
+    if other.op() != ins.op()
+        || other.flags_set() != ins.flags_set()
+        || other.operands().len() != ins.operands().len()
+    {
+        return None;
+    }
+
+    let mut field = None;
+
+    for (a, b) in ins.operands().iter().zip(other.operands()) {
+        if a == b {
+            continue;
+        }
+
+        let f = difference(a, b)?;
+        if field.is_some_and(|g| g != f) {
+            return None;
+        }
+
+        field = Some(f);
+    }
+
+    field
+}
+
+/// Returns the kind of field which differs between two operands, if only one
+fn difference(a: &Operand, b: &Operand) -> Option<Field> {
+    if matches!((a, b), (Operand::Label(_), Operand::Label(_))) {
+        return Some(Field::Label);
+    }
+
+    if without_imms(*a) == without_imms(*b) {
+        return Some(Field::Immediate);
+    }
+
+    // a register may change number, but not size or kind
+    let gpr = |r: Reg| (Reg::W0 as u32..=Reg::SP as u32).contains(&(r as u32));
+    let same_kind = |x: Reg, y: Reg| {
+        x.size() == y.size()
+            && gpr(x) == gpr(y)
+            && x.is_simd() == y.is_simd()
+            && x.is_sve() == y.is_sve()
+            && x.is_pred() == y.is_pred()
+    };
+
+    let regs_a = registers(a);
+    let regs_b = registers(b);
+    let kinds_match = regs_a.iter().zip(&regs_b).all(|pair| match pair {
+        (Some(x), Some(y)) => same_kind(*x, *y),
+        (x, y) => x.is_none() && y.is_none(),
+    });
+
+    if kinds_match && without_regs(*a) == without_regs(*b) {
+        return Some(Field::Register);
+    }
+
+    None
+}
+
+fn without_shift(shift: Shift) -> Shift {
+    match shift {
+        Shift::LSL(_) => Shift::LSL(0),
+        Shift::LSR(_) => Shift::LSR(0),
+        Shift::ASR(_) => Shift::ASR(0),
+        Shift::ROR(_) => Shift::ROR(0),
+        Shift::UXTW(_) => Shift::UXTW(0),
+        Shift::SXTW(_) => Shift::SXTW(0),
+        Shift::SXTX(_) => Shift::SXTX(0),
+        Shift::UXTX(_) => Shift::UXTX(0),
+        Shift::SXTB(_) => Shift::SXTB(0),
+        Shift::SXTH(_) => Shift::SXTH(0),
+        Shift::UXTH(_) => Shift::UXTH(0),
+        Shift::UXTB(_) => Shift::UXTB(0),
+        Shift::MSL(_) => Shift::MSL(0),
+    }
+}
+
+/// Returns an operand with its immediates and shift amounts zeroed
+fn without_imms(o: Operand) -> Operand {
+    const ZERO: Imm = Imm::Unsigned(0);
+
+    match o {
+        Operand::Imm32 { shift, .. } => Operand::Imm32 {
+            imm: ZERO,
+            shift: shift.map(without_shift),
+        },
+        Operand::Imm64 { shift, .. } => Operand::Imm64 {
+            imm: ZERO,
+            shift: shift.map(without_shift),
+        },
+        Operand::FImm32(_) => Operand::FImm32(0),
+        Operand::ShiftReg { reg, shift } => Operand::ShiftReg {
+            reg,
+            shift: without_shift(shift),
+        },
+        Operand::MemOffset {
+            reg,
+            mul_vl,
+            arrspec,
+            ..
+        } => Operand::MemOffset {
+            reg,
+            offset: ZERO,
+            mul_vl,
+            arrspec,
+        },
+        Operand::MemPreIdx { reg, .. } => Operand::MemPreIdx { reg, imm: ZERO },
+        Operand::MemPostIdxImm { reg, .. } => Operand::MemPostIdxImm { reg, imm: ZERO },
+        Operand::MemExt {
+            regs,
+            shift,
+            arrspec,
+        } => Operand::MemExt {
+            regs,
+            shift: shift.map(without_shift),
+            arrspec,
+        },
+        Operand::SmeTile {
+            tile,
+            slice,
+            arrspec,
+            reg,
+            ..
+        } => Operand::SmeTile {
+            tile,
+            slice,
+            arrspec,
+            reg,
+            imm: ZERO,
+        },
+        Operand::AccumArray { reg, .. } => Operand::AccumArray { reg, imm: ZERO },
+        Operand::IndexedElement { regs, arrspec, .. } => Operand::IndexedElement {
+            regs,
+            arrspec,
+            imm: ZERO,
+        },
+        Operand::StrImm { str, .. } => Operand::StrImm { str, imm: 0 },
+        o => o,
+    }
+}
+
+/// Returns the registers of an operand, in order
+fn registers(o: &Operand) -> [Option<Reg>; 4] {
+    match *o {
+        Operand::ShiftReg { reg, .. }
+        | Operand::QualReg { reg, .. }
+        | Operand::Reg { reg, .. }
+        | Operand::MemReg(reg)
+        | Operand::MemOffset { reg, .. }
+        | Operand::MemPreIdx { reg, .. }
+        | Operand::MemPostIdxImm { reg, .. }
+        | Operand::AccumArray { reg, .. } => [Some(reg), None, None, None],
+        Operand::SmeTile { reg, .. } => [reg, None, None, None],
+        Operand::MemPostIdxReg(regs)
+        | Operand::MemExt { regs, .. }
+        | Operand::IndexedElement { regs, .. } => [Some(regs[0]), Some(regs[1]), None, None],
+        Operand::MultiReg { regs, .. } => {
+            let mut out = [None; 4];
+            for (o, r) in out.iter_mut().zip(regs) {
+                *o = r;
+            }
+            out
+        }
+        _ => [None; 4],
+    }
+}
+
+/// Returns an operand with its registers replaced by `X0`
+fn without_regs(o: Operand) -> Operand {
+    const R: Reg = Reg::X0;
+
+    match o {
+        Operand::ShiftReg { shift, .. } => Operand::ShiftReg { reg: R, shift },
+        Operand::QualReg { qual, .. } => Operand::QualReg { reg: R, qual },
+        Operand::Reg { arrspec, .. } => Operand::Reg { reg: R, arrspec },
+        Operand::MultiReg { regs, arrspec } => Operand::MultiReg {
+            regs: regs.map(|r| r.map(|_| R)),
+            arrspec,
+        },
+        Operand::MemReg(_) => Operand::MemReg(R),
+        Operand::MemOffset {
+            offset,
+            mul_vl,
+            arrspec,
+            ..
+        } => Operand::MemOffset {
+            reg: R,
+            offset,
+            mul_vl,
+            arrspec,
+        },
+        Operand::MemPreIdx { imm, .. } => Operand::MemPreIdx { reg: R, imm },
+        Operand::MemPostIdxReg(_) => Operand::MemPostIdxReg([R; 2]),
+        Operand::MemPostIdxImm { imm, .. } => Operand::MemPostIdxImm { reg: R, imm },
+        Operand::MemExt { shift, arrspec, .. } => Operand::MemExt {
+            regs: [R; 2],
+            shift,
+            arrspec,
+        },
+        Operand::SmeTile {
+            tile,
+            slice,
+            arrspec,
+            reg,
+            imm,
+        } => Operand::SmeTile {
+            tile,
+            slice,
+            arrspec,
+            reg: reg.map(|_| R),
+            imm,
+        },
+        Operand::AccumArray { imm, .. } => Operand::AccumArray { reg: R, imm },
+        Operand::IndexedElement { arrspec, imm, .. } => Operand::IndexedElement {
+            regs: [R; 2],
+            arrspec,
+            imm,
+        },
+        o => o,
+    }
+}
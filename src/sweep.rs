@@ -0,0 +1,395 @@
+//! Linear sweep with data detection
+//!
+//! [`crate::disasm`] decodes every word it is given, so literal pools, jump
+//! tables and alignment padding come out as bogus instructions or decode
+//! errors. [`sweep`] decodes a buffer the same way, then picks out the words
+//! which are really data:
+//!
+//! - constants loaded by `LDR (literal)` and `LDRSW (literal)`
+//! - jump tables addressed by an `ADR` and indexed by a load feeding a `BR`
+//! - zero words (`UDF #0`), and runs of `NOP` no branch can reach
+//! - ranges the caller already knows to be data, such as ELF `$d` regions
+//! - any other word which fails to decode
+//!
+//! Adjacent data words of the same kind are merged into one [`Data`] item.
+//! This is a heuristic: a table without a visible bound is assumed to go on
+//! while its entries look like offsets into the code.
+//!
+//! # Example
+//! ```
+//! use bad64::Op;
+//! use bad64::sweep::{sweep, DataKind, Item};
+//!
+//! // ldr x0, #8
+//! // ret
+//! // .quad 0x1122334455667788
+//! let code = b"\x40\x00\x00\x58\xc0\x03\x5f\xd6\x88\x77\x66\x55\x44\x33\x22\x11";
+//! let items = sweep(code, 0x1000, &[]);
+//!
+//! assert_eq!(items.len(), 3);
+//! assert!(matches!(&items[1], Item::Instruction(i) if i.op() == Op::RET));
+//!
+//! let Item::Data(data) = &items[2] else { panic!() };
+//! assert_eq!(data.address, 0x1008);
+//! assert_eq!(data.bytes.len(), 8);
+//! assert_eq!(data.kind, DataKind::Literal);
+//! ```
+
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use core::convert::TryInto;
+use core::ops::Range;
+
+use crate::{Imm, Instruction, NextPc, Op, Operand, Reg, decode, next_pcs};
+
+// how far from an ADR to look for the load and BR using a jump table
+const WINDOW: usize = 8;
+
+/// What a run of data words holds
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DataKind {
+    /// Constants loaded by `LDR (literal)`
+    Literal,
+    /// The entries of a jump table
+    JumpTable,
+    /// Zero words, or `NOP`s which are never executed
+    Padding,
+    /// A range the caller marked as data
+    Mapped,
+    /// Words which fail to decode
+    Invalid,
+}
+
+/// A run of bytes holding data rather than instructions
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Data<'a> {
+    pub address: u64,
+    pub bytes: &'a [u8],
+    pub kind: DataKind,
+}
+
+/// An instruction or run of data found by [`sweep`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Item<'a> {
+    Instruction(Instruction),
+    Data(Data<'a>),
+}
+
+impl Item<'_> {
+    /// Returns the address of the item
+    pub fn address(&self) -> u64 {
+        match self {
+            Self::Instruction(ins) => ins.address(),
+            Self::Data(data) => data.address,
+        }
+    }
+}
+
+/// Disassemble a buffer, returning the words found to be data as [`Data`]
+///
+/// `data` holds address ranges known to be data, which are always returned
+/// as [`DataKind::Mapped`]. Any trailing bytes short of a word are returned as
+/// [`DataKind::Invalid`].
+pub fn sweep<'a>(code: &'a [u8], address: u64, data: &[Range<u64>]) -> Vec<Item<'a>> {
+    let words: Vec<Option<Instruction>> = code
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(n, w)| {
+            decode(
+                u32::from_le_bytes(w.try_into().unwrap()),
+                address.wrapping_add(n as u64 * 4),
+            )
+            .ok()
+        })
+        .collect();
+
+    let mut sweep = Sweep {
+        code,
+        address,
+        kinds: vec![None; words.len()],
+        words,
+    };
+
+    for r in data {
+        sweep.mark(r.clone(), DataKind::Mapped);
+    }
+
+    sweep.literals();
+    sweep.jump_tables();
+    sweep.padding();
+    sweep.items()
+}
+
+struct Sweep<'a> {
+    code: &'a [u8],
+    address: u64,
+    words: Vec<Option<Instruction>>,
+    // per word, the kind of data found there so far
+    kinds: Vec<Option<DataKind>>,
+}
+
+impl<'a> Sweep<'a> {
+    /// Returns the index of the word containing an address
+    fn index(&self, address: u64) -> Option<usize> {
+        let n = address.checked_sub(self.address)? / 4;
+
+        (n < self.words.len() as u64).then_some(n as usize)
+    }
+
+    /// Returns the instruction at a word, unless it has been found to be data
+    fn instruction(&self, n: usize) -> Option<&Instruction> {
+        match self.kinds.get(n)? {
+            Some(_) => None,
+            None => self.words[n].as_ref(),
+        }
+    }
+
+    /// Marks the words overlapping a range, unless they are already data
+    fn mark(&mut self, range: Range<u64>, kind: DataKind) {
+        let end = self.address.saturating_add(self.words.len() as u64 * 4);
+        let start = range.start.max(self.address);
+        let stop = range.end.min(end);
+
+        if start >= stop {
+            return;
+        }
+
+        let first = ((start - self.address) / 4) as usize;
+        let last = ((stop - 1 - self.address) / 4) as usize;
+
+        for k in &mut self.kinds[first..=last] {
+            k.get_or_insert(kind);
+        }
+    }
+
+    fn literals(&mut self) {
+        for n in 0..self.words.len() {
+            if let Some((target, size)) = self.instruction(n).and_then(literal) {
+                self.mark(target..target.saturating_add(size), DataKind::Literal);
+            }
+        }
+    }
+
+    fn jump_tables(&mut self) {
+        for n in 0..self.words.len() {
+            if let Some(table) = self.jump_table(n) {
+                self.mark(table, DataKind::JumpTable);
+            }
+        }
+    }
+
+    /// Returns the jump table addressed by an `ADR` at word `n`, if any
+    fn jump_table(&self, n: usize) -> Option<Range<u64>> {
+        let adr = self.instruction(n).filter(|i| i.op() == Op::ADR)?;
+
+        let (base, table) = match adr.operands() {
+            [Operand::Reg { reg, .. }, Operand::Label(Imm::Unsigned(t))] => (*reg, *t),
+            _ => return None,
+        };
+        self.index(table)?;
+
+        // the load indexing the table must come before the BR
+        let mut size = None;
+        let br = (n + 1..n + 1 + WINDOW).find(|m| {
+            let Some(ins) = self.instruction(*m) else {
+                return false;
+            };
+
+            size = size.or_else(|| entry_size(ins, base));
+            ins.op() == Op::BR
+        })?;
+        let size = size?;
+
+        // a compare against the number of cases bounds the table
+        let count = (n.saturating_sub(WINDOW)..br)
+            .rev()
+            .filter_map(|m| self.instruction(m))
+            .find_map(bound);
+
+        let count = match count {
+            Some(count) => count,
+            None if size >= 4 => self.entries(table, size),
+            None => 1,
+        };
+
+        Some(table..table.saturating_add(count.saturating_mul(size)))
+    }
+
+    /// Counts the entries of an unbounded jump table, while they look like
+    /// offsets into or addresses of the code
+    fn entries(&self, table: u64, size: u64) -> u64 {
+        let len = self.code.len() as u64;
+        let code = self.address..self.address.saturating_add(len);
+
+        let mut count = 0;
+        while let Some(at) = table.checked_add(count * size) {
+            let Some(n) = self.index(at) else { break };
+            if self.kinds[n].is_some() {
+                break;
+            }
+
+            let off = (at - self.address) as usize;
+            let Some(bytes) = self.code.get(off..off + size as usize) else {
+                break;
+            };
+
+            let plausible = match size {
+                4 => {
+                    let v = i32::from_le_bytes(bytes.try_into().unwrap());
+                    v != 0 && v % 4 == 0 && v.unsigned_abs() < len as u32
+                }
+                _ => code.contains(&u64::from_le_bytes(bytes.try_into().unwrap())),
+            };
+
+            if !plausible {
+                break;
+            }
+
+            count += 1;
+        }
+
+        count.max(1)
+    }
+
+    fn padding(&mut self) {
+        let mut targets = BTreeSet::new();
+        for ins in (0..self.words.len()).filter_map(|n| self.instruction(n)) {
+            for pc in &next_pcs(ins, &()) {
+                if let NextPc::Taken(target) = pc {
+                    targets.insert(*target);
+                }
+            }
+        }
+
+        // whether execution can flow into the current word from the previous
+        let mut reachable = true;
+
+        for n in 0..self.words.len() {
+            if self.kinds[n].is_some() {
+                reachable = false;
+                continue;
+            }
+
+            if self.code[n * 4..n * 4 + 4] == [0; 4] {
+                self.kinds[n] = Some(DataKind::Padding);
+                reachable = false;
+                continue;
+            }
+
+            let Some(ins) = &self.words[n] else {
+                reachable = false;
+                continue;
+            };
+
+            if ins.op() == Op::NOP && !reachable && !targets.contains(&ins.address()) {
+                self.kinds[n] = Some(DataKind::Padding);
+                continue;
+            }
+
+            reachable = falls_through(ins);
+        }
+    }
+
+    fn items(self) -> Vec<Item<'a>> {
+        let mut items: Vec<Item<'a>> = Vec::new();
+
+        for (n, (word, kind)) in self.words.into_iter().zip(self.kinds).enumerate() {
+            let kind = match (word, kind) {
+                (Some(ins), None) => {
+                    items.push(Item::Instruction(ins));
+                    continue;
+                }
+                (None, None) => DataKind::Invalid,
+                (_, Some(kind)) => kind,
+            };
+
+            match items.last_mut() {
+                Some(Item::Data(data)) if data.kind == kind => {
+                    let start = data.address.wrapping_sub(self.address) as usize;
+                    data.bytes = &self.code[start..n * 4 + 4];
+                }
+                _ => items.push(Item::Data(Data {
+                    address: self.address.wrapping_add(n as u64 * 4),
+                    bytes: &self.code[n * 4..n * 4 + 4],
+                    kind,
+                })),
+            }
+        }
+
+        let tail = self.code.len() & !3;
+        if tail < self.code.len() {
+            items.push(Item::Data(Data {
+                address: self.address.wrapping_add(tail as u64),
+                bytes: &self.code[tail..],
+                kind: DataKind::Invalid,
+            }));
+        }
+
+        items
+    }
+}
+
+/// Returns the address and size of the constant read by a literal load
+fn literal(ins: &Instruction) -> Option<(u64, u64)> {
+    match (ins.op(), ins.operands()) {
+        (Op::LDRSW, [_, Operand::Label(Imm::Unsigned(target))]) => Some((*target, 4)),
+        (
+            Op::LDR,
+            [
+                Operand::Reg { reg, .. },
+                Operand::Label(Imm::Unsigned(target)),
+            ],
+        ) => Some((*target, reg.size() as u64)),
+        _ => None,
+    }
+}
+
+/// Returns the entry size of a load from a table based at `base`
+fn entry_size(ins: &Instruction, base: Reg) -> Option<u64> {
+    let [Operand::Reg { reg, .. }, Operand::MemExt { regs, .. }] = ins.operands() else {
+        return None;
+    };
+
+    if regs[0] != base {
+        return None;
+    }
+
+    match ins.op() {
+        Op::LDRB | Op::LDRSB => Some(1),
+        Op::LDRH | Op::LDRSH => Some(2),
+        Op::LDRSW => Some(4),
+        Op::LDR => Some(reg.size() as u64),
+        _ => None,
+    }
+}
+
+/// Returns the number of cases implied by a `CMP` against an immediate
+fn bound(ins: &Instruction) -> Option<u64> {
+    match (ins.op(), ins.operands()) {
+        (
+            Op::CMP,
+            [
+                _,
+                Operand::Imm32 { imm, shift: None } | Operand::Imm64 { imm, shift: None },
+            ],
+        ) => match imm {
+            Imm::Unsigned(n) => n.checked_add(1),
+            Imm::Signed(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns if execution can continue at the next word
+fn falls_through(ins: &Instruction) -> bool {
+    let call = matches!(
+        ins.op(),
+        Op::BL | Op::BLR | Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ
+    );
+
+    call || next_pcs(ins, &())
+        .iter()
+        .any(|pc| matches!(pc, NextPc::Fallthrough(_)))
+}
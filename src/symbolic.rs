@@ -0,0 +1,586 @@
+//! Symbolic register values over a basic block
+//!
+//! Evaluates a straight-line sequence of instructions, tracking the value of
+//! every general purpose register as an [`Expr`] over the registers' values
+//! on entry to the block and constants. Constants are folded as they are
+//! built, so values materialized across several instructions, such as an
+//! address built with `MOVZ`/`MOVK` or `ADRP`/`ADD`, resolve to a single
+//! [`Expr::Const`].
+//!
+//! Only simple integer data movement and arithmetic is modelled: `MOV`,
+//! `MOVZ`, `MOVN`, `MOVK`, `ADD`, `SUB`, `NEG`, `AND`, `ORR`, `EOR`, `MVN`
+//! (including the flag setting forms), `LSL`, `LSR`, `ASR`, `ADR` and `ADRP`,
+//! plus base register writeback on loads and stores. Any other instruction
+//! writing a register makes that register's value unknown, as do calls for
+//! the AAPCS64 caller-saved registers.
+//!
+//! # Example
+//! ```
+//! use bad64::{decode, Reg};
+//! use bad64::symbolic::{Expr, State};
+//!
+//! let ins = [
+//!     decode(0xd2a24680, 0x1000).unwrap(), // mov x0, #0x12340000
+//!     decode(0xf2cacf00, 0x1004).unwrap(), // movk x0, #0x5678, lsl #32
+//!     decode(0xd0000021, 0x1008).unwrap(), // adrp x1, 0x7000
+//!     decode(0x91004021, 0x100c).unwrap(), // add x1, x1, #0x10
+//!     decode(0x8b020c22, 0x1010).unwrap(), // add x2, x1, x2, lsl #3
+//! ];
+//!
+//! let state = State::analyze(&ins);
+//!
+//! assert_eq!(state.constant(Reg::X0), Some(0x5678_1234_0000));
+//! assert_eq!(state.constant(Reg::W0), Some(0x1234_0000));
+//! assert_eq!(state.constant(Reg::X1), Some(0x7010));
+//! assert_eq!(state.value(Reg::X2).unwrap().to_string(), "((x2 << 0x3) + 0x7010)");
+//! assert_eq!(state.value(Reg::X3), Some(Expr::Reg(Reg::X3)));
+//! ```
+
+use alloc::boxed::Box;
+
+use core::fmt;
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::{Imm, Instruction, Op, Operand, Reg, Shift};
+
+/// A symbolic 64-bit value
+///
+/// Constructing values through [`State`] keeps them in a simplified form:
+/// constants are folded and constant operands are always on the right.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Expr {
+    /// A constant
+    Const(u64),
+    /// The value of a 64-bit register (`X0`-`X30` or `SP`) on entry
+    Reg(Reg),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    /// Shift left by a constant
+    Shl(Box<Expr>, u32),
+    /// Logical shift right by a constant
+    LShr(Box<Expr>, u32),
+    /// Arithmetic shift right by a constant
+    AShr(Box<Expr>, u32),
+}
+
+impl Expr {
+    /// Returns the value if the expression is a constant
+    pub fn as_const(&self) -> Option<u64> {
+        match *self {
+            Expr::Const(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the expression given the block's input registers
+    ///
+    /// Returns `None` if `input` does not know a register the expression
+    /// depends on.
+    ///
+    /// # Example
+    /// ```
+    /// use bad64::{decode, Reg};
+    /// use bad64::symbolic::State;
+    ///
+    /// // sub w0, w1, #0x8
+    /// let state = State::analyze(&[decode(0x51002020, 0x1000).unwrap()]);
+    /// let x0 = state.value(Reg::X0).unwrap();
+    ///
+    /// assert_eq!(x0.eval(|_| Some(0x1_0000_0004)), Some(0xffff_fffc));
+    /// assert_eq!(x0.eval(|_| None), None);
+    /// ```
+    pub fn eval<F>(&self, input: F) -> Option<u64>
+    where
+        F: Fn(Reg) -> Option<u64> + Copy,
+    {
+        Some(match self {
+            Expr::Const(c) => *c,
+            Expr::Reg(r) => input(*r)?,
+            Expr::Add(a, b) => a.eval(input)?.wrapping_add(b.eval(input)?),
+            Expr::Sub(a, b) => a.eval(input)?.wrapping_sub(b.eval(input)?),
+            Expr::And(a, b) => a.eval(input)? & b.eval(input)?,
+            Expr::Or(a, b) => a.eval(input)? | b.eval(input)?,
+            Expr::Xor(a, b) => a.eval(input)? ^ b.eval(input)?,
+            Expr::Shl(a, n) => a.eval(input)?.checked_shl(*n).unwrap_or(0),
+            Expr::LShr(a, n) => a.eval(input)?.checked_shr(*n).unwrap_or(0),
+            Expr::AShr(a, n) => ((a.eval(input)? as i64) >> (*n).min(63)) as u64,
+        })
+    }
+
+    fn add(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.wrapping_add(b)),
+            (a @ Expr::Const(_), b) => Expr::add(b, a),
+            (a, Expr::Const(0)) => a,
+            (Expr::Add(a, c1), Expr::Const(c2)) if c1.as_const().is_some() => {
+                Expr::add(*a, Expr::add(*c1, Expr::Const(c2)))
+            }
+            (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn sub(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (a, Expr::Const(c)) => Expr::add(a, Expr::Const(c.wrapping_neg())),
+            (a, b) => Expr::Sub(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn and(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a & b),
+            (a @ Expr::Const(_), b) => Expr::and(b, a),
+            (_, Expr::Const(0)) => Expr::Const(0),
+            (a, Expr::Const(u64::MAX)) => a,
+            (Expr::And(a, c1), Expr::Const(c2)) if c1.as_const().is_some() => {
+                Expr::and(*a, Expr::and(*c1, Expr::Const(c2)))
+            }
+            (a, b) => Expr::And(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn or(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a | b),
+            (a @ Expr::Const(_), b) => Expr::or(b, a),
+            (a, Expr::Const(0)) => a,
+            (Expr::Or(a, c1), Expr::Const(c2)) if c1.as_const().is_some() => {
+                Expr::or(*a, Expr::or(*c1, Expr::Const(c2)))
+            }
+            (a, b) => Expr::Or(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn xor(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a ^ b),
+            (a @ Expr::Const(_), b) => Expr::xor(b, a),
+            (a, Expr::Const(0)) => a,
+            (a, b) => Expr::Xor(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn shl(a: Expr, n: u32) -> Expr {
+        match a {
+            a if n == 0 => a,
+            Expr::Const(c) => Expr::Const(c.checked_shl(n).unwrap_or(0)),
+            a => Expr::Shl(Box::new(a), n),
+        }
+    }
+
+    fn lshr(a: Expr, n: u32) -> Expr {
+        match a {
+            a if n == 0 => a,
+            Expr::Const(c) => Expr::Const(c.checked_shr(n).unwrap_or(0)),
+            a => Expr::LShr(Box::new(a), n),
+        }
+    }
+
+    fn ashr(a: Expr, n: u32) -> Expr {
+        match a {
+            a if n == 0 => a,
+            Expr::Const(c) => Expr::Const(((c as i64) >> n.min(63)) as u64),
+            a => Expr::AShr(Box::new(a), n),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(c) => write!(f, "{:#x}", c),
+            Expr::Reg(r) => write!(f, "{}", r),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Sub(a, b) => write!(f, "({} - {})", a, b),
+            Expr::And(a, b) => write!(f, "({} & {})", a, b),
+            Expr::Or(a, b) => write!(f, "({} | {})", a, b),
+            Expr::Xor(a, b) => write!(f, "({} ^ {})", a, b),
+            Expr::Shl(a, n) => write!(f, "({} << {:#x})", a, n),
+            Expr::LShr(a, n) => write!(f, "({} >> {:#x})", a, n),
+            Expr::AShr(a, n) => write!(f, "({} s>> {:#x})", a, n),
+        }
+    }
+}
+
+const SP: usize = 31;
+
+/// The symbolic register state at a point in a block
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct State {
+    // X0-X30 then SP, `None` once a register's value is unknown
+    regs: [Option<Expr>; 32],
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    /// Create the state on entry to a block, with every register holding its
+    /// input value
+    pub fn new() -> Self {
+        Self {
+            regs: core::array::from_fn(|n| Some(Expr::Reg(x_reg(n)))),
+        }
+    }
+
+    /// Evaluate a straight-line sequence of instructions from the entry
+    /// state
+    pub fn analyze<'a, I>(ins: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Instruction>,
+    {
+        let mut state = Self::new();
+
+        for ins in ins {
+            state.step(ins);
+        }
+
+        state
+    }
+
+    /// Returns the value of a general purpose register
+    ///
+    /// `W` registers are the low 32 bits of the `X` register and the zero
+    /// registers are always 0. Returns `None` if the value is unknown or the
+    /// register is not a general purpose register.
+    pub fn value(&self, reg: Reg) -> Option<Expr> {
+        match reg {
+            Reg::XZR | Reg::WZR => return Some(Expr::Const(0)),
+            _ => (),
+        }
+
+        let (n, bits) = index(reg)?;
+        let value = self.regs[n].clone()?;
+
+        Some(truncate(value, bits))
+    }
+
+    /// Returns the value of a general purpose register if it is a constant
+    pub fn constant(&self, reg: Reg) -> Option<u64> {
+        self.value(reg)?.as_const()
+    }
+
+    /// Updates the state with an instruction's effects
+    ///
+    /// Branches are not followed; the instruction is evaluated as if it were
+    /// the next one in the block.
+    pub fn step(&mut self, ins: &Instruction) {
+        if let Some((reg, value)) = self.eval(ins) {
+            self.set(reg, value);
+            return;
+        }
+
+        let ops = ins.operands();
+
+        match ins.op() {
+            // aapcs64 caller-saved registers, and the link register
+            Op::BL | Op::BLR | Op::BLRAA | Op::BLRAAZ | Op::BLRAB | Op::BLRABZ => {
+                for n in (0..=18).chain([30]) {
+                    self.regs[n] = None;
+                }
+            }
+            op if is_branch_or_compare(op) => (),
+            op => match ops.iter().position(is_mem) {
+                Some(mem) => {
+                    if !is_store(op) {
+                        for o in &ops[..mem] {
+                            if let Operand::Reg { reg, .. } = o {
+                                self.set(*reg, None);
+                            }
+                        }
+                    }
+
+                    self.writeback(&ops[mem]);
+                }
+                None => {
+                    if let Some(Operand::Reg { reg, .. }) = ops.first() {
+                        self.set(*reg, None);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Returns the destination and its new value for modelled instructions
+    fn eval(&self, ins: &Instruction) -> Option<(Reg, Option<Expr>)> {
+        let ops = ins.operands();
+
+        let (dst, value) = match (ins.op(), ops) {
+            (Op::MOV, [Operand::Reg { reg, .. }, imm]) => (*reg, self.operand(imm)),
+            (Op::MOVZ, [Operand::Reg { reg, .. }, imm]) => (*reg, self.operand(imm)),
+            (Op::MOVN, [Operand::Reg { reg, .. }, imm]) => (
+                *reg,
+                self.operand(imm).map(|v| Expr::xor(v, Expr::Const(!0))),
+            ),
+            (
+                Op::MOVK,
+                [
+                    Operand::Reg { reg, .. },
+                    Operand::Imm32 { imm, shift } | Operand::Imm64 { imm, shift },
+                ],
+            ) => {
+                let shift = match shift {
+                    Some(Shift::LSL(s)) => *s,
+                    None => 0,
+                    Some(_) => return None,
+                };
+                let keep = Expr::Const(!(0xffff << shift));
+                let imm = Expr::Const(imm_raw(*imm) << shift);
+
+                (
+                    *reg,
+                    self.value(*reg).map(|v| Expr::or(Expr::and(v, keep), imm)),
+                )
+            }
+            (Op::ADR | Op::ADRP, [Operand::Reg { reg, .. }, Operand::Label(imm)]) => {
+                (*reg, Some(Expr::Const(imm_raw(*imm))))
+            }
+            (Op::NEG | Op::NEGS, [Operand::Reg { reg, .. }, src]) => (
+                *reg,
+                self.operand(src).map(|v| Expr::sub(Expr::Const(0), v)),
+            ),
+            (Op::MVN, [Operand::Reg { reg, .. }, src]) => (
+                *reg,
+                self.operand(src).map(|v| Expr::xor(v, Expr::Const(!0))),
+            ),
+            (
+                op @ (Op::ADD
+                | Op::ADDS
+                | Op::SUB
+                | Op::SUBS
+                | Op::AND
+                | Op::ANDS
+                | Op::ORR
+                | Op::EOR),
+                [Operand::Reg { reg, .. }, lhs, rhs],
+            ) => {
+                let value = self.operand(lhs).zip(self.operand(rhs)).map(|(a, b)| {
+                    let f = match op {
+                        Op::ADD | Op::ADDS => Expr::add,
+                        Op::SUB | Op::SUBS => Expr::sub,
+                        Op::AND | Op::ANDS => Expr::and,
+                        Op::ORR => Expr::or,
+                        _ => Expr::xor,
+                    };
+
+                    f(a, b)
+                });
+
+                (*reg, value)
+            }
+            (
+                op @ (Op::LSL | Op::LSR | Op::ASR),
+                [
+                    Operand::Reg { reg, .. },
+                    src,
+                    Operand::Imm32 { imm, .. } | Operand::Imm64 { imm, .. },
+                ],
+            ) => {
+                let n = imm_raw(*imm) as u32;
+                let value = self.operand(src).and_then(|v| match op {
+                    Op::LSL => Some(Expr::shl(v, n)),
+                    Op::LSR => Some(Expr::lshr(v, n)),
+                    _ => self.asr(*reg, v, n),
+                });
+
+                (*reg, value)
+            }
+            _ => return None,
+        };
+
+        Some((dst, value))
+    }
+
+    /// Returns the value of a source operand
+    fn operand(&self, o: &Operand) -> Option<Expr> {
+        match *o {
+            Operand::Reg { reg, arrspec: None } => self.value(reg),
+            Operand::Imm32 { imm, shift } | Operand::Imm64 { imm, shift } => {
+                let imm = Expr::Const(imm_raw(imm));
+
+                match shift {
+                    None => Some(imm),
+                    Some(Shift::LSL(s)) => Some(Expr::shl(imm, s)),
+                    Some(_) => None,
+                }
+            }
+            Operand::ShiftReg { reg, shift } => {
+                let v = self.value(reg)?;
+
+                match shift {
+                    Shift::LSL(s) => Some(Expr::shl(v, s)),
+                    Shift::LSR(s) => Some(Expr::lshr(v, s)),
+                    Shift::ASR(s) => self.asr(reg, v, s),
+                    Shift::UXTB(s) => Some(Expr::shl(truncate(v, 8), s)),
+                    Shift::UXTH(s) => Some(Expr::shl(truncate(v, 16), s)),
+                    Shift::UXTW(s) => Some(Expr::shl(truncate(v, 32), s)),
+                    Shift::UXTX(s) => Some(Expr::shl(v, s)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Arithmetic shifts are only modelled on 64-bit registers
+    fn asr(&self, reg: Reg, v: Expr, n: u32) -> Option<Expr> {
+        match index(reg) {
+            Some((_, 64)) => Some(Expr::ashr(v, n)),
+            _ => None,
+        }
+    }
+
+    fn writeback(&mut self, mem: &Operand) {
+        let (reg, imm) = match *mem {
+            Operand::MemPreIdx { reg, imm } | Operand::MemPostIdxImm { reg, imm } => (reg, imm),
+            Operand::MemPostIdxReg([reg, _]) => {
+                self.set(reg, None);
+                return;
+            }
+            _ => return,
+        };
+
+        let value = self
+            .value(reg)
+            .map(|v| Expr::add(v, Expr::Const(imm_raw(imm))));
+
+        self.set(reg, value);
+    }
+
+    /// Writes a register, truncating and zero-extending for `W` registers
+    fn set(&mut self, reg: Reg, value: Option<Expr>) {
+        if let Some((n, bits)) = index(reg) {
+            self.regs[n] = value.map(|v| truncate(v, bits));
+        }
+    }
+}
+
+fn truncate(v: Expr, bits: u32) -> Expr {
+    match bits {
+        64 => v,
+        bits => Expr::and(v, Expr::Const((1 << bits) - 1)),
+    }
+}
+
+fn in_range(reg: Reg, first: Reg, last: Reg) -> Option<usize> {
+    let r = reg.to_u32().unwrap();
+    let first = first.to_u32().unwrap();
+
+    if r >= first && r <= last.to_u32().unwrap() {
+        Some((r - first) as usize)
+    } else {
+        None
+    }
+}
+
+/// Returns the state slot and width of a general purpose register
+fn index(reg: Reg) -> Option<(usize, u32)> {
+    match reg {
+        Reg::SP => return Some((SP, 64)),
+        Reg::WSP => return Some((SP, 32)),
+        _ => (),
+    }
+
+    if let Some(n) = in_range(reg, Reg::X0, Reg::X30) {
+        return Some((n, 64));
+    }
+
+    in_range(reg, Reg::W0, Reg::W30).map(|n| (n, 32))
+}
+
+fn x_reg(n: usize) -> Reg {
+    match n {
+        SP => Reg::SP,
+        n => Reg::from_u32(Reg::X0.to_u32().unwrap() + n as u32).unwrap(),
+    }
+}
+
+fn imm_raw(imm: Imm) -> u64 {
+    match imm {
+        Imm::Signed(i) => i as u64,
+        Imm::Unsigned(u) => u,
+    }
+}
+
+fn is_mem(o: &Operand) -> bool {
+    matches!(
+        o,
+        Operand::MemReg(_)
+            | Operand::MemOffset { .. }
+            | Operand::MemPreIdx { .. }
+            | Operand::MemPostIdxImm { .. }
+            | Operand::MemPostIdxReg(_)
+            | Operand::MemExt { .. }
+    )
+}
+
+/// Stores that write no general purpose register, besides base writeback
+fn is_store(op: Op) -> bool {
+    matches!(
+        op,
+        Op::STR
+            | Op::STRB
+            | Op::STRH
+            | Op::STUR
+            | Op::STURB
+            | Op::STURH
+            | Op::STP
+            | Op::STNP
+            | Op::STLR
+            | Op::STLRB
+            | Op::STLRH
+            | Op::STLUR
+            | Op::STLURB
+            | Op::STLURH
+            | Op::STTR
+            | Op::STTRB
+            | Op::STTRH
+            | Op::ST1
+            | Op::ST2
+            | Op::ST3
+            | Op::ST4
+            | Op::PRFM
+            | Op::PRFUM
+    )
+}
+
+/// Instructions whose first operand is a source
+fn is_branch_or_compare(op: Op) -> bool {
+    matches!(
+        op,
+        Op::CMP
+            | Op::CMN
+            | Op::TST
+            | Op::CCMP
+            | Op::CCMN
+            | Op::FCMP
+            | Op::FCMPE
+            | Op::FCCMP
+            | Op::FCCMPE
+            | Op::CBZ
+            | Op::CBNZ
+            | Op::TBZ
+            | Op::TBNZ
+            | Op::BR
+            | Op::BRAA
+            | Op::BRAAZ
+            | Op::BRAB
+            | Op::BRABZ
+            | Op::RET
+            | Op::RETAA
+            | Op::RETAB
+            | Op::MSR
+            | Op::SYS
+            | Op::DC
+            | Op::IC
+            | Op::AT
+            | Op::TLBI
+    )
+}
@@ -7,6 +7,7 @@ use bad64_sys::*;
 
 /// A system register
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(non_camel_case_types)]
 pub enum SysReg {
@@ -0,0 +1,147 @@
+use core::convert::TryInto;
+
+use crate::{DecodeError, Instruction, decode};
+
+/// How [`Walk`] treats addresses which are not a multiple of four
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Alignment {
+    /// Decode every four bytes from wherever a region starts
+    #[default]
+    Exact,
+    /// Skip ahead to the next aligned address, returning the skipped bytes as
+    /// a [`DecodeError::short`] error
+    Align,
+    /// Decode every four bytes, but step a single byte past any word which
+    /// fails to decode, returning just that byte, to find the instructions in
+    /// data of unknown alignment
+    Resync,
+}
+
+/// An offset into a region, its address, the bytes decoded and the result
+pub type Step<'a> = (usize, u64, &'a [u8], Result<Instruction, DecodeError>);
+
+/// A disassembling iterator over one or more regions of memory
+///
+/// Unlike [`crate::disasm`], each item carries its offset into its region
+/// and the raw bytes it was decoded from. A trailing partial word is
+/// returned as a [`DecodeError::short`] error, and iteration moves on to the
+/// next region. Gaps between regions show as jumps in the address.
+///
+/// # Examples
+/// ```
+/// use bad64::{Alignment, DecodeError, Op, Walk};
+///
+/// // a nop at 0x1000, then a dump starting two bytes into a ret
+/// let regions = [
+///     (0x1000, b"\x1f\x20\x03\xd5".as_slice()),
+///     (0x2002, b"\x5f\xd6\xc0\x03\x5f\xd6".as_slice()),
+/// ];
+///
+/// let walk = Walk::regions(&regions).alignment(Alignment::Align);
+/// let steps: Vec<_> = walk.map(|(off, addr, bytes, res)| (off, addr, bytes.len(), res)).collect();
+///
+/// assert_eq!(steps.len(), 3);
+/// assert_eq!(steps[0].3.as_ref().unwrap().op(), Op::NOP);
+/// assert_eq!(steps[1], (0, 0x2002, 2, Err(DecodeError::short(0x2002))));
+/// assert_eq!(steps[2].0, 2);
+/// assert_eq!(steps[2].3.as_ref().unwrap().op(), Op::RET);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Walk<'a> {
+    base: u64,
+    data: &'a [u8],
+    rest: &'a [(u64, &'a [u8])],
+    offset: usize,
+    alignment: Alignment,
+}
+
+impl<'a> Walk<'a> {
+    /// Disassemble a single region at `address`
+    pub fn new(code: &'a [u8], address: u64) -> Self {
+        Self {
+            base: address,
+            data: code,
+            rest: &[],
+            offset: 0,
+            alignment: Alignment::Exact,
+        }
+    }
+
+    /// Disassemble a list of `(base, bytes)` regions, in order
+    pub fn regions(regions: &'a [(u64, &'a [u8])]) -> Self {
+        let (first, rest) = match regions.split_first() {
+            Some((first, rest)) => (*first, rest),
+            None => ((0, [].as_slice()), regions),
+        };
+
+        Self {
+            base: first.0,
+            data: first.1,
+            rest,
+            offset: 0,
+            alignment: Alignment::Exact,
+        }
+    }
+
+    /// Set the alignment policy
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Start at an offset into the first region
+    pub fn start(mut self, offset: usize) -> Self {
+        self.offset = offset.min(self.data.len());
+        self
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Step<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset >= self.data.len() {
+            let ((base, data), rest) = self.rest.split_first()?;
+
+            self.base = *base;
+            self.data = data;
+            self.rest = rest;
+            self.offset = 0;
+        }
+
+        let offset = self.offset;
+        let address = self.base.wrapping_add(offset as u64);
+        let bytes = &self.data[offset..];
+
+        let misaligned = (address % 4) as usize;
+        if self.alignment == Alignment::Align && misaligned != 0 {
+            let len = (4 - misaligned).min(bytes.len());
+            self.offset += len;
+
+            return Some((
+                offset,
+                address,
+                &bytes[..len],
+                Err(DecodeError::short(address)),
+            ));
+        }
+
+        let Some(word) = bytes.get(..4) else {
+            self.offset = self.data.len();
+
+            return Some((offset, address, bytes, Err(DecodeError::short(address))));
+        };
+
+        let result = decode(u32::from_le_bytes(word.try_into().unwrap()), address);
+
+        if result.is_err() && self.alignment == Alignment::Resync {
+            self.offset += 1;
+
+            return Some((offset, address, &word[..1], result));
+        }
+
+        self.offset += 4;
+
+        Some((offset, address, word, result))
+    }
+}
@@ -0,0 +1,44 @@
+use bad64::Reg;
+use bad64::abi::Abi;
+
+#[test]
+fn abi_registers() {
+    for abi in [Abi::Aapcs64, Abi::Apple, Abi::Windows] {
+        assert_eq!(abi.argument_regs().len(), 8);
+        assert_eq!(abi.fp_argument_regs().last(), Some(&Reg::V7));
+        assert_eq!(abi.return_regs(), [Reg::X0, Reg::X1]);
+        assert_eq!(abi.fp_return_regs().len(), 4);
+        assert_eq!(abi.platform_reg(), Reg::X18);
+
+        // every register is either preserved or clobbered, never both
+        for reg in abi.callee_saved_regs() {
+            assert!(abi.preserves(*reg), "{:?} {}", abi, reg);
+            assert!(!abi.caller_saved_regs().contains(reg));
+        }
+
+        assert!(abi.caller_saved_regs().contains(&Reg::X30));
+        assert!(abi.caller_saved_regs().contains(&Reg::V8));
+        assert!(!abi.preserves(Reg::V8));
+        assert!(abi.preserves(Reg::S15));
+        assert!(!abi.preserves(Reg::S16));
+        assert!(abi.preserves(Reg::SP));
+        assert!(!abi.preserves(Reg::XZR));
+        assert!(!abi.preserves(Reg::X30));
+    }
+}
+
+#[test]
+fn abi_platform_register() {
+    assert!(!Abi::Aapcs64.is_reserved(Reg::X18));
+    assert!(Abi::Aapcs64.caller_saved_regs().contains(&Reg::X18));
+
+    for abi in [Abi::Apple, Abi::Windows] {
+        assert!(abi.is_reserved(Reg::W18));
+        assert!(abi.preserves(Reg::X18));
+        assert!(!abi.caller_saved_regs().contains(&Reg::X18));
+    }
+
+    assert!(Abi::Apple.variadic_args_on_stack());
+    assert!(!Abi::Windows.variadic_args_on_stack());
+    assert!(Abi::Windows.variadic_fp_in_general_regs());
+}
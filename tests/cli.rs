@@ -0,0 +1,82 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+fn bad64(args: &[&str]) -> (bool, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_bad64"))
+        .args(args)
+        .output()
+        .unwrap();
+
+    (out.status.success(), String::from_utf8(out.stdout).unwrap())
+}
+
+#[test]
+fn cli_hex() {
+    let (ok, out) = bad64(&[
+        "-x",
+        "1f2003d5 02000014 41414141",
+        "-b",
+        "0x1000",
+        "--bytes",
+    ]);
+    let lines: Vec<_> = out.lines().collect();
+
+    assert!(ok);
+    assert_eq!(lines[1], "    1000:  1f 20 03 d5  nop");
+    assert_eq!(lines[2], "    1004:  02 00 00 14  b 0x100c");
+    assert!(lines[3].ends_with("(bad: the encoding space is unallocated)"));
+
+    let (ok, out) = bad64(&["-x", "1f2003d5 c0035fd6", "-s", "4", "-f", "json"]);
+    let record: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+    assert!(ok);
+    assert_eq!(record["address"], 4);
+    assert_eq!(record["mnemonic"], "ret");
+}
+
+#[test]
+fn cli_elf() {
+    let (ok, out) = bad64(&["tests/elf/funcs.o", "-S", ".text"]);
+    let lines: Vec<_> = out.lines().collect();
+
+    assert!(ok);
+    assert!(lines.contains(&"0000000000000008 <load_const>:"));
+    assert!(lines.contains(&"       8:  ldr x0, 0x10 <load_const+0x8>"));
+    assert!(lines.contains(&"      10:  .word 0x55667788"));
+    assert!(!out.contains("other"));
+
+    // data is a record in JSON too, so both cover the same addresses
+    let (_, out) = bad64(&["tests/elf/funcs.o", "-S", ".text", "-f", "json"]);
+    let records: Vec<serde_json::Value> = out
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let word = records.iter().find(|r| r["address"] == 0x10).unwrap();
+    assert_eq!(word["data"], true);
+    assert_eq!(word["bytes"], "88776655");
+    assert_eq!(
+        records.len(),
+        lines.iter().filter(|l| l.contains(":  ")).count()
+    );
+
+    let (_, out) = bad64(&["tests/elf/funcs.o", "--no-symbols"]);
+    assert!(!out.contains("load_const"));
+}
+
+#[test]
+fn cli_macho() {
+    let (ok, out) = bad64(&["tests/macho/funcs.o", "-S", "__TEXT,__text"]);
+
+    assert!(ok);
+    assert!(out.contains("<_jump>:"));
+}
+
+#[test]
+fn cli_errors() {
+    assert!(!bad64(&[]).0);
+    assert!(!bad64(&["-x", "zz"]).0);
+    assert!(!bad64(&["-x", "1f2003d5", "-S", ".text"]).0);
+    assert!(!bad64(&["tests/elf/funcs.o", "-S", ".nope"]).0);
+    assert!(!bad64(&["/nonexistent"]).0);
+}
@@ -0,0 +1,18 @@
+//! Fixture helpers shared by the integration tests
+#![allow(dead_code)]
+
+use bad64::{Instruction, decode};
+
+/// Decodes instruction words at 0x1000 onwards
+pub fn decode_all(words: &[u32]) -> Vec<Instruction> {
+    words
+        .iter()
+        .enumerate()
+        .map(|(n, w)| decode(*w, 0x1000 + n as u64 * 4).unwrap())
+        .collect()
+}
+
+/// Returns instruction words as little-endian bytes
+pub fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "lift")]
+
+mod common;
+
+use bad64::dataflow::*;
+use bad64::*;
+use common::decode_all;
+
+// sums the bytes of a nul terminated string, then passes the sum to a call
+const SUM: [u32; 7] = [
+    0xd2800001, // mov x1, #0
+    0x38401402, // ldrb w2, [x0], #1
+    0x8b020021, // add x1, x1, x2
+    0x35ffffc2, // cbnz w2, 0x1004
+    0xaa0103e0, // mov x0, x1
+    0x940003fb, // bl 0x2000
+    0xd65f03c0, // ret
+];
+
+#[test]
+fn dataflow_cfg() {
+    let ins = decode_all(&SUM);
+    let cfg = Cfg::build(&ins);
+
+    let blocks: Vec<_> = cfg
+        .blocks()
+        .iter()
+        .map(|b| (b.address(), b.instructions.len(), b.successors.clone()))
+        .collect();
+
+    assert_eq!(
+        blocks,
+        [
+            (Some(0x1000), 1, vec![1]),
+            (Some(0x1004), 3, vec![1, 2]),
+            (Some(0x1010), 3, vec![]),
+        ]
+    );
+    assert_eq!(cfg.block_at(0x1008), Some(1));
+    assert_eq!(cfg.block_at(0x2000), None);
+}
+
+#[test]
+fn dataflow_liveness() {
+    let ins = decode_all(&SUM);
+    let cfg = Cfg::build(&ins);
+    let live = Liveness::analyze(&cfg);
+
+    assert!(live.live_in(0).contains(Reg::X0));
+    assert!(!live.live_in(0).contains(Reg::X1));
+    assert!(!live.live_in(0).contains(Reg::XZR));
+    assert!(live.live_in(1).contains(Reg::W1));
+    assert!(!live.live_in(1).contains(Reg::X2));
+    assert_eq!(live.live_out(1), live.live_in(1) | live.live_in(2));
+
+    // the call reads the argument registers and clobbers the caller-saved ones
+    let before_call = live.live_at(&cfg, 0x1014).unwrap();
+    assert!(before_call.contains(Reg::X0));
+    assert!(before_call.contains(Reg::X19));
+    assert!(before_call.contains(Reg::D8));
+    assert!(!before_call.contains(Reg::X9));
+    assert!(!before_call.contains(Reg::X30));
+
+    let before_ret = live.live_at(&cfg, 0x1018).unwrap();
+    assert!(before_ret.contains(Reg::X30));
+    assert!(before_ret.contains(Reg::SP));
+    assert!(!before_ret.contains(Reg::X16));
+
+    let free = !live.live_at(&cfg, 0x1008).unwrap();
+    assert!(free.contains(Reg::X9));
+    assert!(!free.contains(Reg::X2));
+
+    assert_eq!(live.live_at(&cfg, 0x2000), None);
+
+    // a branch out of the function may read anything
+    let ins = decode_all(&[
+        0xd2800029, // mov x9, #1
+        0xb400ffe0, // cbz x0, 0x2ffc
+        0xd2800049, // mov x9, #2
+        0xd65f03c0, // ret
+    ]);
+    let cfg = Cfg::build(&ins);
+    let live = Liveness::analyze(&cfg);
+
+    assert!(cfg.blocks()[0].exits);
+    assert!(!cfg.blocks()[1].exits);
+    assert_eq!(live.live_out(0), RegSet::ALL);
+    assert!(live.live_at(&cfg, 0x1004).unwrap().contains(Reg::X9));
+}
+
+#[test]
+fn dataflow_def_use() {
+    let ins = decode_all(&SUM);
+    let cfg = Cfg::build(&ins);
+    let chains = DefUse::analyze(&cfg);
+
+    assert_eq!(chains.reaching(0x1008, Reg::X2), [Def::At(0x1004)]);
+    assert_eq!(
+        chains.reaching(0x1008, Reg::X1),
+        [Def::At(0x1000), Def::At(0x1008)]
+    );
+    assert_eq!(
+        chains.reaching(0x1004, Reg::X0),
+        [Def::Entry, Def::At(0x1004)]
+    );
+    assert_eq!(chains.reaching(0x1000, Reg::X0), []);
+
+    // the call may read x1 and x2 as arguments
+    assert_eq!(
+        chains.uses(Def::At(0x1004), Reg::W2),
+        [0x1008, 0x100c, 0x1014]
+    );
+    assert_eq!(chains.uses(Def::At(0x1010), Reg::X0), [0x1014]);
+    assert_eq!(
+        chains.uses(Def::At(0x1008), Reg::X1),
+        [0x1008, 0x1010, 0x1014]
+    );
+}
+
+#[test]
+fn dataflow_access() {
+    // mov w3, w4
+    let a = Access::of(&decode(0x2a0403e3, 0x1000).unwrap());
+    assert_eq!(a.uses, [Reg::X4].into_iter().collect());
+    assert_eq!(a.defs, [Reg::X3].into_iter().collect());
+
+    // mov v0.s[1], w5
+    let a = Access::of(&decode(0x4e0c1ca0, 0x1000).unwrap());
+    assert_eq!(a.uses.to_string(), "{x5, v0}");
+    assert_eq!(a.defs.to_string(), "{v0}");
+    assert!(a.exact);
+
+    // aese v0.16b, v1.16b
+    let a = Access::of(&decode(0x4e284820, 0x1000).unwrap());
+    assert_eq!(a.uses.to_string(), "{v0, v1}");
+    assert_eq!(a.defs.to_string(), "{v0}");
+    assert!(!a.exact);
+}
+
+#[test]
+fn dataflow_abi() {
+    let ins = decode_all(&SUM);
+
+    let cfg = Cfg::build(&ins);
+    let live = Liveness::analyze(&cfg);
+    assert!(!live.live_at(&cfg, 0x1014).unwrap().contains(Reg::X18));
+
+    // x18 survives calls and must be preserved for the caller
+    let cfg = Cfg::build(&ins).with_abi(abi::Abi::Apple);
+    let live = Liveness::analyze(&cfg);
+    assert!(live.live_at(&cfg, 0x1014).unwrap().contains(Reg::X18));
+    assert!(live.live_in(0).contains(Reg::X18));
+}
@@ -26,8 +26,13 @@ fn decode_iter_nop() {
 fn decode_iter_err() {
     let mut ii = disasm(&[0x41_u8; 8], 0);
 
-    assert_eq!(ii.next().unwrap(), Err(DecodeError::Unallocated(0)));
-    assert_eq!(ii.next().unwrap(), Err(DecodeError::Unallocated(4)));
+    let e0 = ii.next().unwrap().unwrap_err();
+    let e1 = ii.next().unwrap().unwrap_err();
+
+    assert_eq!(e0.kind(), DecodeErrorKind::Unallocated);
+    assert_eq!(e0.address(), 0);
+    assert_eq!(e1.address(), 4);
+    assert_eq!(e1.opcode(), Some(0x41414141));
     assert_eq!(ii.next(), None);
 }
 
@@ -35,10 +40,24 @@ fn decode_iter_err() {
 fn decode_iter_short() {
     let mut ii = disasm(&[0x41_u8; 3], 0);
 
-    assert_eq!(ii.next().unwrap(), Err(DecodeError::Short(0)));
+    assert_eq!(ii.next().unwrap(), Err(DecodeError::short(0)));
     assert_eq!(ii.next(), None);
 }
 
+#[test]
+fn decode_iter_be() {
+    // add x0, x1, #0x41; ret
+    let le: Vec<_> = disasm(b"\x20\x04\x01\x91\xc0\x03\x5f\xd6", 0x1000).collect();
+    let be: Vec<_> = disasm_be(b"\x91\x01\x04\x20\xd6\x5f\x03\xc0", 0x1000).collect();
+
+    assert_eq!(le, be);
+    assert_eq!(be[1].as_ref().unwrap().op(), Op::RET);
+    assert_eq!(be[1].as_ref().unwrap().address(), 0x1004);
+
+    let mut ii = disasm_be(&[0xd5, 0x03, 0x20], 0);
+    assert_eq!(ii.next().unwrap(), Err(DecodeError::short(0)));
+}
+
 #[test]
 fn decode_add() {
     // add x0, x1, #0x41
@@ -88,5 +107,56 @@ fn system_reg() {
 
 #[test]
 fn decode_failure() {
-    assert_eq!(decode(0x41414141, 0), Err(DecodeError::Unallocated(0)));
+    let e = decode(0x41414141, 0x1000).unwrap_err();
+
+    assert_eq!(e.kind(), DecodeErrorKind::Unallocated);
+    assert_eq!(e.address(), 0x1000);
+    assert_eq!(e.opcode(), Some(0x41414141));
+    assert_eq!(e.reason(), "the encoding space is unallocated");
+    assert_eq!(
+        e.to_string(),
+        "Unallocated: 0x1000: 0x41414141 (the encoding space is unallocated)"
+    );
+
+    assert_eq!(e.encoding(), None);
+
+    // an SVE encoding, which the decoder matched before rejecting
+    let e = decode(0x04140414, 0).unwrap_err();
+    assert_eq!(e.kind(), DecodeErrorKind::Undefined);
+    assert!(e.encoding().is_some());
+
+    assert_eq!(DecodeError::short(4).opcode(), None);
+    assert_eq!(DecodeError::short(4).kind(), DecodeErrorKind::Short);
+}
+
+#[test]
+fn flags_detail() {
+    // ccmp x0, #0x1, #0x4, eq
+    let ins = decode(0xfa410804, 0).unwrap();
+    assert_eq!(ins.condition(), Some(Condition::EQ));
+    assert_eq!(ins.flags_read(), Nzcv::Z);
+    assert_eq!(ins.flags_written(), Nzcv::ALL);
+
+    // sbc x0, x1, x2
+    let ins = decode(0xda020020, 0).unwrap();
+    assert_eq!(ins.flags_read(), Nzcv::C);
+    assert_eq!(ins.flags_written(), Nzcv::NONE);
+
+    // fcmp s0, s1
+    let ins = decode(0x1e212000, 0).unwrap();
+    assert_eq!(ins.flags_written(), Nzcv::ALL);
+
+    // cfinv
+    let ins = decode(0xd500401f, 0).unwrap();
+    assert_eq!(ins.flags_read(), Nzcv::C);
+    assert_eq!(ins.flags_written(), Nzcv::C);
+
+    // axflag
+    let ins = decode(0xd500405f, 0).unwrap();
+    assert_eq!(ins.flags_written(), Nzcv::ALL);
+
+    // msr nzcv, x0
+    let ins = decode(0xd51b4200, 0).unwrap();
+    assert_eq!(ins.flags_read(), Nzcv::NONE);
+    assert_eq!(ins.flags_written(), Nzcv::ALL);
 }
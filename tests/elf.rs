@@ -0,0 +1,113 @@
+#![cfg(feature = "elf")]
+
+use bad64::Op;
+use bad64::elf::*;
+
+// built from tests/elf/funcs.s
+static FUNCS: &[u8] = include_bytes!("elf/funcs.o");
+static FUNCS_BE: &[u8] = include_bytes!("elf/funcs_be.o");
+
+#[test]
+fn elf_functions() {
+    for (buf, endian) in [(FUNCS, Endian::Little), (FUNCS_BE, Endian::Big)] {
+        let elf = Elf::parse(buf).unwrap();
+        assert_eq!(elf.endian(), endian);
+
+        let funcs: Vec<_> = elf
+            .functions()
+            .iter()
+            .map(|f| (f.name, f.address, f.data.len()))
+            .collect();
+        assert_eq!(
+            funcs,
+            [
+                ("add_one", 0, 8),
+                ("load_const", 8, 16),
+                ("tail", 0x18, 4),
+                ("other", 0, 8)
+            ]
+        );
+
+        let load_const = &elf.functions()[1];
+        assert_eq!(load_const.literals().len(), 1);
+        assert_eq!(load_const.literals()[0], 0x10..0x18);
+
+        let ops: Vec<_> = load_const.disasm().map(|i| i.unwrap().op()).collect();
+        assert_eq!(ops, [Op::LDR, Op::RET]);
+
+        let value = elf.symbols().iter().find(|s| s.name == "value").unwrap();
+        assert_eq!(value.kind, SymbolKind::Object);
+        assert_eq!(value.size, 8);
+    }
+}
+
+#[test]
+fn elf_sections() {
+    let elf = Elf::parse(FUNCS).unwrap();
+
+    let names: Vec<_> = elf.executable_sections().map(|s| s.name).collect();
+    assert_eq!(names, [".text", ".text.other"]);
+
+    let text = elf.section_by_name(".text").unwrap();
+    let ins: Vec<_> = elf
+        .disasm(text)
+        .map(|i| {
+            let i = i.unwrap();
+            (i.address(), i.op())
+        })
+        .collect();
+    assert_eq!(
+        ins,
+        [
+            (0x0, Op::ADD),
+            (0x4, Op::RET),
+            (0x8, Op::LDR),
+            (0xc, Op::RET),
+            (0x18, Op::B)
+        ]
+    );
+
+    let data = elf.section_by_name(".data").unwrap();
+    assert!(!data.is_executable());
+    assert_eq!(data.data, 1u64.to_le_bytes());
+}
+
+#[test]
+fn elf_errors() {
+    assert_eq!(Elf::parse(b"MZ\x90\x00"), Err(ElfError::BadMagic));
+    assert_eq!(Elf::parse(&FUNCS[..40]), Err(ElfError::Truncated));
+
+    let mut x86 = FUNCS.to_vec();
+    x86[18] = 62;
+    assert_eq!(Elf::parse(&x86), Err(ElfError::BadMachine(62)));
+
+    let mut elf32 = FUNCS.to_vec();
+    elf32[4] = 1;
+    assert_eq!(Elf::parse(&elf32), Err(ElfError::BadClass));
+
+    // .text running past the end of the address space
+    let mut wrapped = FUNCS.to_vec();
+    let shoff = u64::from_le_bytes(FUNCS[40..48].try_into().unwrap()) as usize;
+    let addr = shoff + 2 * 64 + 16;
+    wrapped[addr..addr + 8].copy_from_slice(&0xffff_ffff_ffff_fffcu64.to_le_bytes());
+    assert_eq!(Elf::parse(&wrapped), Err(ElfError::Truncated));
+
+    // symbols in sections which do not exist
+    let mut bad_shndx = FUNCS.to_vec();
+    let u64_at = |off: usize| u64::from_le_bytes(FUNCS[off..off + 8].try_into().unwrap());
+    let shnum = u16::from_le_bytes([FUNCS[60], FUNCS[61]]) as usize;
+    for n in 0..shnum {
+        let sh = shoff + n * 64;
+        if FUNCS[sh + 4] != 2 {
+            continue;
+        }
+
+        let (offset, size) = (u64_at(sh + 24) as usize, u64_at(sh + 32) as usize);
+        for sym in (offset..offset + size).step_by(24) {
+            bad_shndx[sym + 6..sym + 8].copy_from_slice(&0x100u16.to_le_bytes());
+        }
+    }
+    let elf = Elf::parse(&bad_shndx).unwrap();
+    assert!(elf.symbols().iter().all(|s| s.section.is_none()));
+    assert!(elf.functions().is_empty());
+}
@@ -0,0 +1,35 @@
+// llvm-mc -triple=aarch64 -filetype=obj funcs.s -o funcs.o
+// llvm-mc -triple=aarch64_be -filetype=obj funcs.s -o funcs_be.o
+    .text
+    .globl add_one
+    .type add_one, %function
+add_one:
+    add x0, x0, #1
+    ret
+    .size add_one, .-add_one
+
+    .globl load_const
+    .type load_const, %function
+load_const:
+    ldr x0, 1f
+    ret
+1:  .quad 0x1122334455667788
+    .size load_const, .-load_const
+
+    .type tail, %function
+tail:
+    b add_one
+
+    .section .text.other,"ax",%progbits
+    .globl other
+    .type other, %function
+other:
+    nop
+    ret
+    .size other, .-other
+
+    .data
+    .type value, %object
+value:
+    .quad 1
+    .size value, 8
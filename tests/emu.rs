@@ -0,0 +1,194 @@
+#![cfg(feature = "emu")]
+
+use bad64::emu::*;
+use bad64::*;
+
+const CODE: u64 = 0x1000;
+const DATA: u64 = 0x2000;
+const STOP: u64 = 0xdead_0000;
+
+fn emulator(code: &[u32]) -> Emulator<FlatMemory<Vec<u8>>> {
+    let mut mem = FlatMemory::new(CODE, vec![0; 0x2000]);
+
+    for (n, word) in code.iter().enumerate() {
+        assert!(mem.write(CODE + n as u64 * 4, &word.to_le_bytes()));
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.pc = CODE;
+    cpu.sp = DATA + 0x1000;
+    cpu.x[30] = STOP;
+
+    Emulator::new(cpu, mem)
+}
+
+#[test]
+fn emu_xor_loop() {
+    let mut emu = emulator(&[
+        0x38401403, // ldrb w3, [x0], #1
+        0x4a040063, // eor w3, w3, w4
+        0x38001423, // strb w3, [x1], #1
+        0xf1000442, // subs x2, x2, #1
+        0x54ffff81, // b.ne 0x1000
+        0xd65f03c0, // ret
+    ]);
+
+    let plain = b"firmware";
+    let cipher: Vec<u8> = plain.iter().map(|b| b ^ 0xa5).collect();
+    assert!(emu.mem.write(DATA, &cipher));
+
+    emu.cpu.x[0] = DATA;
+    emu.cpu.x[1] = DATA + 0x100;
+    emu.cpu.x[2] = plain.len() as u64;
+    emu.cpu.x[4] = 0xa5;
+
+    assert_eq!(emu.run(STOP, 1000), Ok(plain.len() * 5 + 1));
+
+    let mut out = [0; 8];
+    assert!(emu.mem.read(DATA + 0x100, &mut out));
+    assert_eq!(&out, plain);
+    assert_eq!(emu.cpu.x[2], 0);
+    assert_eq!(emu.cpu.nzcv, Nzcv::Z | Nzcv::C);
+}
+
+#[test]
+fn emu_carry_chain() {
+    let mut emu = emulator(&[
+        0xab020000, // adds x0, x0, x2
+        0x9a030021, // adc x1, x1, x3
+        0xf100143f, // cmp x1, #5
+        0x54000041, // b.ne 0x1014
+        0xd2800024, // mov x4, #1
+        0xd65f03c0, // ret
+    ]);
+
+    emu.cpu.x[0] = u64::MAX;
+    emu.cpu.x[1] = 2;
+    emu.cpu.x[2] = 2;
+    emu.cpu.x[3] = 2;
+
+    assert_eq!(emu.run(STOP, 100), Ok(6));
+    assert_eq!(emu.cpu.x[0], 1);
+    assert_eq!(emu.cpu.x[1], 5);
+    assert_eq!(emu.cpu.x[4], 1);
+}
+
+#[test]
+fn emu_integer() {
+    let mut emu = emulator(&[
+        0xd2a24680, // mov x0, #0x12340000
+        0xf28acf00, // movk x0, #0x5678
+        0x93c02001, // ror x1, x0, #8
+        0x5ac00802, // rev w2, w0
+        0xdac01003, // clz x3, x0
+        0x9ac60805, // udiv x5, x0, x6
+        0x9b000407, // madd x7, x0, x0, x1
+    ]);
+
+    emu.cpu.x[6] = 0x10;
+
+    assert_eq!(emu.run(STOP, 7), Ok(7));
+    assert_eq!(emu.cpu.x[0], 0x1234_5678);
+    assert_eq!(emu.cpu.x[1], 0x7800_0000_0012_3456);
+    assert_eq!(emu.cpu.x[2], 0x7856_3412);
+    assert_eq!(emu.cpu.x[3], 35);
+    assert_eq!(emu.cpu.x[5], 0x0123_4567);
+    assert_eq!(
+        emu.cpu.x[7],
+        0x1234_5678u64
+            .wrapping_mul(0x1234_5678)
+            .wrapping_add(0x7800_0000_0012_3456)
+    );
+    assert_eq!(emu.cpu.pc, CODE + 7 * 4);
+}
+
+#[test]
+fn emu_simd() {
+    let mut emu = emulator(&[
+        0x4c407000, // ld1 {v0.16b}, [x0]
+        0x4c407021, // ld1 {v1.16b}, [x1]
+        0x6e211c00, // eor v0.16b, v0.16b, v1.16b
+        0x4c007040, // st1 {v0.16b}, [x2]
+        0xd65f03c0, // ret
+    ]);
+
+    let a: Vec<u8> = (0..16).collect();
+    let b = [0x5a; 16];
+    assert!(emu.mem.write(DATA, &a));
+    assert!(emu.mem.write(DATA + 0x10, &b));
+
+    emu.cpu.x[0] = DATA;
+    emu.cpu.x[1] = DATA + 0x10;
+    emu.cpu.x[2] = DATA + 0x20;
+
+    assert_eq!(emu.run(STOP, 100), Ok(5));
+
+    let mut out = [0; 16];
+    assert!(emu.mem.read(DATA + 0x20, &mut out));
+    for (n, b) in out.iter().enumerate() {
+        assert_eq!(*b, n as u8 ^ 0x5a);
+    }
+    assert_eq!(emu.cpu.reg(Reg::Q0), Some(u128::from_le_bytes(out)));
+}
+
+#[test]
+fn emu_float() {
+    let mut emu = emulator(&[
+        0x9e620000, // scvtf d0, x0
+        0x1e6c1001, // fmov d1, #0.5
+        0x1e612800, // fadd d0, d0, d1
+        0x1e600800, // fmul d0, d0, d0
+        0x9e780001, // fcvtzs x1, d0
+        0xd65f03c0, // ret
+    ]);
+
+    emu.cpu.x[0] = -3i64 as u64;
+
+    assert_eq!(emu.run(STOP, 100), Ok(6));
+    assert_eq!(emu.cpu.v[0], 6.25f64.to_bits() as u128);
+    assert_eq!(emu.cpu.x[1], 6);
+}
+
+#[test]
+fn emu_fp_control() {
+    let mut emu = emulator(&[
+        0xd51b4401, // msr fpcr, x1
+        0xd53b4400, // mrs x0, fpcr
+    ]);
+
+    emu.cpu.x[1] = 0x0040_0000;
+
+    assert_eq!(emu.run(STOP, 2), Ok(2));
+    assert_eq!(emu.cpu.fpcr, 0x0040_0000);
+    assert_eq!(emu.cpu.x[0], 0x0040_0000);
+}
+
+#[test]
+fn emu_errors() {
+    let mut emu = emulator(&[
+        0xd4000001, // svc #0
+        0xf8627820, // ldr x0, [x1, x2, lsl #3]
+        0x4e284820, // aese v0.16b, v1.16b
+    ]);
+
+    assert_eq!(
+        emu.step(),
+        Err(EmuError::Trap {
+            op: Op::SVC,
+            imm: 0
+        })
+    );
+    assert_eq!(emu.cpu.pc, CODE + 4);
+
+    emu.cpu.x[1] = 0x8000;
+    emu.cpu.x[2] = 1;
+    assert_eq!(emu.step(), Err(EmuError::Read(0x8008)));
+    assert_eq!(emu.cpu.pc, CODE + 4);
+
+    emu.cpu.pc = CODE + 8;
+    assert_eq!(emu.step(), Err(EmuError::Unimplemented(Op::AESE)));
+    assert_eq!(emu.cpu.pc, CODE + 8);
+
+    emu.cpu.pc = 0x10_0000;
+    assert_eq!(emu.step(), Err(EmuError::Fetch(0x10_0000)));
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "alloc")]
+
+mod common;
+
+use bad64::frame::Frame;
+use bad64::*;
+use common::decode_all;
+
+#[test]
+fn frame_without_fp() {
+    let ins = decode_all(&[
+        0xd100c3ff, // sub sp, sp, #0x30
+        0xa90253f3, // stp x19, x20, [sp, #0x20]
+        0xf90007e0, // str x0, [sp, #0x8]
+        0xb9400fe1, // ldr w1, [sp, #0xc]
+        0xa94253f3, // ldp x19, x20, [sp, #0x20]
+        0x9100c3ff, // add sp, sp, #0x30
+        0xd65f03c0, // ret
+    ]);
+
+    let frame = Frame::analyze(&ins);
+
+    assert_eq!(frame.size(), 0x30);
+    assert!(!frame.has_frame_pointer());
+
+    let spills: Vec<_> = frame.spills().iter().map(|s| (s.reg, s.offset)).collect();
+    assert_eq!(spills, [(Reg::X19, -0x10), (Reg::X20, -0x8)]);
+
+    assert_eq!(frame.vars().len(), 2);
+    assert_eq!(frame.vars()[0].offset, -0x28);
+    assert_eq!(frame.vars()[0].size, 8);
+    assert!(frame.vars()[0].written);
+    assert_eq!(frame.vars()[1].offset, -0x24);
+    assert_eq!(frame.vars()[1].size, 4);
+    assert!(frame.vars()[1].read);
+    assert_eq!(frame.var_at(-0x26), Some(&frame.vars()[0]));
+}
+
+#[test]
+fn frame_sized_by_register() {
+    let ins = decode_all(&[
+        0xa9bf7bfd, // stp fp, lr, [sp, #-0x10]!
+        0x910003fd, // mov fp, sp
+        0xd2820010, // mov x16, #0x1000
+        0xcb3063ff, // sub sp, sp, x16
+        0x910023e0, // add x0, sp, #0x8
+    ]);
+
+    let frame = Frame::analyze(&ins);
+
+    assert_eq!(frame.size(), 0x1010);
+    assert_eq!(frame.frame_pointer(), Some(-0x10));
+
+    let var = frame.vars()[0];
+    assert_eq!(var.offset, -0x1008);
+    assert!(var.address_taken);
+    assert_eq!(var.to_string(), "var_1008");
+
+    // a write to w16 replaces all of x16
+    let sized = |words: &[u32]| {
+        let mut ins = vec![0xa9bf7bfd, 0xd2820010];
+        ins.extend_from_slice(words);
+        ins.push(0xcb3063ff);
+        Frame::analyze(&decode_all(&ins)).size()
+    };
+    assert_eq!(sized(&[0x52800410]), 0x30); // mov w16, #0x20
+    assert_eq!(sized(&[0x12800010]), 0x1_0000_000f); // mov w16, #-1
+    assert_eq!(sized(&[0x11000410]), 0x10); // add w16, w0, #1
+
+    // an amount which overflows the stack pointer leaves it unknown
+    assert_eq!(sized(&[0xd2f00010]), 0x10); // mov x16, #0x8000000000000000
+}
+
+#[test]
+fn frame_early_return() {
+    let ins = decode_all(&[
+        0xd100c3ff, // sub sp, sp, #0x30
+        0xb4000040, // cbz x0, 0x100c
+        0x9100c3ff, // add sp, sp, #0x30
+        0xd65f03c0, // ret
+        0xf90007e0, // str x0, [sp, #0x8]
+    ]);
+
+    let frame = Frame::analyze(&ins);
+
+    assert_eq!(frame.size(), 0x30);
+    assert_eq!(frame.vars()[0].offset, -0x28);
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "lift")]
+
+mod common;
+
+use bad64::Reg;
+use bad64::gadget::*;
+use common::bytes;
+
+const CODE: [u32; 12] = [
+    0xd503245f, // bti c
+    0xf9400410, // ldr x16, [x0, #8]
+    0xd61f0200, // br x16
+    0x14000002, // b 0x14
+    0xd10043ff, // sub sp, sp, #0x10
+    0xd63f0020, // blr x1
+    0x9100005f, // mov sp, x2
+    0xf84087fe, // ldr lr, [sp], #8
+    0xd65f0bff, // retaa
+    0xd71f0864, // braa x3, x4
+    0xffffffff, // unallocated
+    0xd65f03c0, // ret
+];
+
+#[test]
+fn gadget_find() {
+    let code = bytes(&CODE);
+    let gadgets = find(&code, 0, 2);
+
+    let found: Vec<_> = gadgets
+        .iter()
+        .map(|g| (g.address, g.instructions.len(), g.kind, g.authenticated))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (0x08, 1, GadgetKind::Jump, false),
+            (0x04, 2, GadgetKind::Jump, false),
+            (0x00, 3, GadgetKind::Jump, false),
+            (0x14, 1, GadgetKind::Call, false),
+            (0x10, 2, GadgetKind::Call, false),
+            (0x20, 1, GadgetKind::Return, true),
+            (0x1c, 2, GadgetKind::Return, true),
+            (0x18, 3, GadgetKind::Return, true),
+            (0x24, 1, GadgetKind::Jump, true),
+            (0x2c, 1, GadgetKind::Return, false),
+        ]
+    );
+
+    // fewer instructions before the branch
+    assert_eq!(find(&code, 0, 1).len(), 8);
+    assert_eq!(find(&code, 0, 0).len(), 5);
+    assert_eq!(find(&code[..6], 0, 4).len(), 0);
+
+    // addresses wrap around the top of the address space
+    let top = find(&code, u64::MAX - 7, 2);
+    assert_eq!(top.len(), gadgets.len());
+    assert_eq!(top[0].address, 0);
+}
+
+#[test]
+fn gadget_effects() {
+    let code = bytes(&CODE);
+    let gadgets = find(&code, 0x1000, 2);
+
+    let load = &gadgets[2];
+    assert_eq!(load.to_string(), "bti c; ldr x16, [x0, #0x8]; br x16");
+    assert!(load.landing_pad);
+    assert!(!gadgets[1].landing_pad);
+    assert_eq!(load.target, Reg::X16);
+    assert_eq!(load.reads.to_string(), "{x0}");
+    assert_eq!(load.writes.to_string(), "{x16}");
+    assert_eq!(load.stack, Some(0));
+
+    let call = &gadgets[4];
+    assert_eq!(call.target, Reg::X1);
+    assert_eq!(call.reads.to_string(), "{x1, sp}");
+    assert_eq!(call.stack, Some(-16));
+
+    // sp is replaced, so moves by an unknown amount
+    let pivot = &gadgets[7];
+    assert_eq!(pivot.target, Reg::X30);
+    assert_eq!(pivot.reads.to_string(), "{x2}");
+    assert_eq!(pivot.writes.to_string(), "{lr, sp}");
+    assert_eq!(pivot.stack, None);
+    assert_eq!(gadgets[6].stack, Some(8));
+
+    let braa = &gadgets[8];
+    assert_eq!(braa.target, Reg::X3);
+    assert_eq!(braa.reads.to_string(), "{x3, x4}");
+    assert!(braa.writes.is_empty());
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "alloc")]
+
+use bad64::decode;
+use bad64::hardening::*;
+
+fn audit(words: &[u32]) -> Audit {
+    let ins: Vec<_> = words
+        .iter()
+        .enumerate()
+        .map(|(n, w)| decode(*w, n as u64 * 4).unwrap())
+        .collect();
+
+    Audit::of(&ins)
+}
+
+fn findings(audit: &Audit) -> Vec<(u64, Issue)> {
+    audit
+        .findings
+        .iter()
+        .map(|f| (f.address, f.issue))
+        .collect()
+}
+
+#[test]
+fn hardening_unprotected() {
+    let a = audit(&[
+        0xa9bf7bfd, // stp fp, lr, [sp, #-16]!
+        0xd63f0100, // blr x8
+        0xd61f0200, // br x16
+        0xa8c17bfd, // ldp fp, lr, [sp], #16
+        0xd65f03c0, // ret
+    ]);
+
+    assert_eq!(a.signed, None);
+    assert_eq!(a.landing_pad, None);
+    assert!(!a.is_hardened());
+    assert_eq!(
+        findings(&a),
+        [
+            (0x0, Issue::MissingLandingPad),
+            (0x0, Issue::UnsignedReturnAddress),
+            (0x4, Issue::UnauthenticatedCall),
+            (0x8, Issue::UnauthenticatedJump),
+        ]
+    );
+
+    // a leaf function need not sign lr
+    let leaf = audit(&[
+        0xd503245f, // bti c
+        0xd2800020, // mov x0, #1
+        0xd65f03c0, // ret
+    ]);
+    assert_eq!(leaf.landing_pad, Some(Bti::C));
+    assert!(leaf.is_hardened());
+
+    assert_eq!(audit(&[]), Audit::default());
+}
+
+#[test]
+fn hardening_returns() {
+    let a = audit(&[
+        0xd503233f, // paciasp
+        0xa9bf7bfd, // stp fp, lr, [sp, #-16]!
+        0xd73f0909, // blraa x8, x9
+        0xb4000060, // cbz x0, 0x18
+        0xa8c17bfd, // ldp fp, lr, [sp], #16
+        0xd50323bf, // autiasp
+        0xd65f03c0, // ret
+        0xa8c17bfd, // ldp fp, lr, [sp], #16
+        0xd65f03c0, // ret, with lr still signed
+    ]);
+
+    assert_eq!(a.signed, Some(Key::A));
+    assert_eq!(a.landing_pad, Some(Bti::C));
+    assert_eq!(findings(&a), [(0x20, Issue::UnauthenticatedReturn)]);
+
+    let b = audit(&[
+        0xd503245f, // bti c
+        0xd503237f, // pacibsp
+        0xa9bf7bfd, // stp fp, lr, [sp, #-16]!
+        0xa8c17bfd, // ldp fp, lr, [sp], #16
+        0xd50323bf, // autiasp
+        0xd65f03c0, // ret
+        0xd65f0fff, // retab
+    ]);
+
+    assert_eq!(b.signed, Some(Key::B));
+    assert_eq!(findings(&b), [(0x10, Issue::KeyMismatch)]);
+}
+
+#[test]
+fn hardening_landing_pads() {
+    let entry = |bti| {
+        let a = audit(&[bti, 0xd65f0fff]);
+        (a.landing_pad, findings(&a))
+    };
+
+    assert_eq!(entry(0xd50324df), (Some(Bti::JC), vec![]));
+    assert_eq!(entry(0xd503237f), (Some(Bti::C), vec![]));
+    assert_eq!(
+        entry(0xd503249f),
+        (Some(Bti::J), vec![(0, Issue::WrongLandingPad)])
+    );
+    assert_eq!(
+        entry(0xd503241f),
+        (Some(Bti::None), vec![(0, Issue::WrongLandingPad)])
+    );
+    assert!(!Bti::J.accepts_calls());
+
+    // inside the function, only jumps land
+    let a = audit(&[
+        0xd503245f, // bti c
+        0xb4000040, // cbz x0, 0xc
+        0xd503249f, // bti j
+        0xd503245f, // bti c
+        0xd50324df, // bti jc
+        0xd65f03c0, // ret
+    ]);
+    assert_eq!(findings(&a), [(0xc, Issue::WrongLandingPad)]);
+    assert!(Bti::JC.accepts_jumps());
+}
+
+#[cfg(feature = "elf")]
+#[test]
+fn hardening_elf() {
+    let elf = bad64::elf::Elf::parse(include_bytes!("elf/funcs.o")).unwrap();
+
+    for f in elf.functions() {
+        let audit = f.audit();
+
+        assert_eq!(audit.signed, None);
+        assert_eq!(audit.findings[0].issue, Issue::MissingLandingPad);
+        assert_eq!(audit.findings[0].address, f.address);
+    }
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "jsonl")]
+
+use bad64::jsonl::{self, Record};
+use bad64::{DecodeErrorKind, decode};
+use serde_json::{Value, json};
+
+fn lines(code: &[u8], address: u64) -> Vec<Value> {
+    let mut out = Vec::new();
+    jsonl::write(&mut out, code, address).unwrap();
+
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn jsonl_records() {
+    // adds x0, x1, #0x41; cbz x0, #8; ret; then an unallocated word and two stray bytes
+    let code = b"\x20\x04\x01\xb1\x40\x00\x00\xb4\xc0\x03\x5f\xd6\x41\x41\x41\x41\xaa\xbb";
+    let lines = lines(code, 0x1000);
+
+    assert_eq!(lines.len(), 5);
+
+    assert_eq!(lines[0]["address"], 0x1000);
+    assert_eq!(lines[0]["bytes"], "200401b1");
+    assert_eq!(lines[0]["mnemonic"], "adds");
+    assert_eq!(lines[0]["text"], "adds x0, x1, #0x41");
+    assert_eq!(lines[0]["flags_set"], "Integer");
+    assert_eq!(
+        lines[0]["operands"][0],
+        json!({"Reg": {"reg": "X0", "arrspec": null}})
+    );
+    assert_eq!(lines[0]["targets"], json!([]));
+
+    // only the taken edge, the fallthrough is implied
+    assert_eq!(lines[1]["targets"], json!([{"Taken": 0x100c}]));
+    assert_eq!(
+        lines[2]["targets"],
+        json!([{"Indirect": {"reg": "X30", "target": null}}])
+    );
+
+    assert_eq!(lines[3]["mnemonic"], Value::Null);
+    assert_eq!(lines[3]["error"]["kind"], "Unallocated");
+    assert_eq!(lines[3]["error"]["opcode"], 0x41414141);
+    assert_eq!(lines[4]["bytes"], "aabb");
+    assert_eq!(lines[4]["error"]["kind"], "Short");
+}
+
+#[test]
+fn jsonl_record() {
+    let result = decode(0xd503201f, 0);
+    let record = Record::new(&[0x1f, 0x20, 0x03, 0xd5], &result);
+
+    assert_eq!(record.mnemonic, Some("nop"));
+    assert!(record.operands.is_empty());
+
+    let result = decode(0x41414141, 0);
+    let record = Record::new(&[0x41; 4], &result);
+
+    assert_eq!(record.error.unwrap().kind(), DecodeErrorKind::Unallocated);
+    assert_eq!(record.text, None);
+    assert!(!record.data);
+
+    let record = Record::data(0x10, &[0x88, 0x77, 0x66, 0x55]);
+    assert_eq!(record.bytes, "88776655");
+    assert_eq!(record.error, None);
+    assert!(record.data);
+}
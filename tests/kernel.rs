@@ -0,0 +1,323 @@
+#![cfg(feature = "kernel")]
+
+use bad64::Op;
+use bad64::elf::Endian;
+use bad64::kernel::*;
+
+const BASE: u64 = 0xffff_8000_8000_0000;
+
+const TEXT: [u32; 10] = [
+    0xf9400020, // 0x40 do_read: ldr x0, [x1]
+    0xd65f03c0, // ret
+    0x928001a0, // 0x48 fixup: mov x0, #-14
+    0xd65f03c0, // ret
+    0xd503201f, // 0x50 patched: nop, an alternative
+    0xd503201f, // nop, a jump label
+    0xd65f03c0, // ret
+    0x52800020, // 0x5c mov w0, #1
+    0xd65f03c0, // ret
+    0xd5033fdf, // 0x64 isb, the replacement
+];
+
+// kind, name, offset from BASE
+fn symbols() -> Vec<(char, String, u32)> {
+    let mut syms: Vec<_> = [
+        ('T', "_text", 0),
+        ('T', "do_read", 0x40),
+        ('t', "patched", 0x50),
+        ('D', "__start___ex_table", 0x80),
+        ('D', "__stop___ex_table", 0x8c),
+        ('D', "__alt_instructions", 0x8c),
+        ('D', "__alt_instructions_end", 0xa4),
+        ('D', "__start___jump_table", 0xa8),
+        ('D', "__stop___jump_table", 0xb8),
+        ('d', "my_key", 0x200),
+    ]
+    .into_iter()
+    .map(|(k, n, a)| (k, n.to_string(), a))
+    .collect();
+
+    // enough to need a second marker
+    syms.extend((0..300).map(|n| ('d', format!("filler_{}", n), 0x208 + n * 8)));
+
+    syms
+}
+
+// every byte is its own token, except for a few longer ones
+fn tokens() -> Vec<Vec<u8>> {
+    let mut tokens: Vec<_> = (0..=255u8).map(|b| vec![b]).collect();
+    tokens[0] = b"ex".to_vec();
+    tokens[1] = b"__".to_vec();
+    tokens[2] = b"ta".to_vec();
+    tokens[3] = b"ble".to_vec();
+    tokens[4] = b"filler_".to_vec();
+
+    tokens
+}
+
+fn compress(name: &str, tokens: &[Vec<u8>]) -> Vec<u8> {
+    let mut name = name.as_bytes();
+    let mut out = Vec::new();
+
+    while !name.is_empty() {
+        let b = (0..5)
+            .find(|t| name.starts_with(&tokens[*t]))
+            .unwrap_or(name[0] as usize);
+
+        out.push(b as u8);
+        name = &name[tokens[b].len()..];
+    }
+
+    out
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(8), 0);
+}
+
+fn put32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn rel(from: u32, to: u32) -> u32 {
+    to.wrapping_sub(from)
+}
+
+// an Image with kallsyms laid out as before 6.4, with the offsets first, or
+// after, with them last and kallsyms_seqs_of_names present
+fn image(offsets_last: bool) -> Vec<u8> {
+    let mut buf = vec![0u8; 0x40];
+    buf[16..24].copy_from_slice(&0x10000u64.to_le_bytes());
+    buf[24] = 0xa; // 4K pages, anywhere in memory
+    buf[56..60].copy_from_slice(b"ARM\x64");
+
+    for w in TEXT {
+        put32(&mut buf, w);
+    }
+    buf.resize(0x80, 0);
+
+    // __ex_table
+    for v in [rel(0x80, 0x40), rel(0x84, 0x48), 1] {
+        put32(&mut buf, v);
+    }
+
+    // .altinstructions, an isb and a callback
+    for v in [
+        rel(0x8c, 0x50),
+        rel(0x90, 0x64),
+        5 | (4 << 16) | (4 << 24),
+        rel(0x98, 0x58),
+        rel(0x9c, 0x40),
+        0x8007 | (4 << 16),
+    ] {
+        put32(&mut buf, v);
+    }
+
+    // __jump_table, with the key's low bit set for a default true branch
+    pad(&mut buf);
+    put32(&mut buf, rel(0xa8, 0x54));
+    put32(&mut buf, rel(0xac, 0x5c));
+    buf.extend_from_slice(&((0x200 - 0xb0) as u64 | 1).to_le_bytes());
+
+    buf.resize(0x1000, 0);
+
+    let syms = symbols();
+    let tokens = tokens();
+    let count = syms.len() as u32;
+
+    let offsets = |buf: &mut Vec<u8>| {
+        for (_, _, a) in &syms {
+            put32(buf, *a);
+        }
+        pad(buf);
+        buf.extend_from_slice(&BASE.to_le_bytes());
+    };
+
+    if !offsets_last {
+        offsets(&mut buf);
+    }
+
+    put32(&mut buf, count);
+    put32(&mut buf, 0);
+
+    let names = buf.len();
+    let mut markers = Vec::new();
+    for (n, (kind, name, _)) in syms.iter().enumerate() {
+        if n % 256 == 0 {
+            markers.push((buf.len() - names) as u32);
+        }
+
+        let c = compress(&format!("{}{}", kind, name), &tokens);
+        buf.push(c.len() as u8);
+        buf.extend_from_slice(&c);
+    }
+    pad(&mut buf);
+
+    for m in markers {
+        put32(&mut buf, m);
+    }
+    pad(&mut buf);
+
+    if offsets_last {
+        buf.extend((0..count * 3).map(|_| 0));
+        pad(&mut buf);
+    }
+
+    let table = buf.len();
+    let mut index = Vec::new();
+    for t in &tokens {
+        index.push((buf.len() - table) as u16);
+        buf.extend_from_slice(t);
+        buf.push(0);
+    }
+    pad(&mut buf);
+
+    for i in index {
+        buf.extend_from_slice(&i.to_le_bytes());
+    }
+    pad(&mut buf);
+
+    if offsets_last {
+        offsets(&mut buf);
+    }
+
+    buf
+}
+
+#[test]
+fn kernel_header() {
+    let buf = image(false);
+    let header = ImageHeader::parse(&buf).unwrap();
+
+    assert_eq!(header.text_offset, 0);
+    assert_eq!(header.image_size, 0x10000);
+    assert_eq!(header.endian(), Endian::Little);
+    assert_eq!(header.page_size(), Some(0x1000));
+
+    assert_eq!(
+        ImageHeader::parse(&buf[..0x20]),
+        Err(KernelError::Truncated)
+    );
+    assert_eq!(Kernel::from_image(&[0; 64]), Err(KernelError::BadMagic));
+}
+
+#[test]
+fn kernel_kallsyms() {
+    for offsets_last in [false, true] {
+        let buf = image(offsets_last);
+        let syms = kallsyms(&buf, Endian::Little).unwrap();
+
+        assert_eq!(syms.len(), 310);
+        assert_eq!(syms[0].name, "_text");
+        assert_eq!(syms[0].address, BASE);
+        assert_eq!(syms[2].kind, 't');
+        assert!(syms[2].is_text());
+        assert_eq!(syms[309].name, "filler_299");
+
+        let kernel = Kernel::from_image(&buf).unwrap();
+        assert_eq!(kernel.symbol("__stop___jump_table"), Some(BASE + 0xb8));
+        assert_eq!(kernel.symbol_at(BASE + 0x200), Some("my_key"));
+    }
+
+    assert_eq!(kallsyms(&[0; 0x100], Endian::Little), None);
+
+    // offsets past the top of the address space wrap
+    let mut buf = image(false);
+    let at = buf
+        .windows(8)
+        .rposition(|w| w == BASE.to_le_bytes())
+        .unwrap();
+    buf[at..at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let syms = kallsyms(&buf, Endian::Little).unwrap();
+    let address = |name| syms.iter().find(|s| s.name == name).unwrap().address;
+    assert_eq!(address("_text"), u64::MAX);
+    assert_eq!(address("do_read"), 0x3f);
+
+    let kernel = Kernel::from_image(&buf).unwrap();
+    assert!(!kernel.functions().is_empty());
+}
+
+#[test]
+fn kernel_functions() {
+    let buf = image(false);
+    let kernel = Kernel::from_image(&buf).unwrap();
+
+    let funcs: Vec<_> = kernel
+        .functions()
+        .iter()
+        .map(|f| (f.name, f.address - BASE, f.data.len()))
+        .collect();
+    assert_eq!(funcs[1], ("do_read", 0x40, 0x10));
+    assert_eq!(funcs[2].0, "patched");
+
+    let ops: Vec<_> = kernel.functions()[1]
+        .disasm()
+        .map(|i| i.unwrap().op())
+        .collect();
+    assert_eq!(ops, [Op::LDR, Op::RET, Op::MOV, Op::RET]);
+}
+
+#[test]
+fn kernel_tables() {
+    let buf = image(true);
+    let kernel = Kernel::from_image(&buf).unwrap();
+
+    assert_eq!(
+        kernel.ex_table(),
+        [ExEntry {
+            insn: BASE + 0x40,
+            fixup: BASE + 0x48,
+            kind: 1,
+            data: 0
+        }]
+    );
+
+    let alts = kernel.alternatives();
+    assert_eq!(
+        alts,
+        [
+            Alternative {
+                original: BASE + 0x50..BASE + 0x54,
+                replacement: BASE + 0x64..BASE + 0x68,
+                feature: 5,
+                callback: false
+            },
+            Alternative {
+                original: BASE + 0x58..BASE + 0x5c,
+                replacement: BASE + 0x40..BASE + 0x40,
+                feature: 7,
+                callback: true
+            },
+        ]
+    );
+
+    let original: Vec<_> = kernel.disasm(alts[0].original.clone()).collect();
+    let replacement: Vec<_> = kernel.disasm(alts[0].replacement.clone()).collect();
+    assert_eq!(original[0].as_ref().unwrap().op(), Op::NOP);
+    assert_eq!(replacement[0].as_ref().unwrap().op(), Op::ISB);
+
+    assert_eq!(
+        kernel.jump_entries(),
+        [JumpEntry {
+            code: BASE + 0x54,
+            target: BASE + 0x5c,
+            key: BASE + 0x200,
+            branch: true,
+            init: false
+        }]
+    );
+}
+
+#[test]
+fn kernel_vmlinux() {
+    let kernel = Kernel::from_vmlinux(include_bytes!("elf/funcs.o")).unwrap();
+
+    assert!(kernel.header().is_none());
+    assert_eq!(kernel.symbol("load_const"), Some(8));
+    assert!(kernel.alternatives().is_empty());
+
+    assert_eq!(
+        Kernel::from_vmlinux(b"MZ"),
+        Err(KernelError::Elf(bad64::elf::ElfError::BadMagic))
+    );
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "lift")]
+
+use bad64::lift::*;
+use bad64::*;
+
+fn text(stmts: &[Stmt]) -> Vec<String> {
+    stmts.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn lift_call_and_branch() {
+    // bl 0x1008
+    let stmts = lift(&decode(0x94000002, 0x1000).unwrap()).unwrap();
+    assert_eq!(text(&stmts), ["lr = #0x1004", "call #0x1008"]);
+
+    // b.ne 0x1008
+    let stmts = lift(&decode(0x54000041, 0x1000).unwrap()).unwrap();
+    assert_eq!(
+        text(&stmts),
+        ["t0 = z", "t1 = xor.1 t0, #0x1", "if t1 jump 0x1008"]
+    );
+
+    // ret
+    let stmts = lift(&decode(0xd65f03c0, 0x1000).unwrap()).unwrap();
+    assert_eq!(
+        stmts[1],
+        Stmt::Jump {
+            target: Value::Temp(Temp(0)),
+            kind: JumpKind::Return
+        }
+    );
+}
+
+#[test]
+fn lift_zero_register() {
+    // orr x0, xzr, #0xff
+    let stmts = lift(&decode(0xb2401fe0, 0x1000).unwrap()).unwrap();
+    assert_eq!(text(&stmts), ["t0 = or.64 #0x0, #0xff", "x0 = t0"]);
+
+    // cmp x1, x2 writes no register
+    let stmts = lift(&decode(0xeb02003f, 0x1000).unwrap()).unwrap();
+    assert!(!stmts.iter().any(|s| matches!(s, Stmt::WriteReg { .. })));
+    assert_eq!(
+        stmts
+            .iter()
+            .filter(|s| matches!(s, Stmt::WriteFlag { .. }))
+            .count(),
+        4
+    );
+}
+
+#[test]
+fn lift_store_pair_writeback() {
+    // stp fp, lr, [sp, #-0x10]!
+    let stmts = lift(&decode(0xa9bf7bfd, 0x1000).unwrap()).unwrap();
+    assert_eq!(
+        text(&stmts),
+        [
+            "t0 = fp",
+            "t1 = lr",
+            "t2 = sp",
+            "t3 = add.64 t2, #0xfffffffffffffff0",
+            "t4 = add.64 t3, #0x8",
+            "store.64 [t3], t0",
+            "store.64 [t4], t1",
+            "sp = t3",
+        ]
+    );
+}
+
+#[test]
+fn lift_unsupported_rolls_back() {
+    let mut lifter = Lifter::new();
+
+    // add x0, x1, #0x41
+    lifter.lift(&decode(0x91010420, 0x1000).unwrap()).unwrap();
+    assert_eq!(lifter.temps(), 2);
+
+    // aese v0.16b, v1.16b
+    let err = lifter.lift(&decode(0x4e284820, 0x1004).unwrap());
+    assert_eq!(err, Err(LiftError::Unsupported(Op::AESE)));
+    assert_eq!(lifter.stmts().len(), 3);
+    assert_eq!(lifter.temps(), 2);
+
+    // sub x0, x0, #0x1
+    let stmts = lifter.lift(&decode(0xd1000400, 0x1008).unwrap()).unwrap();
+    assert_eq!(stmts[0].dst(), Some(Temp(2)));
+}
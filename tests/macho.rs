@@ -0,0 +1,254 @@
+#![cfg(feature = "macho")]
+
+use bad64::Op;
+use bad64::macho::*;
+
+// built from tests/macho/funcs.s
+static FUNCS: &[u8] = include_bytes!("macho/funcs.o");
+
+fn put32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_name(buf: &mut Vec<u8>, name: &str) {
+    let mut b = [0u8; 16];
+    b[..name.len()].copy_from_slice(name.as_bytes());
+    buf.extend_from_slice(&b);
+}
+
+// a linked image with two functions in __text, found by LC_FUNCTION_STARTS,
+// and one stub to _puts
+fn linked() -> Vec<u8> {
+    const BASE: u64 = 0x1_0000_0000;
+    const TEXT: u32 = 0x180;
+    const STUBS: u32 = TEXT + 0x10;
+    const STARTS: u32 = STUBS + 0xc;
+    const SYMS: u32 = STARTS + 4;
+    const STRS: u32 = SYMS + 32;
+    const INDIRECT: u32 = STRS + 16;
+
+    let mut buf = Vec::new();
+
+    // mach_header_64, MH_EXECUTE
+    for v in [0xfeedfacf, CPU_TYPE_ARM64, 0, 2, 4, 352, 0, 0] {
+        put32(&mut buf, v);
+    }
+
+    // LC_SEGMENT_64 __TEXT
+    put32(&mut buf, 0x19);
+    put32(&mut buf, 72 + 2 * 80);
+    put_name(&mut buf, "__TEXT");
+    for v in [BASE, 0x1000, 0, 0x1000] {
+        put64(&mut buf, v);
+    }
+    for v in [5, 5, 2, 0] {
+        put32(&mut buf, v);
+    }
+
+    for (name, off, size, flags, reserved1, reserved2) in [
+        ("__text", TEXT, 0x10, 0x8000_0400, 0, 0),
+        ("__stubs", STUBS, 0xc, 0x8000_0408, 0, 0xc),
+    ] {
+        put_name(&mut buf, name);
+        put_name(&mut buf, "__TEXT");
+        put64(&mut buf, BASE + off as u64);
+        put64(&mut buf, size);
+        for v in [off, 2, 0, 0, flags, reserved1, reserved2, 0] {
+            put32(&mut buf, v);
+        }
+    }
+
+    // LC_SYMTAB
+    for v in [0x2, 24, SYMS, 2, STRS, 16] {
+        put32(&mut buf, v);
+    }
+
+    // LC_DYSYMTAB, with only the indirect symbol table
+    let mut dysymtab = [0u32; 20];
+    dysymtab[..2].copy_from_slice(&[0xb, 80]);
+    dysymtab[14..16].copy_from_slice(&[INDIRECT, 1]);
+    for v in dysymtab {
+        put32(&mut buf, v);
+    }
+
+    // LC_FUNCTION_STARTS
+    for v in [0x26, 16, STARTS, 4] {
+        put32(&mut buf, v);
+    }
+
+    assert_eq!(buf.len(), TEXT as usize);
+
+    // __text: add x0, x0, #1; ret; b _puts; ret
+    for v in [0x91000400, 0xd65f03c0, 0x14000002, 0xd65f03c0] {
+        put32(&mut buf, v);
+    }
+
+    // __stubs: three nops standing in for adrp/ldr/br
+    for _ in 0..3 {
+        put32(&mut buf, 0xd503201f);
+    }
+
+    // function starts: uleb128 0x180, 8, then the terminator
+    buf.extend_from_slice(&[0x80, 0x03, 0x08, 0x00]);
+
+    // _main, defined in section 1, and _puts, undefined
+    put32(&mut buf, 1);
+    buf.extend_from_slice(&[0x0f, 1, 0, 0]);
+    put64(&mut buf, BASE + TEXT as u64);
+    put32(&mut buf, 7);
+    buf.extend_from_slice(&[0x01, 0, 0, 0]);
+    put64(&mut buf, 0);
+
+    buf.extend_from_slice(b"\0_main\0_puts\0\0\0\0");
+
+    put32(&mut buf, 1);
+
+    buf
+}
+
+// wrap images in a universal binary
+fn fat(images: &[(u32, u32, &[u8])]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&0xcafebabe_u32.to_be_bytes());
+    buf.extend_from_slice(&(images.len() as u32).to_be_bytes());
+
+    let mut offset = 0x1000;
+    for (cpu_type, cpu_subtype, data) in images {
+        for v in [*cpu_type, *cpu_subtype, offset, data.len() as u32, 12] {
+            buf.extend_from_slice(&v.to_be_bytes());
+        }
+        offset += 0x1000;
+    }
+
+    for (_, _, data) in images {
+        buf.resize(buf.len().next_multiple_of(0x1000), 0);
+        buf.extend_from_slice(data);
+    }
+
+    buf
+}
+
+#[test]
+fn macho_object() {
+    let macho = MachO::parse(FUNCS).unwrap();
+    assert!(!macho.is_arm64e());
+
+    let text = macho.section_by_name("__TEXT", "__text").unwrap();
+    assert!(text.is_executable());
+    assert_eq!(text.data.len(), 0x20);
+    assert_eq!(macho.executable_sections().count(), 1);
+
+    let data = macho.section_by_name("__DATA", "__data").unwrap();
+    assert!(!data.is_executable());
+
+    // the assembler's ltmp symbols lose to the external ones
+    let funcs: Vec<_> = macho
+        .functions()
+        .iter()
+        .map(|f| (f.name, f.address, f.data.len()))
+        .collect();
+    assert_eq!(funcs, [(Some("_add_one"), 0, 8), (Some("_jump"), 8, 0x18)]);
+
+    assert_eq!(macho.data_in_code().len(), 1);
+    assert_eq!(macho.data_in_code()[0], 0x14..0x1c);
+
+    let jump = &macho.functions()[1];
+    assert_eq!(jump.data_in_code(), macho.data_in_code());
+
+    let ops: Vec<_> = jump.disasm().map(|i| i.unwrap().op()).collect();
+    assert_eq!(ops, [Op::ADR, Op::LDR, Op::BR, Op::RET]);
+
+    assert_eq!(macho.symbol_at(0x20), Some("_value"));
+    assert_eq!(macho.symbol_at(0x4), None);
+}
+
+#[test]
+fn macho_linked() {
+    let buf = linked();
+    let macho = MachO::parse(&buf).unwrap();
+
+    let funcs: Vec<_> = macho
+        .functions()
+        .iter()
+        .map(|f| (f.name, f.address, f.data.len()))
+        .collect();
+    assert_eq!(
+        funcs,
+        [(Some("_main"), 0x1_0000_0180, 8), (None, 0x1_0000_0188, 8)]
+    );
+
+    assert_eq!(macho.stubs(), [(0x1_0000_0190, "_puts")]);
+    assert_eq!(macho.symbol_at(0x1_0000_0190), Some("_puts"));
+
+    let stubs = macho.section_by_name("__TEXT", "__stubs").unwrap();
+    assert_eq!(macho.disasm(stubs).count(), 3);
+
+    let b = macho.functions()[1].disasm().next().unwrap().unwrap();
+    assert_eq!(b.op(), Op::B);
+    assert_eq!(
+        b.operands()[0],
+        bad64::Operand::Label(bad64::Imm::Unsigned(0x1_0000_0190))
+    );
+}
+
+#[test]
+fn macho_fat() {
+    let buf = linked();
+
+    // tag the object as arm64e, so it is picked over the linked image
+    let mut arm64e = FUNCS.to_vec();
+    arm64e[8..12].copy_from_slice(&(CPU_SUBTYPE_ARM64E | 0x8000_0000).to_le_bytes());
+
+    let universal = fat(&[
+        (0x0100_0007, 3, &buf),
+        (CPU_TYPE_ARM64, 0, &buf),
+        (CPU_TYPE_ARM64, CPU_SUBTYPE_ARM64E | 0x8000_0000, &arm64e),
+    ]);
+
+    let slices = slices(&universal).unwrap();
+    assert_eq!(slices.len(), 3);
+    assert_eq!(slices[2].cpu_subtype, CPU_SUBTYPE_ARM64E);
+    assert_eq!(slices[1].data, buf.as_slice());
+
+    let macho = MachO::parse(&universal).unwrap();
+    assert!(macho.is_arm64e());
+    assert_eq!(macho.functions()[0].name, Some("_add_one"));
+
+    let thin = fat(&[(CPU_TYPE_ARM64, 0, &buf)]);
+    let macho = MachO::parse(&thin).unwrap();
+    assert_eq!(macho.functions()[0].name, Some("_main"));
+
+    assert_eq!(
+        MachO::parse(&fat(&[(0x0100_0007, 3, &buf), (0x0100_0007, 8, &buf)])),
+        Err(MachOError::NoSlice)
+    );
+}
+
+#[test]
+fn macho_errors() {
+    assert_eq!(MachO::parse(b"\x7fELF"), Err(MachOError::BadMagic));
+    assert_eq!(MachO::parse(&FUNCS[..64]), Err(MachOError::Truncated));
+
+    let mut x86 = FUNCS.to_vec();
+    x86[4..8].copy_from_slice(&0x0100_0007_u32.to_le_bytes());
+    assert_eq!(MachO::parse(&x86), Err(MachOError::BadCpuType(0x0100_0007)));
+
+    // symbols in sections which do not exist are skipped
+    let mut bad_sect = FUNCS.to_vec();
+    let u32_at = |b: &[u8], off: usize| u32::from_le_bytes(b[off..off + 4].try_into().unwrap());
+    let mut off = 32;
+    while u32_at(FUNCS, off) != 2 {
+        off += u32_at(FUNCS, off + 4) as usize;
+    }
+    let (symoff, nsyms) = (u32_at(FUNCS, off + 8), u32_at(FUNCS, off + 12));
+    for n in 0..nsyms as usize {
+        bad_sect[symoff as usize + n * 16 + 5] = 0x7f;
+    }
+    let macho = MachO::parse(&bad_sect).unwrap();
+    assert!(macho.symbols().is_empty());
+    assert!(macho.functions().is_empty());
+}
@@ -0,0 +1,23 @@
+// llvm-mc -triple=arm64-apple-macos -filetype=obj funcs.s -o funcs.o
+    .section __TEXT,__text,regular,pure_instructions
+    .globl _add_one
+_add_one:
+    add x0, x0, #1
+    ret
+
+    .globl _jump
+_jump:
+    adr x1, Ltable
+    ldr w2, [x1, x0, lsl #2]
+    br x2
+    .data_region jt32
+Ltable:
+    .long 0
+    .long 4
+    .end_data_region
+    ret
+
+    .section __DATA,__data
+    .globl _value
+_value:
+    .quad 1
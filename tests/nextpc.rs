@@ -0,0 +1,118 @@
+use bad64::*;
+
+struct View {
+    x: [Option<u64>; 31],
+    nzcv: Option<Nzcv>,
+}
+
+impl RegisterView for View {
+    fn reg(&self, reg: Reg) -> Option<u64> {
+        let names = [
+            Reg::X0,
+            Reg::X1,
+            Reg::X2,
+            Reg::X3,
+            Reg::X4,
+            Reg::X5,
+            Reg::X6,
+            Reg::X7,
+        ];
+
+        names.iter().position(|r| *r == reg).and_then(|n| self.x[n])
+    }
+
+    fn nzcv(&self) -> Option<Nzcv> {
+        self.nzcv
+    }
+}
+
+fn view(nzcv: Option<Nzcv>) -> View {
+    View {
+        x: [None; 31],
+        nzcv,
+    }
+}
+
+#[test]
+fn next_pc_conditions() {
+    // b.hi 0x1008
+    let bhi = decode(0x54000048, 0x1000).unwrap();
+    // b.ge 0x1008
+    let bge = decode(0x5400004a, 0x1000).unwrap();
+    // b.nv 0x1008
+    let bnv = decode(0x5400004f, 0x1000).unwrap();
+
+    let taken = [NextPc::Taken(0x1008)];
+    let not_taken = [NextPc::Fallthrough(0x1004)];
+
+    // hi needs c set and z clear
+    let v = view(Some(Nzcv::new(false, false, true, false)));
+    assert_eq!(next_pcs(&bhi, &v).as_slice(), &taken);
+    let v = view(Some(Nzcv::new(false, true, true, false)));
+    assert_eq!(next_pcs(&bhi, &v).as_slice(), &not_taken);
+
+    // ge compares n and v
+    let v = view(Some(Nzcv::new(true, false, false, true)));
+    assert_eq!(next_pcs(&bge, &v).as_slice(), &taken);
+    let v = view(Some(Nzcv::new(true, false, false, false)));
+    assert_eq!(next_pcs(&bge, &v).as_slice(), &not_taken);
+
+    // nv is always taken
+    let v = view(Some(Nzcv::default()));
+    assert_eq!(next_pcs(&bnv, &v).as_slice(), &taken);
+}
+
+#[test]
+fn next_pc_unknown_flags() {
+    // b.eq 0x1008
+    let beq = decode(0x54000040, 0x1000).unwrap();
+    let pcs = next_pcs(&beq, &view(None));
+
+    assert_eq!(
+        pcs.as_slice(),
+        &[NextPc::Taken(0x1008), NextPc::Fallthrough(0x1004)]
+    );
+}
+
+#[test]
+fn next_pc_compare_branch() {
+    // cbz w1, 0x1008
+    let cbz = decode(0x34000041, 0x1000).unwrap();
+
+    let mut v = view(None);
+    v.x[1] = Some(0xffff_ffff_0000_0000);
+    assert_eq!(next_pcs(&cbz, &v).as_slice(), &[NextPc::Taken(0x1008)]);
+
+    v.x[1] = Some(1);
+    assert_eq!(
+        next_pcs(&cbz, &v).as_slice(),
+        &[NextPc::Fallthrough(0x1004)]
+    );
+}
+
+#[test]
+fn next_pc_indirect() {
+    // blr x8
+    let blr = decode(0xd63f0100, 0x1000).unwrap();
+    let pcs = next_pcs(&blr, &view(None));
+
+    assert_eq!(
+        pcs.as_slice(),
+        &[NextPc::Indirect {
+            reg: Reg::X8,
+            target: None
+        }]
+    );
+    assert_eq!(pcs.iter().next().unwrap().address(), None);
+
+    // brk #0
+    let brk = decode(0xd4200000, 0x1000).unwrap();
+    assert_eq!(next_pcs(&brk, &view(None)).as_slice(), &[NextPc::Exception]);
+
+    // nop
+    let nop = decode(0xd503201f, 0x1000).unwrap();
+    assert_eq!(
+        next_pcs(&nop, &view(None)).as_slice(),
+        &[NextPc::Fallthrough(0x1004)]
+    );
+}
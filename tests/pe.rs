@@ -0,0 +1,326 @@
+#![cfg(feature = "pe")]
+
+use bad64::pe::*;
+use bad64::{Op, Reg};
+
+const BASE: u64 = 0x1_4000_0000;
+
+// llvm-mc -triple=aarch64-windows output for two functions, full and other,
+// with other tail calling a fragment, next
+const TEXT: [u32; 13] = [
+    0xa9be7bfd, // stp x29, x30, [sp, #-32]!
+    0xa90153f3, // stp x19, x20, [sp, #16]
+    0x910003fd, // mov x29, sp
+    0xd14007ff, // sub sp, sp, #4096
+    0x94000005, // bl other
+    0x914007ff, // add sp, sp, #4096
+    0xa94153f3, // ldp x19, x20, [sp, #16]
+    0xa8c27bfd, // ldp x29, x30, [sp], #32
+    0xd65f03c0, // ret
+    0x6dbf27e8, // stp d8, d9, [sp, #-16]!
+    0x52800020, // mov w0, #1
+    0x14000001, // b next
+    0xd65f03c0, // ret
+];
+
+// a function with packed unwind data
+const PACKED: [u32; 4] = [
+    0xa9bf7bfd, // stp x29, x30, [sp, #-16]!
+    0x910003fd, // mov x29, sp
+    0xa8c17bfd, // ldp x29, x30, [sp], #16
+    0xd65f03c0, // ret
+];
+
+const XDATA: [u32; 8] = [
+    0x20400009, 0x01c00005, 0xc8e100c1, 0xc1e48302, 0x8302c800, 0xe3e3e3e4, 0x08000003, 0xe3e401da,
+];
+
+fn put(buf: &mut [u8], off: usize, bytes: &[u8]) {
+    buf[off..off + bytes.len()].copy_from_slice(bytes);
+}
+
+fn put16(buf: &mut [u8], off: usize, v: u16) {
+    put(buf, off, &v.to_le_bytes());
+}
+
+fn put32(buf: &mut [u8], off: usize, v: u32) {
+    put(buf, off, &v.to_le_bytes());
+}
+
+fn put_words(buf: &mut [u8], off: usize, words: &[u32]) {
+    for (n, w) in words.iter().enumerate() {
+        put32(buf, off + n * 4, *w);
+    }
+}
+
+// .text at rva 0x1000, .rdata with the unwind records, exports and load
+// config at 0x2000, and .pdata at 0x3000
+fn image(machine: u16, chpe: bool) -> Vec<u8> {
+    let mut buf = vec![0u8; 0x800];
+
+    put(&mut buf, 0, b"MZ");
+    put32(&mut buf, 0x3c, 0x40);
+    put(&mut buf, 0x40, b"PE\0\0");
+
+    // file header
+    put16(&mut buf, 0x44, machine);
+    put16(&mut buf, 0x46, 3);
+    put16(&mut buf, 0x54, 240);
+    put16(&mut buf, 0x56, 0x22);
+
+    // optional header
+    let opt = 0x58;
+    put16(&mut buf, opt, 0x20b);
+    put32(&mut buf, opt + 16, 0x1000);
+    put(&mut buf, opt + 24, &BASE.to_le_bytes());
+    put32(&mut buf, opt + 108, 16);
+    put_words(&mut buf, opt + 112, &[0x2040, 40]);
+    put_words(&mut buf, opt + 112 + 3 * 8, &[0x3000, 32]);
+    if chpe {
+        put_words(&mut buf, opt + 112 + 10 * 8, &[0x2100, 0x100]);
+    }
+
+    for (n, (name, rva, size, raw, flags)) in [
+        (".text", 0x1000, 0x44, 0x200, 0x6000_0020),
+        (".rdata", 0x2000, 0x220, 0x400, 0x4000_0040),
+        (".pdata", 0x3000, 0x20, 0x700, 0x4000_0040),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let s = opt + 240 + n * 40;
+        put(&mut buf, s, name.as_bytes());
+        put_words(&mut buf, s + 8, &[size, rva, size, raw]);
+        put32(&mut buf, s + 36, flags);
+    }
+
+    put_words(&mut buf, 0x200, &TEXT);
+    put_words(&mut buf, 0x234, &PACKED);
+
+    let rdata = 0x400;
+    put_words(&mut buf, rdata, &XDATA);
+
+    // export directory, naming full and other
+    put_words(&mut buf, rdata + 0x40 + 20, &[2, 2, 0x2080, 0x2090, 0x20a0]);
+    put_words(&mut buf, rdata + 0x80, &[0x1000, 0x1024]);
+    put_words(&mut buf, rdata + 0x90, &[0x20b0, 0x20b8]);
+    put_words(&mut buf, rdata + 0xa0, &[0x0001_0000]);
+    put(&mut buf, rdata + 0xb0, b"full\0\0\0\0other\0");
+
+    // load config, pointing to the CHPE metadata and its code map
+    put32(&mut buf, rdata + 0x100, 0x100);
+    put(&mut buf, rdata + 0x1c8, &(BASE + 0x2200).to_le_bytes());
+    put_words(&mut buf, rdata + 0x200, &[1, 0x2210, 2]);
+    put_words(&mut buf, rdata + 0x210, &[0x1000, 0x34, 0x1035, 0x10]);
+
+    put_words(
+        &mut buf,
+        0x700,
+        &[
+            0x1000,
+            0x2000,
+            0x1024,
+            0x2018,
+            // next, a fragment of other
+            0x1030,
+            2 | (1 << 2),
+            // packed: 16 bytes, chained, 16 byte frame
+            0x1034,
+            1 | (4 << 2) | (3 << 21) | (1 << 23),
+        ],
+    );
+
+    buf
+}
+
+#[test]
+fn pe_functions() {
+    let buf = image(0xaa64, false);
+    let pe = Pe::parse(&buf).unwrap();
+
+    assert_eq!(pe.machine(), Machine::Arm64);
+    assert_eq!(pe.image_base(), BASE);
+    assert_eq!(pe.entry(), BASE + 0x1000);
+    assert_eq!(pe.executable_sections().count(), 1);
+    assert_eq!(pe.section_by_name(".pdata").unwrap().data.len(), 0x20);
+    assert!(pe.code_ranges().is_empty());
+
+    let funcs: Vec<_> = pe
+        .functions()
+        .iter()
+        .map(|f| (f.name, f.address - BASE, f.data.len()))
+        .collect();
+    assert_eq!(
+        funcs,
+        [
+            (Some("full"), 0x1000, 0x24),
+            (Some("other"), 0x1024, 0xc),
+            (None, 0x1030, 4),
+            (None, 0x1034, 0x10),
+        ]
+    );
+
+    let other = &pe.functions()[1];
+    let ops: Vec<_> = other.disasm().map(|i| i.unwrap().op()).collect();
+    assert_eq!(ops, [Op::STP, Op::MOV, Op::B]);
+
+    assert_eq!(pe.symbol_at(BASE + 0x1024), Some("other"));
+    assert_eq!(pe.symbol_at(BASE + 0x1028), None);
+}
+
+#[test]
+fn pe_unwind_full() {
+    let buf = image(0xaa64, false);
+    let pe = Pe::parse(&buf).unwrap();
+
+    let Unwind::Full(info) = &pe.runtime_functions()[0].unwind else {
+        panic!("expected full unwind data");
+    };
+
+    assert_eq!(info.function_length, 36);
+    assert_eq!(info.handler, None);
+    assert_eq!(info.codes.len(), 16);
+
+    let x29 = UnwindCode::SavePair {
+        first: Reg::X29,
+        second: Reg::X30,
+        offset: -32,
+        writeback: true,
+    };
+    let x19 = UnwindCode::SavePair {
+        first: Reg::X19,
+        second: Reg::X20,
+        offset: 16,
+        writeback: false,
+    };
+
+    assert_eq!(
+        info.prolog(),
+        [
+            UnwindCode::Alloc(4096),
+            UnwindCode::SetFp,
+            x19,
+            x29,
+            UnwindCode::End
+        ]
+    );
+
+    assert_eq!(
+        info.epilogs,
+        [Epilog {
+            offset: Some(20),
+            index: 7
+        }]
+    );
+    assert_eq!(
+        info.epilog(&info.epilogs[0]),
+        [UnwindCode::Alloc(4096), x19, x29, UnwindCode::End]
+    );
+
+    let Unwind::Full(info) = &pe.runtime_functions()[1].unwind else {
+        panic!("expected full unwind data");
+    };
+
+    assert!(info.epilogs.is_empty());
+    assert_eq!(
+        info.prolog(),
+        [
+            UnwindCode::SavePair {
+                first: Reg::D8,
+                second: Reg::D9,
+                offset: -16,
+                writeback: true
+            },
+            UnwindCode::End
+        ]
+    );
+}
+
+#[test]
+fn pe_unwind_packed() {
+    let buf = image(0xaa64, false);
+    let pe = Pe::parse(&buf).unwrap();
+
+    let Unwind::Packed(next) = pe.runtime_functions()[2].unwind else {
+        panic!("expected packed unwind data");
+    };
+    assert!(next.fragment);
+    assert_eq!(next.function_length, 4);
+
+    let Unwind::Packed(packed) = pe.runtime_functions()[3].unwind else {
+        panic!("expected packed unwind data");
+    };
+    assert_eq!(
+        packed,
+        PackedUnwind {
+            function_length: 16,
+            fragment: false,
+            reg_f: 0,
+            reg_i: 0,
+            homes_params: false,
+            cr: 3,
+            frame_size: 16,
+        }
+    );
+    assert!(packed.saves_lr());
+    assert!(packed.has_frame_pointer());
+    assert!(!packed.signs_lr());
+}
+
+#[test]
+fn pe_arm64x() {
+    let buf = image(0xaa64, true);
+    let pe = Pe::parse(&buf).unwrap();
+
+    assert_eq!(pe.machine(), Machine::Arm64X);
+    assert_eq!(
+        pe.code_ranges(),
+        [
+            CodeRange {
+                range: BASE + 0x1000..BASE + 0x1034,
+                kind: CodeKind::Arm64
+            },
+            CodeRange {
+                range: BASE + 0x1034..BASE + 0x1044,
+                kind: CodeKind::Arm64Ec
+            },
+        ]
+    );
+    assert_eq!(pe.functions().len(), 4);
+
+    // an ARM64EC image's exception directory is for its x64 code
+    let buf = image(0x8664, true);
+    let pe = Pe::parse(&buf).unwrap();
+
+    assert_eq!(pe.machine(), Machine::Arm64Ec);
+    assert_eq!(pe.code_ranges().len(), 2);
+    assert!(pe.functions().is_empty());
+}
+
+#[test]
+fn pe_errors() {
+    assert_eq!(Pe::parse(b"\x7fELF"), Err(PeError::BadMagic));
+    assert_eq!(
+        Pe::parse(&image(0xaa64, false)[..0x100]),
+        Err(PeError::Truncated)
+    );
+    assert_eq!(
+        Pe::parse(&image(0x8664, false)),
+        Err(PeError::BadMachine(0x8664))
+    );
+
+    let mut buf = image(0xaa64, false);
+    buf[0x58] = 0x0b;
+    buf[0x59] = 0x01;
+    assert_eq!(Pe::parse(&buf), Err(PeError::BadOptionalHeader));
+
+    // an image base with no room for the image above it
+    let mut buf = image(0xaa64, false);
+    put(&mut buf, 0x58 + 24, &0xffff_ffff_ffff_f000u64.to_le_bytes());
+    assert_eq!(Pe::parse(&buf), Err(PeError::Truncated));
+
+    // an exception directory at the top of the rva space
+    let mut buf = image(0xaa64, false);
+    put_words(&mut buf, 0x58 + 112 + 3 * 8, &[0xffff_fffc, 32]);
+    assert_eq!(Pe::parse(&buf), Err(PeError::Truncated));
+}
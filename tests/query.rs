@@ -0,0 +1,163 @@
+#![cfg(feature = "lift")]
+
+mod common;
+
+use bad64::Reg;
+use bad64::query::*;
+use common::{bytes, decode_all};
+
+const FUNC: [u32; 10] = [
+    0xa9be7bfd, // stp fp, lr, [sp, #-0x20]!
+    0x910003fd, // mov fp, sp
+    0xb0000008, // adrp x8, 0x2000
+    0xf9400908, // ldr x8, [x8, #0x10]
+    0xb9400109, // ldr w9, [x8]
+    0xd63f0100, // blr x8
+    0xb4000040, // cbz x0, 0x101c
+    0xa8c27bfd, // ldp fp, lr, [sp], #0x20
+    0xf8627820, // ldr x0, [x1, x2, lsl #3]
+    0xd65f03c0, // ret
+];
+
+fn addresses(pattern: &str) -> Vec<u64> {
+    let ins = decode_all(&FUNC);
+
+    Pattern::compile(pattern)
+        .unwrap()
+        .find(&ins)
+        .map(|m| m.address)
+        .collect()
+}
+
+#[test]
+fn query_operands() {
+    assert_eq!(addresses("stp x29, x30, [sp, #-?]!"), [0x1000]);
+    assert_eq!(addresses("stp fp, lr, [sp, #-0x20]!"), [0x1000]);
+    assert!(addresses("stp fp, lr, [sp, #?]").is_empty());
+    assert_eq!(addresses("ldp ?, ?, [sp], #32"), [0x101c]);
+    assert_eq!(addresses("ldr ?, [?]"), [0x1010]);
+    assert_eq!(addresses("ldr ?, [x8, #?]"), [0x100c, 0x1010]);
+    assert_eq!(addresses("ldr x0, [x1, x2, lsl #3]"), [0x1020]);
+    assert_eq!(addresses("LDR X0, [X1, ?]"), [0x1020]);
+    assert_eq!(addresses("adrp x8, 0x2000"), [0x1008]);
+    assert_eq!(addresses("cbz ..."), [0x1018]);
+    assert!(addresses("mov ?").is_empty());
+}
+
+#[test]
+fn query_ops() {
+    assert_eq!(addresses("@load ..."), [0x100c, 0x1010, 0x101c, 0x1020]);
+    assert_eq!(addresses("@store ..."), [0x1000]);
+    assert_eq!(addresses("@call ?"), [0x1014]);
+    assert_eq!(addresses("@ret"), [0x1024]);
+    assert_eq!(addresses("@branch ..."), [0x1014, 0x1018, 0x1024]);
+    assert_eq!(addresses("blr|ret ..."), [0x1014, 0x1024]);
+    assert_eq!(addresses("* fp, ..."), [0x1000, 0x1004, 0x101c]);
+}
+
+#[test]
+fn query_captures() {
+    let ins = decode_all(&FUNC);
+
+    let deref = Pattern::compile("ldr $r, [$r, #?]").unwrap();
+    let found: Vec<_> = deref.find(&ins).collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].address, 0x100c);
+    assert_eq!(found[0].captures, [("r", Reg::X8)]);
+
+    // a capture must be the same register each time, but not a different one
+    let seq = Pattern::compile("adrp $a, ?\nldr $b, [$a, #?]; ldr ?, [$b]").unwrap();
+    let m = seq.matches(&ins[2..]).unwrap();
+    assert_eq!((m.address, m.len), (0x1008, 3));
+    assert_eq!(m.capture("a"), Some(Reg::X8));
+    assert_eq!(m.capture("b"), Some(Reg::X8));
+    assert_eq!(m.capture("c"), None);
+    assert!(seq.matches(&ins).is_none());
+
+    // instructions must be consecutive
+    let gap = [ins[2].clone(), ins[4].clone()];
+    let seq = Pattern::compile("adrp x8, ?; ldr w9, ...").unwrap();
+    assert!(seq.matches(&ins[2..]).is_none());
+    assert!(seq.matches(&gap).is_none());
+
+    let code = bytes(&FUNC);
+    let call = Pattern::compile("blr $r").unwrap();
+    let found = call.scan(&code, 0x1000);
+    assert_eq!(found[0].captures, [("r", Reg::X8)]);
+}
+
+#[test]
+fn query_gaps() {
+    let ins = decode_all(&[
+        0xb0000008, // adrp x8, 0x2000
+        0xd2800020, // mov x0, #1
+        0x11004108, // add w8, w8, #0x10
+        0xf9400109, // ldr x9, [x8]
+        0x94000000, // bl 0x1010
+        0xf940050a, // ldr x10, [x8, #8]
+    ]);
+
+    let find = |pattern: &str| -> Vec<Vec<u64>> {
+        Pattern::compile(pattern)
+            .unwrap()
+            .find(&ins)
+            .map(|m| m.addresses)
+            .collect()
+    };
+
+    assert_eq!(find("mov ...; ... 1; ldr ..."), [[0x1004, 0x100c]]);
+    assert_eq!(find("mov ...; ... 2\n...\nldr ..."), [[0x1004, 0x100c]]);
+    assert_eq!(find("adrp ...; ... 3; @call ?"), [[0x1000, 0x1010]]);
+    assert!(find("adrp ...; ... 2; @call ?").is_empty());
+
+    // the add writes x8 through w8, and the call clobbers it
+    assert!(find("adrp $x, ?; ...; ldr ?, [$x, #?]").is_empty());
+    assert!(find("ldr ?, [$x]; ...; ldr ?, [$x, #8]").is_empty());
+    assert_eq!(find("adrp x8, ?; ...; ldr ?, [x8, #?]"), [[0x1000, 0x100c]]);
+
+    // registers captured after the gap may be written in it
+    let load = Pattern::compile("mov ?, #1; ... 4; ldr $y, [x8, #?]").unwrap();
+    let m = load.matches(&ins[1..]).unwrap();
+    assert_eq!((m.address, m.len), (0x1004, 3));
+    assert_eq!(m.capture("y"), Some(Reg::X9));
+
+    // the gap is extended past the first load, which has the wrong offset
+    assert_eq!(find("mov ...; ...; ldr ?, [x8, #8]"), [[0x1004, 0x1014]]);
+}
+
+#[test]
+fn query_errors() {
+    assert_eq!(Pattern::compile(""), Err(QueryError::Empty(0)));
+    assert_eq!(Pattern::compile(" ; "), Err(QueryError::Empty(0)));
+    assert_eq!(Pattern::compile("nop;;lodr"), Err(QueryError::UnknownOp(5)));
+    assert_eq!(Pattern::compile("@jump"), Err(QueryError::UnknownGroup(1)));
+    assert_eq!(
+        Pattern::compile("mov x0, x32"),
+        Err(QueryError::UnknownReg(8))
+    );
+    assert_eq!(
+        Pattern::compile("mov x0, #0xz"),
+        Err(QueryError::BadNumber(9))
+    );
+    assert_eq!(
+        Pattern::compile("ret ..., x0"),
+        Err(QueryError::Unexpected(7))
+    );
+    assert_eq!(Pattern::compile("ret x0 x1").unwrap_err().offset(), 7);
+    assert_eq!(
+        Pattern::compile("... 2; ret"),
+        Err(QueryError::Unexpected(0))
+    );
+    assert_eq!(
+        Pattern::compile("ret; ... 2"),
+        Err(QueryError::Unexpected(10))
+    );
+    assert_eq!(
+        Pattern::compile("nop; ... x; ret"),
+        Err(QueryError::BadNumber(9))
+    );
+    assert_eq!(
+        Pattern::compile("nop; ... 1 2"),
+        Err(QueryError::Unexpected(11))
+    );
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "serde")]
+
+use bad64::*;
+
+#[test]
+fn serde_instruction() {
+    // ldr x0, [x1, #8]; mrs x0, tpidr_el0; add x0, x1, #0x41; msr nzcv, x0
+    for word in [0xf9400420, 0xd53bd040, 0x91010420, 0xd51b4200] {
+        let ins = decode(word, 0x1000).unwrap();
+
+        let json = serde_json::to_string(&ins).unwrap();
+        let back: Instruction = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(back, ins);
+        assert_eq!(back.operands().len(), ins.operands().len());
+    }
+
+    let ins = decode(0x91010420, 0x1000).unwrap();
+    let json = serde_json::to_value(&ins).unwrap();
+
+    assert_eq!(json["op"], "ADD");
+    assert_eq!(json["opcode"], 0x91010420_u32);
+    assert_eq!(json["operands"].as_array().unwrap().len(), 3);
+    assert_eq!(json["operands"][0]["Reg"]["reg"], "X0");
+    assert_eq!(json["flags_set"], serde_json::Value::Null);
+}
+
+#[test]
+fn serde_names() {
+    // bti c, with its target as a name
+    let ins = decode(0xd503245f, 0).unwrap();
+    let json = serde_json::to_value(&ins).unwrap();
+
+    assert_eq!(json["operands"][0]["Name"], "c");
+    assert_eq!(serde_json::from_value::<Instruction>(json).unwrap(), ins);
+
+    let long = serde_json::json!({ "Name": "a_name_too_long_to_fit" });
+    assert!(serde_json::from_value::<Operand>(long).is_err());
+}
+
+#[test]
+fn serde_error() {
+    let err = decode(0x41414141, 0x1000).unwrap_err();
+    let json = serde_json::to_string(&err).unwrap();
+
+    assert_eq!(serde_json::from_str::<DecodeError>(&json).unwrap(), err);
+    assert_eq!(
+        serde_json::to_value(err).unwrap()["kind"],
+        serde_json::json!("Unallocated")
+    );
+    assert_eq!(serde_json::to_string(&Condition::NE).unwrap(), "\"NE\"");
+}
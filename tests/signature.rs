@@ -0,0 +1,130 @@
+#![cfg(feature = "alloc")]
+
+mod common;
+
+use bad64::decode;
+use bad64::signature::*;
+use common::bytes;
+
+const BUILD_A: [u32; 5] = [
+    0xf0000000, // adrp x0, 0x4000
+    0x91004000, // add x0, x0, #0x10
+    0x94000040, // bl 0x1100
+    0xf94007e1, // ldr x1, [sp, #8]
+    0xd65f03c0, // ret
+];
+
+#[test]
+fn signature_fields() {
+    let fields = |word| Fields::of(&decode(word, 0x1000).unwrap());
+
+    let adrp = fields(0xf0000000);
+    assert_eq!(adrp.labels, 0x60ffffe0);
+    assert_eq!(adrp.registers, 0x1f);
+    assert_eq!(adrp.immediates, 0);
+
+    let add = fields(0x91004000);
+    assert_eq!(add.immediates, 0x003ffc00);
+    assert_eq!(add.registers, 0x3ff);
+
+    let bl = fields(0x94000040);
+    assert_eq!(bl.labels, 0x03ffffff);
+
+    // the condition of a branch is part of the op
+    let b_eq = fields(0x54000040);
+    assert_eq!(b_eq.labels, 0x00ffffe0);
+    assert_eq!(b_eq.immediates | b_eq.registers, 0);
+
+    assert_eq!(fields(0xd65f03c0), Fields::default());
+}
+
+#[test]
+fn signature_scan() {
+    let sig = Signature::new(&bytes(&BUILD_A), 0x1000, Wildcards::default());
+    assert_eq!(sig.len(), 20);
+    assert_eq!(
+        sig.to_string(),
+        "90000000/9f00001f 91000000/ffc003ff 94000000/fc000000 f94003e1/ffc003ff d65f03c0"
+    );
+
+    // another build, with other addresses and offsets
+    let mut build_b = bytes(&[
+        0xd503201f, // nop
+        0xb0000040, // adrp x0, 0xa000
+        0x91012000, // add x0, x0, #0x48
+        0x94000200, // bl 0x1800
+        0xf9400fe1, // ldr x1, [sp, #0x18]
+        0xd65f03c0, // ret
+    ]);
+    assert_eq!(sig.scan(&build_b, 0x2000).collect::<Vec<_>>(), [0x2004]);
+    assert!(sig.matches(&build_b[4..]));
+    assert!(!sig.matches(&build_b));
+
+    // only aligned addresses are searched
+    build_b.insert(0, 0);
+    assert_eq!(sig.scan(&build_b, 0x1fff).collect::<Vec<_>>(), [0x2004]);
+    assert_eq!(sig.scan(&build_b[..20], 0x1fff).count(), 0);
+
+    // addresses wrap around the top of the address space
+    let top = Signature::new(&bytes(&BUILD_A), u64::MAX - 7, Wildcards::default());
+    assert_eq!(top.to_string(), sig.to_string());
+    assert_eq!(
+        sig.scan(&build_b[1..], u64::MAX - 3).collect::<Vec<_>>(),
+        [0]
+    );
+
+    // with other registers
+    let build_c = bytes(&[
+        0xb0000042, // adrp x2, 0xa000
+        0x91012042, // add x2, x2, #0x48
+        0x94000200, // bl 0x1800
+        0xf9400fe3, // ldr x3, [sp, #0x18]
+        0xd65f03c0, // ret
+    ]);
+    assert_eq!(sig.scan(&build_c, 0).count(), 0);
+
+    let wildcards = Wildcards {
+        registers: true,
+        ..Wildcards::default()
+    };
+    let sig = Signature::new(&bytes(&BUILD_A), 0x1000, wildcards);
+    assert_eq!(sig.scan(&build_c, 0).collect::<Vec<_>>(), [0]);
+
+    // labels alone keep the offsets
+    let wildcards = Wildcards {
+        immediates: false,
+        ..Wildcards::default()
+    };
+    let sig = Signature::new(&bytes(&BUILD_A), 0x1000, wildcards);
+    assert_eq!(sig.scan(&build_b, 0x1fff).count(), 0);
+}
+
+#[test]
+fn signature_parse() {
+    let mut code = bytes(&BUILD_A);
+    code.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xaa]);
+
+    // the word which fails to decode is matched exactly, the byte after ignored
+    let sig = Signature::new(&code, 0x1000, Wildcards::default());
+    assert_eq!(sig.words().len(), 6);
+    assert_eq!(sig.words()[5], (0xffffffff, 0xffffffff));
+
+    let parsed: Signature = sig.to_string().parse().unwrap();
+    assert_eq!(parsed, sig);
+
+    assert_eq!("".parse::<Signature>(), Ok(Signature::default()));
+    assert!(Signature::default().matches(&[]));
+
+    assert_eq!(
+        "d65f03c0 d65f03c".parse::<Signature>(),
+        Err(SignatureError::BadWord(1))
+    );
+    assert_eq!(
+        "94000001/fc000000".parse::<Signature>(),
+        Err(SignatureError::BadWord(0))
+    );
+    assert_eq!(
+        "94000000/fc00000g".parse::<Signature>(),
+        Err(SignatureError::BadWord(0))
+    );
+}
@@ -0,0 +1,165 @@
+#![cfg(feature = "alloc")]
+
+mod common;
+
+use bad64::Op;
+use bad64::sweep::*;
+use common::bytes;
+
+// each item as either an op or a data kind, address and size
+fn summary(items: &[Item]) -> Vec<Result<Op, (DataKind, u64, usize)>> {
+    items
+        .iter()
+        .map(|i| match i {
+            Item::Instruction(ins) => Ok(ins.op()),
+            Item::Data(d) => Err((d.kind, d.address, d.bytes.len())),
+        })
+        .collect()
+}
+
+#[test]
+fn sweep_literals() {
+    let mut code = bytes(&[
+        0x58000080, // ldr x0, 0x1010
+        0x980000a1, // ldrsw x1, 0x1018
+        0xd65f03c0, // ret
+        0xd503201f, // nop
+        0x91000400, // .quad, which decodes
+        0xd65f03c0, //
+        0x11223344, // .word
+        0xd65f03c0, // a mapped word
+        0xffffffff, // unallocated
+        0x00000000, // udf #0
+    ]);
+    code.extend_from_slice(&[0xaa, 0xbb]);
+
+    // the second range is outside the code
+    let items = sweep(&code, 0x1000, &[0x101c..0x1020, 0x2000..0x2004]);
+    assert_eq!(
+        summary(&items),
+        [
+            Ok(Op::LDR),
+            Ok(Op::LDRSW),
+            Ok(Op::RET),
+            Err((DataKind::Padding, 0x100c, 4)),
+            Err((DataKind::Literal, 0x1010, 12)),
+            Err((DataKind::Mapped, 0x101c, 4)),
+            Err((DataKind::Invalid, 0x1020, 4)),
+            Err((DataKind::Padding, 0x1024, 4)),
+            Err((DataKind::Invalid, 0x1028, 2)),
+        ]
+    );
+
+    let Item::Data(literal) = &items[4] else {
+        panic!("expected a literal pool");
+    };
+    assert_eq!(literal.bytes, &code[0x10..0x1c]);
+    assert_eq!(items[5].address(), 0x101c);
+
+    // code at the top of the address space wraps around
+    let mut code = bytes(&[
+        0xd65f03c0, // ret
+        0x11223344, // a mapped word
+        0xffffffff, // unallocated
+    ]);
+    code.push(0xaa);
+
+    let items = sweep(&code, u64::MAX - 7, &[u64::MAX - 3..u64::MAX, 0x8..0xc]);
+    assert_eq!(
+        summary(&items),
+        [
+            Ok(Op::RET),
+            Err((DataKind::Mapped, u64::MAX - 3, 4)),
+            Err((DataKind::Invalid, 0, 4)),
+            Err((DataKind::Invalid, 4, 1)),
+        ]
+    );
+}
+
+#[test]
+fn sweep_jump_table() {
+    let mut words = [
+        0x7100083f, // cmp w1, #2
+        0x54000188, // b.hi 0x34
+        0x100000b0, // adr x16, 0x1c
+        0xb8a17a11, // ldrsw x17, [x16, x1, lsl #2]
+        0x8b110210, // add x16, x16, x17
+        0xd61f0200, // br x16
+        0xd503201f, // nop
+        0x00000010, // .word 0x2c - 0x1c
+        0x00000014, // .word 0x30 - 0x1c
+        0x00000018, // .word 0x34 - 0x1c
+        0x00000000, //
+        0x52800020, // mov w0, #1
+        0x52800040, // mov w0, #2
+        0xd65f03c0, // ret
+    ];
+
+    let expected = |first| {
+        [
+            Ok(first),
+            Ok(Op::B_HI),
+            Ok(Op::ADR),
+            Ok(Op::LDRSW),
+            Ok(Op::ADD),
+            Ok(Op::BR),
+            Err((DataKind::Padding, 0x18, 4)),
+            Err((DataKind::JumpTable, 0x1c, 12)),
+            Err((DataKind::Padding, 0x28, 4)),
+            Ok(Op::MOV),
+            Ok(Op::MOV),
+            Ok(Op::RET),
+        ]
+    };
+
+    let code = bytes(&words);
+    assert_eq!(summary(&sweep(&code, 0, &[])), expected(Op::CMP));
+
+    // without the bound, the entries are followed while they look like offsets
+    words[0] = 0xd503201f;
+    let code = bytes(&words);
+    assert_eq!(summary(&sweep(&code, 0, &[])), expected(Op::NOP));
+}
+
+#[test]
+fn sweep_branch_target() {
+    let code = bytes(&[
+        0xb4000060, // cbz x0, 0xc
+        0xd65f03c0, // ret
+        0xd503201f, // nop
+        0xd503201f, // nop, a branch target
+        0xd65f03c0, // ret
+    ]);
+
+    assert_eq!(
+        summary(&sweep(&code, 0, &[])),
+        [
+            Ok(Op::CBZ),
+            Ok(Op::RET),
+            Err((DataKind::Padding, 8, 4)),
+            Ok(Op::NOP),
+            Ok(Op::RET),
+        ]
+    );
+}
+
+#[cfg(feature = "elf")]
+#[test]
+fn sweep_elf() {
+    let elf = bad64::elf::Elf::parse(include_bytes!("elf/funcs.o")).unwrap();
+
+    let load_const = elf
+        .functions()
+        .into_iter()
+        .find(|f| f.name == "load_const")
+        .unwrap();
+    assert_eq!(
+        summary(&load_const.sweep()),
+        [Ok(Op::LDR), Ok(Op::RET), Err((DataKind::Mapped, 0x10, 8))]
+    );
+
+    let text = elf.executable_sections().next().unwrap();
+    let ops = summary(&elf.sweep(text));
+    assert_eq!(ops.len(), 6);
+    assert_eq!(ops[4], Err((DataKind::Mapped, 0x10, 8)));
+}
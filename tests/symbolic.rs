@@ -0,0 +1,113 @@
+#![cfg(feature = "alloc")]
+
+mod common;
+
+use bad64::symbolic::{Expr, State};
+use bad64::*;
+use common::decode_all;
+
+fn text(state: &State, reg: Reg) -> Option<String> {
+    state.value(reg).map(|e| e.to_string())
+}
+
+#[test]
+fn symbolic_constants() {
+    let ins = decode_all(&[
+        0xd2822220, // mov x0, #0x1111
+        0xf2a44440, // movk x0, #0x2222, lsl #16
+        0xf2c66660, // movk x0, #0x3333, lsl #32
+        0xf2e88880, // movk x0, #0x4444, lsl #48
+        0x12800001, // mov w1, #-1
+        0xaa0003f7, // mov x23, x0
+    ]);
+
+    let state = State::analyze(&ins);
+
+    assert_eq!(state.constant(Reg::X0), Some(0x4444_3333_2222_1111));
+    assert_eq!(state.constant(Reg::W0), Some(0x2222_1111));
+    assert_eq!(state.constant(Reg::X1), Some(0xffff_ffff));
+    assert_eq!(state.constant(Reg::X23), Some(0x4444_3333_2222_1111));
+    assert_eq!(state.constant(Reg::XZR), Some(0));
+    assert_eq!(state.constant(Reg::X2), None);
+
+    // writes to w registers zero the upper half
+    let ins = decode_all(&[
+        0x528acf08, // mov w8, #0x5678
+        0x72a24688, // movk w8, #0x1234, lsl #16
+        0x92800009, // mov x9, #-1
+        0x72a00009, // movk w9, #0, lsl #16
+    ]);
+
+    let state = State::analyze(&ins);
+
+    assert_eq!(state.constant(Reg::X8), Some(0x1234_5678));
+    assert_eq!(state.constant(Reg::X9), Some(0xffff));
+}
+
+#[test]
+fn symbolic_inputs() {
+    let ins = decode_all(&[
+        0x11000462, // add w2, w3, #1
+        0xf297dde4, // movk x4, #0xbeef
+        0xb2781e73, // orr x19, x19, #0xff00
+        0xd348fe74, // lsr x20, x19, #8
+        0xcb1603f5, // neg x21, x22
+        0xd10083e7, // sub x7, sp, #0x20
+    ]);
+
+    let state = State::analyze(&ins);
+
+    assert_eq!(
+        text(&state, Reg::X2).as_deref(),
+        Some("(((x3 & 0xffffffff) + 0x1) & 0xffffffff)")
+    );
+    assert_eq!(
+        text(&state, Reg::X4).as_deref(),
+        Some("((x4 & 0xffffffffffff0000) | 0xbeef)")
+    );
+    assert_eq!(
+        text(&state, Reg::X20).as_deref(),
+        Some("((x19 | 0xff00) >> 0x8)")
+    );
+    assert_eq!(text(&state, Reg::X21).as_deref(), Some("(0x0 - x22)"));
+    assert_eq!(
+        text(&state, Reg::X7).as_deref(),
+        Some("(sp + 0xffffffffffffffe0)")
+    );
+
+    let input = |r: Reg| match r {
+        Reg::X19 => Some(0x12),
+        Reg::SP => Some(0x8000),
+        _ => None,
+    };
+    assert_eq!(state.value(Reg::X20).unwrap().eval(input), Some(0xff));
+    assert_eq!(state.value(Reg::X7).unwrap().eval(input), Some(0x7fe0));
+}
+
+#[test]
+fn symbolic_clobbers() {
+    let ins = decode_all(&[
+        0xd2822220, // mov x0, #0x1111
+        0xd2822233, // mov x19, #0x1111
+        0xa9bf7bfd, // stp x29, x30, [sp, #-16]!
+        0xf84084c5, // ldr x5, [x6], #8
+    ]);
+
+    let mut state = State::analyze(&ins);
+
+    assert_eq!(
+        text(&state, Reg::SP).as_deref(),
+        Some("(sp + 0xfffffffffffffff0)")
+    );
+    assert_eq!(text(&state, Reg::X6).as_deref(), Some("(x6 + 0x8)"));
+    assert_eq!(state.value(Reg::X5), None);
+    assert_eq!(state.value(Reg::X29), Some(Expr::Reg(Reg::X29)));
+    assert_eq!(state.constant(Reg::X0), Some(0x1111));
+
+    // bl 0x1110
+    state.step(&decode(0x94000040, 0x1010).unwrap());
+
+    assert_eq!(state.value(Reg::X0), None);
+    assert_eq!(state.value(Reg::X30), None);
+    assert_eq!(state.constant(Reg::X19), Some(0x1111));
+}
@@ -0,0 +1,83 @@
+use bad64::{Alignment, DecodeError, Op, Walk};
+
+const NOP: [u8; 4] = [0x1f, 0x20, 0x03, 0xd5];
+const RET: [u8; 4] = [0xc0, 0x03, 0x5f, 0xd6];
+
+// each step's offset, address, length and op or error
+fn summary(walk: Walk) -> Vec<(usize, u64, usize, Result<Op, DecodeError>)> {
+    walk.map(|(off, addr, bytes, res)| (off, addr, bytes.len(), res.map(|i| i.op())))
+        .collect()
+}
+
+#[test]
+fn walk_single() {
+    let code = [NOP, RET].concat();
+    let short = [NOP.as_slice(), &RET[..2]].concat();
+
+    assert_eq!(
+        summary(Walk::new(&code, 0x1000)),
+        [(0, 0x1000, 4, Ok(Op::NOP)), (4, 0x1004, 4, Ok(Op::RET))]
+    );
+    assert_eq!(
+        summary(Walk::new(&short, 0x1000)),
+        [
+            (0, 0x1000, 4, Ok(Op::NOP)),
+            (4, 0x1004, 2, Err(DecodeError::short(0x1004)))
+        ]
+    );
+
+    // starting part way in keeps offsets relative to the whole slice
+    assert_eq!(
+        summary(Walk::new(&code, 0x1000).start(4)),
+        [(4, 0x1004, 4, Ok(Op::RET))]
+    );
+    assert_eq!(Walk::new(&[], 0).count(), 0);
+}
+
+#[test]
+fn walk_unaligned() {
+    // a ret two bytes in, behind the tail of a nop
+    let code = [&NOP[2..], RET.as_slice()].concat();
+
+    let exact = summary(Walk::new(&code, 0x1002));
+    assert_eq!(exact.len(), 2);
+    assert_eq!(exact[1], (4, 0x1006, 2, Err(DecodeError::short(0x1006))));
+
+    assert_eq!(
+        summary(Walk::new(&code, 0x1002).alignment(Alignment::Align)),
+        [
+            (0, 0x1002, 2, Err(DecodeError::short(0x1002))),
+            (2, 0x1004, 4, Ok(Op::RET))
+        ]
+    );
+
+    // the same bytes at an aligned address, found by stepping a byte at a time
+    let code = [&[0xff, 0xff], &NOP[2..], RET.as_slice()].concat();
+    let resync = summary(Walk::new(&code, 0x1000).alignment(Alignment::Resync));
+
+    assert!(resync[..4].iter().all(|s| s.2 == 1 && s.3.is_err()));
+    assert_eq!(resync[4], (4, 0x1004, 4, Ok(Op::RET)));
+    assert_eq!(resync.len(), 5);
+}
+
+#[test]
+fn walk_regions() {
+    let code = [NOP, RET].concat();
+    let regions = [
+        (0x1000, code.as_slice()),
+        (0x2000, &[][..]),
+        (0x8000, &RET[..3]),
+        (0x9000, &RET[..]),
+    ];
+
+    assert_eq!(
+        summary(Walk::regions(&regions)),
+        [
+            (0, 0x1000, 4, Ok(Op::NOP)),
+            (4, 0x1004, 4, Ok(Op::RET)),
+            (0, 0x8000, 3, Err(DecodeError::short(0x8000))),
+            (0, 0x9000, 4, Ok(Op::RET)),
+        ]
+    );
+    assert_eq!(Walk::regions(&[]).count(), 0);
+}